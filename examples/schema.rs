@@ -3,7 +3,9 @@ use std::fs::create_dir_all;
 
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
-use secret_contract::msg::{HandleMsg, HandleResult, InitMsg, QueryMsg, QueryResult, Status};
+use secret_contract::msg::{
+    HandleMsg, HandleResult, InitMsg, Parameters, QueryMsg, QueryResult, Status,
+};
 
 fn main() {
     let mut out_dir = current_dir().unwrap();
@@ -17,4 +19,5 @@ fn main() {
     export_schema(&schema_for!(HandleResult), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
     export_schema(&schema_for!(QueryResult), &out_dir);
+    export_schema(&schema_for!(Parameters), &out_dir);
 }
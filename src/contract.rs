@@ -1,29 +1,171 @@
 use cosmwasm_std::{
-    to_binary, Api, Env, Extern, HandleResponse, HumanAddr, InitResponse, Querier, QueryResponse,
-    StdError, StdResult, Storage, Uint128,
+    log, to_binary, Api, BankMsg, Binary, CanonicalAddr, Coin, CosmosMsg, Env, Extern,
+    HandleResponse, HumanAddr, InitResponse, Querier, QueryRequest, QueryResponse, StdError,
+    StdResult, Storage, Uint128, WasmQuery,
 };
 
-use crate::msg::{HandleMsg, HandleResult, InitMsg, QueryMsg, QueryResult, Status};
+use sha2::{Digest, Sha256};
+
+use crate::msg::{
+    AllowancePair, BurnAction, FormattedAmount, GuardQueryMsg, GuardResponse, HandleMsg,
+    HandleResult, HandlerId, InitMsg, MinterInfo, OracleQueryMsg, Parameters, PriceOracleInfo,
+    PriceResponse, QueryMsg, QueryResult, RewardPoolInfo, Rounding, SchemaVersion, Status,
+    SymbolRegistryInfo, TransferGuardInfo,
+};
 use crate::state::{
-    get_allowance, set_allowance, Allowance, Balances, Constants, ContractStorage,
-    ReadOnlyBalances, ReadOnlyContractStorage,
+    add_escrow, add_pending_transfer, allowance_count, apply_atomic, dry_run, get_allowance,
+    get_allowance_entry, get_allowance_spenders, get_burn_allowance, get_claim_code,
+    get_default_allowance_expiry, get_escrow, get_fallback_recipient, get_first_received_height,
+    get_guardian, get_large_transfer_threshold, get_queued_transfers, get_receive_policy,
+    get_recovery, get_reward_debt, get_tag_accounts, is_locked, is_registered, is_seen, mark_seen,
+    next_tx_index, register_account, remove_allowance, remove_escrow, set_account_tag,
+    set_allowance, set_burn_allowance, set_claim_code, set_default_allowance_expiry,
+    set_fallback_recipient, set_guardian, set_large_transfer_threshold, set_locked,
+    set_queued_transfers, set_receive_policy, set_recovery, set_reward_debt, take_pending_transfer,
+    total_escrowed, Allowance, Balances, ClaimCode, Constants, ContractStorage, Escrow, Minter,
+    PendingTransfer, PriceOracle, ReadOnlyBalances, ReadOnlyContractStorage, ReceivePolicy,
+    Recovery, RewardDebt, RewardPool, TokenAmount, TransferGuard, KEY_CONSTANTS, KEY_TOTAL_SUPPLY,
 };
 
+/// Symbols of well known tokens that can not be used
+/// to prevent impersonation. Owners extend the list with ReserveSymbols
+/// for tokens using their contract as the symbol registry.
+pub const RESERVED_SYMBOLS: &[&str] = &["SCRT", "SSCRT", "BTC", "ETH", "USDC", "USDT", "DAI"];
+
+/// Maximum length of the transfer log note.
+pub const MAX_LOG_NOTE_LEN: usize = 256;
+
+/// Default number of blocks between initiating and finalizing account recovery.
+pub const DEFAULT_RECOVERY_DELAY: u64 = 100_000;
+
+/// Maximum number of pairs in the allowances batch query.
+pub const MAX_ALLOWANCES_BATCH: usize = 64;
+
+/// Upper bound and default for the number of actions in batch handlers.
+pub const MAX_BATCH_SIZE: u32 = 100;
+
+/// Upper bound and default for the number of entries scanned by paginated queries.
+pub const MAX_PAGE_LIMIT: u32 = 100;
+
+/// Maximum length of the metadata URI.
+pub const MAX_METADATA_URI_LEN: usize = 256;
+
+/// URI schemes accepted for the metadata URI.
+pub const METADATA_URI_SCHEMES: &[&str] = &["https://", "ipfs://", "ar://"];
+
+/// Message schema version stored at init.
+pub const SCHEMA_VERSION: SchemaVersion = SchemaVersion { major: 1, minor: 0 };
+
+/// Scale of the rewards per token accumulator.
+pub const REWARD_PRECISION: u128 = 1_000_000_000_000;
+
 pub fn init<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     msg: InitMsg,
 ) -> StdResult<InitResponse> {
+    let reserved = is_reserved_symbol(&[], &msg.symbol)
+        || match &msg.symbol_registry {
+            Some(registry) => query_symbol_registry(deps, registry, &msg.symbol)?,
+            None => false,
+        };
+    if reserved {
+        return Err(StdError::generic_err(format!(
+            "ReservedSymbol: symbol {} is reserved",
+            msg.symbol
+        )));
+    }
+
+    let max_batch_size = msg.max_batch_size.unwrap_or(MAX_BATCH_SIZE);
+    if max_batch_size > MAX_BATCH_SIZE {
+        return Err(StdError::generic_err(format!(
+            "Max batch size can not exceed {}",
+            MAX_BATCH_SIZE
+        )));
+    }
+
+    let symbol = if msg.normalize_symbol.unwrap_or(true) {
+        msg.symbol.to_uppercase()
+    } else {
+        msg.symbol
+    };
+
+    let burn_dust_threshold = match (msg.burn_sweeps_dust, msg.dust_threshold) {
+        (true, Some(threshold)) => Some(TokenAmount::from(threshold)),
+        (true, None) => {
+            return Err(StdError::generic_err(
+                "Dust threshold is required to sweep dust on burn",
+            ))
+        }
+        (false, _) => None,
+    };
+
     let constants = Constants {
         name: msg.name,
-        symbol: msg.symbol,
+        symbol,
         decimals: msg.decimals,
+        token_id: msg.token_id,
         owner: deps.api.canonical_address(&env.message.sender)?,
+        contract_address: deps.api.canonical_address(&env.contract.address)?,
+        reserve: msg.reserve,
+        verbose_logging: msg.verbose_logging,
+        recovery_delay: msg.recovery_delay.unwrap_or(DEFAULT_RECOVERY_DELAY),
+        max_batch_size,
+        metadata_uri: None,
+        reject_value_above_supply: msg.reject_value_above_supply,
+        created_height: env.block.height,
+        created_time: env.block.time,
+        max_wallet_balance: msg.max_wallet_balance.map(TokenAmount::from),
+        treat_zero_address_as_burn: msg.treat_zero_address_as_burn,
+        metadata_frozen: msg.metadata_frozen,
+        require_registration: msg.require_registration,
+        mint_admin: None,
+        default_allowance_expiry: msg.default_allowance_expiry,
+        min_holding_period: msg.min_holding_period,
+        zero_approve_revokes: msg.zero_approve_revokes,
+        clamp_transfer_from: msg.clamp_transfer_from,
+        burn_dust_threshold,
+        price_oracle: match msg.price_oracle {
+            Some(oracle) => Some(PriceOracle {
+                contract: deps.api.canonical_address(&oracle.address)?,
+                code_hash: oracle.code_hash,
+            }),
+            None => None,
+        },
+        min_total_supply: msg.min_total_supply.into(),
+        emit_sequence: msg.emit_sequence,
     };
 
     let mut storage = ContractStorage::from_storage(&mut deps.storage);
     storage.set_constants(&constants)?;
-    storage.set_total_supply(0)?;
+    storage.set_total_supply(TokenAmount(0))?;
+    storage.set_disabled_handlers(&[])?;
+    storage.set_schema_version(&SCHEMA_VERSION)?;
+
+    let mut pairs = Vec::with_capacity(msg.initial_allowances.len());
+    for initial in msg.initial_allowances {
+        let owner = deps.api.canonical_address(&initial.owner)?;
+        let spender = deps.api.canonical_address(&initial.spender)?;
+        if owner == spender {
+            return Err(StdError::generic_err("Can not approve to self"));
+        }
+        if pairs.contains(&(owner.clone(), spender.clone())) {
+            return Err(StdError::generic_err(format!(
+                "Duplicate initial allowance from {} to {}",
+                initial.owner, initial.spender
+            )));
+        }
+        set_allowance(
+            &mut deps.storage,
+            &owner,
+            &spender,
+            Allowance {
+                amount: initial.amount.into(),
+                expiration: None,
+            },
+        )?;
+        pairs.push((owner, spender));
+    }
 
     Ok(InitResponse::default())
 }
@@ -32,14 +174,136 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     msg: HandleMsg,
+) -> StdResult<HandleResponse> {
+    if let Some(id) = msg.handler_id() {
+        let storage = ReadOnlyContractStorage::from_storage(&deps.storage);
+        if storage.disabled_handlers()?.contains(&id) {
+            return Err(StdError::generic_err(format!(
+                "HandlerDisabled: {:?} handler is disabled",
+                id
+            )));
+        }
+    }
+
+    let read_only = msg.is_read_only();
+    let mut res = dispatch(deps, env, msg)?;
+
+    let mut storage = ContractStorage::from_storage(&mut deps.storage);
+    if !read_only && storage.constants()?.emit_sequence {
+        let sequence = storage.sequence()? + 1;
+        storage.set_sequence(sequence)?;
+        res.log.push(log("sequence", sequence));
+    }
+    Ok(res)
+}
+
+fn dispatch<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    msg: HandleMsg,
 ) -> StdResult<HandleResponse> {
     match msg {
         HandleMsg::DepositTo { to, value } => deposit_to(deps, env, to, value),
         HandleMsg::BurnFrom { from, value } => burn_from(deps, env, from, value),
-        HandleMsg::Transfer { to, value } => transfer(deps, env, to, value),
-        HandleMsg::TransferFrom { from, to, value } => transfer_from(deps, env, from, to, value),
-        HandleMsg::Approve { spender, value } => approve(deps, env, spender, value),
+        HandleMsg::Transfer {
+            to,
+            value,
+            log_note,
+            deadline,
+        } => transfer(deps, env, to, value, log_note, deadline),
+        HandleMsg::TransferFrom {
+            from,
+            to,
+            value,
+            deadline,
+        } => transfer_from(deps, env, from, to, value, deadline),
+        HandleMsg::Approve {
+            spender,
+            value,
+            expiration,
+        } => approve(deps, env, spender, value, expiration),
         HandleMsg::Allowance { owner, spender } => allowance(deps, owner, spender),
+        HandleMsg::SetDisabledHandlers { handlers } => set_disabled_handlers(deps, env, handlers),
+        HandleMsg::BatchBurn { actions } => batch_burn(deps, env, actions),
+        HandleMsg::ReassignAllowance {
+            from_spender,
+            to_spender,
+            value,
+        } => reassign_allowance(deps, env, from_spender, to_spender, value),
+        HandleMsg::SetRewardPool {
+            pool,
+            max_reward_bps,
+        } => set_reward_pool(deps, env, pool, max_reward_bps),
+        HandleMsg::TransferWithReward {
+            to,
+            value,
+            reward_bps,
+        } => transfer_with_reward(deps, env, to, value, reward_bps),
+        HandleMsg::SetGuardian { guardian } => set_account_guardian(deps, env, guardian),
+        HandleMsg::InitiateRecovery { account, to } => initiate_recovery(deps, env, account, to),
+        HandleMsg::CancelRecovery {} => cancel_recovery(deps, env),
+        HandleMsg::FinalizeRecovery { account } => finalize_recovery(deps, env, account),
+        HandleMsg::SetMinters { minters } => set_minters(deps, env, minters),
+        HandleMsg::SetLargeTransferThreshold { threshold } => {
+            set_account_large_transfer_threshold(deps, env, threshold)
+        }
+        HandleMsg::ProposeLargeTransfer { to, value } => {
+            propose_large_transfer(deps, env, to, value)
+        }
+        HandleMsg::ConfirmLargeTransfer { id } => confirm_large_transfer(deps, env, id),
+        HandleMsg::SetReceivePolicy { allowed_senders } => {
+            set_account_receive_policy(deps, env, allowed_senders)
+        }
+        #[cfg(feature = "debug")]
+        HandleMsg::DebugSetBalance { address, value } => {
+            debug_set_balance(deps, env, address, value)
+        }
+        HandleMsg::ApproveBurn {
+            spender,
+            value,
+            expiration,
+        } => approve_burn(deps, env, spender, value, expiration),
+        HandleMsg::RenounceOwnership { confirm } => renounce_ownership(deps, env, confirm),
+        HandleMsg::QueueTransfer { to, value } => queue_transfer(deps, env, to, value),
+        HandleMsg::SettleTransfers {} => settle_transfers(deps, env),
+        HandleMsg::SetMetadata { metadata_uri } => set_metadata(deps, env, metadata_uri),
+        HandleMsg::AdjustAllowance { spender, delta } => {
+            adjust_allowance(deps, env, spender, delta)
+        }
+        HandleMsg::SetTransferGuard { guard } => set_transfer_guard(deps, env, guard),
+        HandleMsg::DistributeRewards { amount } => distribute_rewards(deps, env, amount),
+        HandleMsg::SwapBalances { a, b } => swap_balances(deps, env, a, b),
+        HandleMsg::RegisterAccount {} => register(deps, env),
+        HandleMsg::SetMintAdmin { mint_admin } => set_mint_admin(deps, env, mint_admin),
+        HandleMsg::SetDefaultAllowanceExpiry { blocks } => {
+            set_account_default_allowance_expiry(deps, env, blocks)
+        }
+        HandleMsg::ForceRedeemAll { accounts } => force_redeem_all(deps, env, accounts),
+        HandleMsg::RegisterClaimCode { code, amount } => {
+            register_claim_code(deps, env, code, amount)
+        }
+        HandleMsg::ClaimWithCode { code } => claim_with_code(deps, env, code),
+        HandleMsg::LockMyAccount {} => set_account_locked(deps, env, true),
+        HandleMsg::UnlockMyAccount {} => set_account_locked(deps, env, false),
+        HandleMsg::SetFallbackRecipient { fallback } => {
+            set_account_fallback_recipient(deps, env, fallback)
+        }
+        HandleMsg::MintAndApprove {
+            to,
+            mint_value,
+            spender,
+            approve_value,
+        } => mint_and_approve(deps, env, to, mint_value, spender, approve_value),
+        HandleMsg::CreateEscrow {
+            recipient,
+            value,
+            release_height,
+        } => create_escrow(deps, env, recipient, value, release_height),
+        HandleMsg::ReleaseEscrow { id } => release_escrow(deps, env, id),
+        HandleMsg::RefundEscrow { id } => refund_escrow(deps, env, id),
+        HandleMsg::SetAccountTag { account, tag } => set_tag(deps, env, account, tag),
+        HandleMsg::ReserveSymbols { symbols } => reserve_symbols(deps, env, symbols),
+        HandleMsg::SetOwnerQueryKey { key } => set_owner_query_key(deps, env, key),
     }
 }
 
@@ -53,36 +317,148 @@ fn deposit_to<S: Storage, A: Api, Q: Querier>(
         return Err(StdError::generic_err("Can not deposit zero tokens"));
     }
 
-    let mut storage = ContractStorage::from_storage(&mut deps.storage);
-
     let sender = deps.api.canonical_address(&env.message.sender)?;
-    let contract_owner = storage.constants()?.owner;
-    if sender != contract_owner {
+    if !can_mint(&deps.storage, &sender, env.block.height)? {
         return Err(StdError::generic_err(
-            "Only contract owner can deposit tokens",
+            "Only contract owner or minters can deposit tokens",
         ));
     }
 
+    let account_owner = deps.api.canonical_address(&to)?;
+    mint_tokens(deps, &account_owner, value.into(), env.block.height)?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::DepositTo {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+/// Returns whether `account` is the owner or a minter not expired at `height`.
+fn can_mint<S: Storage>(storage: &S, account: &CanonicalAddr, height: u64) -> StdResult<bool> {
+    let storage = ReadOnlyContractStorage::from_storage(storage);
+    Ok(*account == storage.constants()?.owner
+        || storage
+            .minters()?
+            .iter()
+            .any(|minter| minter.address == *account && !minter.is_expired(height)))
+}
+
+/// Adds `value` tokens to `account` balance and total supply at block `height`,
+/// checking native reserves if the token is backed by them.
+fn mint_tokens<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    account: &CanonicalAddr,
+    value: TokenAmount,
+    height: u64,
+) -> StdResult<()> {
+    let mut storage = ContractStorage::from_storage(&mut deps.storage);
+    let constants = storage.constants()?;
+    if constants.owner.is_empty() {
+        return Err(StdError::generic_err(
+            "MintingDisabled: ownership was renounced",
+        ));
+    }
     let total_supply = storage.total_supply()?;
-    if let Some(new_total) = total_supply.checked_add(value.u128()) {
+    if let Some(new_total) = total_supply.checked_add(value) {
+        if let Some(reserve) = constants.reserve {
+            let contract_address = deps.api.human_address(&constants.contract_address)?;
+            let reserves = deps
+                .querier
+                .query_balance(contract_address, &reserve.denom)?
+                .amount;
+            let required = new_total
+                .u128()
+                .checked_mul(reserve.backing_ratio_bps as u128)
+                .ok_or_else(|| StdError::generic_err("Required reserves overflow"))?
+                / 10_000;
+            if reserves.u128() < required {
+                return Err(StdError::generic_err(format!(
+                    "Undercollateralized: {} {} reserves required",
+                    required, reserve.denom
+                )));
+            }
+        }
         storage.set_total_supply(new_total)?;
+        let total_minted = storage.total_minted()?.saturating_add(value);
+        storage.set_total_minted(total_minted)?;
     } else {
         return Err(StdError::generic_err("Total supply overflow"));
     }
 
-    let account_owner = deps.api.canonical_address(&to)?;
-    let mut balances = Balances::from_storage(&mut deps.storage);
-    let sender_balance = balances.balance(&account_owner);
-    if let Some(new_balance) = sender_balance.checked_add(value.u128()) {
-        balances.set_balance(&account_owner, new_balance);
+    update_rewards(&mut deps.storage, account)?;
+    let balances = Balances::from_storage(&mut deps.storage);
+    let account_balance = balances.balance(account);
+    if let Some(new_balance) = account_balance.checked_add(value) {
+        check_max_wallet_balance(&deps.storage, account, new_balance)?;
+        let mut balances = Balances::from_storage(&mut deps.storage);
+        balances.set_balance(account, new_balance);
     } else {
         return Err(StdError::generic_err("Account balance overflow"));
     }
+    mark_seen(&mut deps.storage, account, height);
+    Ok(())
+}
+
+/// Mints `mint_value` to `to` and approves `spender` to spend `approve_value` of it.
+/// Minters can only approve from their own account, the owner from any.
+fn mint_and_approve<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    to: HumanAddr,
+    mint_value: Uint128,
+    spender: HumanAddr,
+    approve_value: Uint128,
+) -> StdResult<HandleResponse> {
+    if mint_value.is_zero() {
+        return Err(StdError::generic_err("Can not deposit zero tokens"));
+    }
+    if approve_value.is_zero() {
+        return Err(StdError::generic_err("Can not approve zero tokens"));
+    }
+
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    if !can_mint(&deps.storage, &sender, env.block.height)? {
+        return Err(StdError::generic_err(
+            "Only contract owner or minters can deposit tokens",
+        ));
+    }
+
+    let account_owner = deps.api.canonical_address(&to)?;
+    let spender = deps.api.canonical_address(&spender)?;
+    let contract_owner = ReadOnlyContractStorage::from_storage(&deps.storage)
+        .constants()?
+        .owner;
+    if account_owner != sender && sender != contract_owner {
+        return Err(StdError::generic_err(
+            "Only contract owner can approve on behalf of another account",
+        ));
+    }
+    if account_owner == spender {
+        return Err(StdError::generic_err("Can not approve to self"));
+    }
+    check_not_locked(&deps.storage, &account_owner)?;
+
+    mint_tokens(deps, &account_owner, mint_value.into(), env.block.height)?;
+    let expiration = default_allowance_expiry(&deps.storage, &account_owner)?
+        .map(|blocks| env.block.height.saturating_add(blocks));
+    set_allowance(
+        &mut deps.storage,
+        &account_owner,
+        &spender,
+        Allowance {
+            amount: approve_value.into(),
+            expiration,
+        },
+    )?;
 
     let res = HandleResponse {
         messages: vec![],
         log: vec![],
-        data: Some(to_binary(&HandleResult::DepositTo {
+        data: Some(to_binary(&HandleResult::MintAndApprove {
             status: Status::Success,
         })?),
     };
@@ -101,31 +477,29 @@ fn burn_from<S: Storage, A: Api, Q: Querier>(
 
     let sender = deps.api.canonical_address(&env.message.sender)?;
     let account_owner = deps.api.canonical_address(&from)?;
-    {
-        let storage = ReadOnlyContractStorage::from_storage(&deps.storage);
-        let contract_owner = storage.constants()?.owner;
-        if sender != contract_owner && sender != account_owner {
-            return Err(StdError::generic_err(
-                "Only contract owner or account owner can burn tokens",
-            ));
+    let contract_owner = ReadOnlyContractStorage::from_storage(&deps.storage)
+        .constants()?
+        .owner;
+    if sender != contract_owner && sender != account_owner {
+        let allowance = get_burn_allowance(&deps.storage, &account_owner, &sender)?;
+        if allowance.is_expired(env.block.height) {
+            return Err(StdError::generic_err("Burn allowance expired"));
+        }
+        match allowance.amount.checked_sub(value.into()) {
+            Some(amount) => set_burn_allowance(
+                &mut deps.storage,
+                &account_owner,
+                &sender,
+                Allowance {
+                    amount,
+                    ..allowance
+                },
+            )?,
+            None => return Err(StdError::generic_err("Not enough burn allowance")),
         }
     }
 
-    let mut balances = Balances::from_storage(&mut deps.storage);
-    let sender_balance = balances.balance(&account_owner);
-    if let Some(new_balance) = sender_balance.checked_sub(value.u128()) {
-        balances.set_balance(&account_owner, new_balance);
-    } else {
-        return Err(StdError::generic_err("Account balance underflow"));
-    }
-
-    let mut storage = ContractStorage::from_storage(&mut deps.storage);
-    let total_supply = storage.total_supply()?;
-    if let Some(new_total) = total_supply.checked_sub(value.u128()) {
-        storage.set_total_supply(new_total)?;
-    } else {
-        return Err(StdError::generic_err("Total supply underflow"));
-    }
+    burn_tokens(&mut deps.storage, &account_owner, value.into())?;
 
     let res = HandleResponse {
         messages: vec![],
@@ -137,42 +511,477 @@ fn burn_from<S: Storage, A: Api, Q: Querier>(
     Ok(res)
 }
 
+/// Removes `value` tokens from `account` balance and total supply,
+/// together with the remaining balance if it is below the dust threshold.
+fn burn_tokens<S: Storage>(
+    storage: &mut S,
+    account: &CanonicalAddr,
+    value: TokenAmount,
+) -> StdResult<()> {
+    update_rewards(storage, account)?;
+    let dust_threshold = ReadOnlyContractStorage::from_storage(&*storage)
+        .constants()?
+        .burn_dust_threshold;
+    let mut balances = Balances::from_storage(storage);
+    let account_balance = balances.balance(account);
+    let value = match account_balance.checked_sub(value) {
+        // remainder below the dust threshold is burned as well
+        Some(new_balance) if matches!(dust_threshold, Some(dust) if new_balance < dust) => {
+            balances.set_balance(account, TokenAmount(0));
+            account_balance
+        }
+        Some(new_balance) => {
+            balances.set_balance(account, new_balance);
+            value
+        }
+        None => return Err(StdError::generic_err("Account balance underflow")),
+    };
+
+    let mut storage = ContractStorage::from_storage(storage);
+    let total_supply = storage.total_supply()?;
+    if let Some(new_total) = total_supply.checked_sub(value) {
+        check_min_total_supply(&storage.constants()?, new_total)?;
+        storage.set_total_supply(new_total)?;
+        let total_burned = storage.total_burned()?.saturating_add(value);
+        storage.set_total_burned(total_burned)
+    } else {
+        Err(StdError::generic_err("Total supply underflow"))
+    }
+}
+
+/// Rejects burns leaving total supply below the configured floor.
+fn check_min_total_supply(constants: &Constants, total_supply: TokenAmount) -> StdResult<()> {
+    if total_supply < constants.min_total_supply {
+        return Err(StdError::generic_err(format!(
+            "BelowMinimumSupply: total supply can not go below {}",
+            constants.min_total_supply.u128()
+        )));
+    }
+    Ok(())
+}
+
 fn transfer<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     to: HumanAddr,
     value: Uint128,
+    log_note: Option<String>,
+    deadline: Option<u64>,
 ) -> StdResult<HandleResponse> {
+    check_deadline(env.block.height, deadline)?;
+
+    if let Some(note) = &log_note {
+        if note.len() > MAX_LOG_NOTE_LEN {
+            return Err(StdError::generic_err(format!(
+                "Log note can not be longer than {} bytes",
+                MAX_LOG_NOTE_LEN
+            )));
+        }
+    }
+
+    let (sender, target) =
+        check_transfer_target(deps, &env.message.sender, &to, value, env.block.height)?;
+
+    let mut logs: Vec<_> = log_note.into_iter().map(|note| log("note", note)).collect();
+    match target {
+        TransferTarget::Burn => burn_tokens(&mut deps.storage, &sender, value.into())?,
+        TransferTarget::Account(recipient) => move_tokens(
+            &mut deps.storage,
+            &sender,
+            &recipient,
+            value.into(),
+            env.block.height,
+        )?,
+        TransferTarget::Fallback(fallback) => {
+            logs.push(log("redirected_to", deps.api.human_address(&fallback)?));
+            move_tokens(
+                &mut deps.storage,
+                &sender,
+                &fallback,
+                value.into(),
+                env.block.height,
+            )?;
+        }
+    }
+
+    if !env.message.sent_funds.is_empty() {
+        record_tips(&mut deps.storage, &env.message.sent_funds)?;
+    }
+
+    let tx_index = next_tx_index(&mut deps.storage, &sender)?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: logs,
+        data: Some(to_binary(&HandleResult::Transfer {
+            status: Status::Success,
+            block_height: env.block.height,
+            tx_index,
+        })?),
+    };
+    Ok(res)
+}
+
+/// Where Transfer puts the tokens.
+enum TransferTarget {
+    Burn,
+    Account(CanonicalAddr),
+    /// Fallback recipient of an account refusing the transfer
+    Fallback(CanonicalAddr),
+}
+
+/// Runs the checks Transfer of `value` from `from` to `to` at `height` makes
+/// before moving any tokens. Returns the canonical sender and the target.
+fn check_transfer_target<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    from: &HumanAddr,
+    to: &HumanAddr,
+    value: Uint128,
+    height: u64,
+) -> StdResult<(CanonicalAddr, TransferTarget)> {
     if value.is_zero() {
         return Err(StdError::generic_err("Can not transfer zero tokens"));
     }
+    check_plausible_amount(&deps.storage, value)?;
 
-    let sender = deps.api.canonical_address(&env.message.sender)?;
-    let recipient = deps.api.canonical_address(&to)?;
+    let sender = deps.api.canonical_address(from)?;
+    check_not_locked(&deps.storage, &sender)?;
+    let recipient = deps.api.canonical_address(to)?;
 
     if sender == recipient {
         return Err(StdError::generic_err("Can not sent tokens to self"));
     }
 
-    let mut balances = Balances::from_storage(&mut deps.storage);
-    let sender_balance = balances.balance(&sender);
-    let recipient_balance = balances.balance(&recipient);
-    if let Some(new_sender_balance) = sender_balance.checked_sub(value.u128()) {
-        if let Some(new_recipient_balance) = recipient_balance.checked_add(value.u128()) {
-            balances.set_balance(&sender, new_sender_balance);
-            balances.set_balance(&recipient, new_recipient_balance);
+    check_large_transfer_threshold(&deps.storage, &sender, value.into())?;
+    check_holding_period(&deps.storage, &sender, height)?;
+    check_transfer_guard(deps, from, to, value)?;
+
+    let treat_zero_address_as_burn = ReadOnlyContractStorage::from_storage(&deps.storage)
+        .constants()?
+        .treat_zero_address_as_burn;
+    if treat_zero_address_as_burn && recipient.as_slice().iter().all(|byte| *byte == 0) {
+        return Ok((sender, TransferTarget::Burn));
+    }
+
+    let target = match check_recipient_accepts(&deps.storage, &sender, &recipient) {
+        Ok(()) => TransferTarget::Account(recipient),
+        // refused transfers fail in move_tokens unless there is a fallback
+        Err(_) => match get_fallback_recipient(&deps.storage, &recipient)? {
+            Some(fallback) if fallback != sender => TransferTarget::Fallback(fallback),
+            _ => TransferTarget::Account(recipient),
+        },
+    };
+    Ok((sender, target))
+}
+
+fn check_deadline(height: u64, deadline: Option<u64>) -> StdResult<()> {
+    match deadline {
+        Some(deadline) if height > deadline => Err(StdError::generic_err(format!(
+            "Expired: transfer deadline {} has passed",
+            deadline
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Rejects `value` above total supply if the contract is configured to.
+fn check_plausible_amount<S: Storage>(storage: &S, value: Uint128) -> StdResult<()> {
+    let storage = ReadOnlyContractStorage::from_storage(storage);
+    if storage.constants()?.reject_value_above_supply && storage.total_supply()? < value.into() {
+        return Err(StdError::generic_err(
+            "ImplausibleAmount: value is above total supply",
+        ));
+    }
+    Ok(())
+}
+
+/// Adds native funds attached to a transfer to the contract tip ledger.
+fn record_tips<S: Storage>(storage: &mut S, funds: &[Coin]) -> StdResult<()> {
+    let mut storage = ContractStorage::from_storage(storage);
+    let mut tips = storage.tips()?;
+    for fund in funds {
+        match tips.iter_mut().find(|tip| tip.denom == fund.denom) {
+            Some(tip) => {
+                tip.amount = Uint128(
+                    tip.amount
+                        .u128()
+                        .checked_add(fund.amount.u128())
+                        .ok_or_else(|| StdError::generic_err("Tip amount overflow"))?,
+                )
+            }
+            None => tips.push(fund.clone()),
+        }
+    }
+    storage.set_tips(&tips)
+}
+
+/// Moves rewards pending for the current `account` balance to its accrued rewards.
+/// Must be called before every change of the account balance.
+fn update_rewards<S: Storage>(storage: &mut S, account: &CanonicalAddr) -> StdResult<()> {
+    let reward_per_token = ReadOnlyContractStorage::from_storage(&*storage).reward_per_token()?;
+    let debt = get_reward_debt(&*storage, account)?;
+    if debt.reward_per_token == reward_per_token {
+        return Ok(());
+    }
+    let balance = ReadOnlyBalances::from_storage(&*storage).balance(account);
+    let accrued = pending_rewards(balance, reward_per_token, &debt)?;
+    set_reward_debt(
+        storage,
+        account,
+        &RewardDebt {
+            reward_per_token,
+            accrued,
+        },
+    )
+}
+
+/// Rewards accrued by the account with `balance` held since the `debt` snapshot.
+fn pending_rewards(
+    balance: TokenAmount,
+    reward_per_token: u128,
+    debt: &RewardDebt,
+) -> StdResult<TokenAmount> {
+    let pending = balance
+        .u128()
+        .checked_mul(reward_per_token - debt.reward_per_token)
+        .ok_or_else(|| StdError::generic_err("Rewards overflow"))?
+        / REWARD_PRECISION;
+    debt.accrued
+        .checked_add(TokenAmount(pending))
+        .ok_or_else(|| StdError::generic_err("Rewards overflow"))
+}
+
+/// Moves `value` tokens from `sender` to `recipient` balance at block `height`.
+fn move_tokens<S: Storage>(
+    storage: &mut S,
+    sender: &CanonicalAddr,
+    recipient: &CanonicalAddr,
+    value: TokenAmount,
+    height: u64,
+) -> StdResult<()> {
+    check_recipient_accepts(&*storage, sender, recipient)?;
+    update_rewards(storage, sender)?;
+    update_rewards(storage, recipient)?;
+
+    let balances = Balances::from_storage(storage);
+    let sender_balance = balances.balance(sender);
+    let recipient_balance = balances.balance(recipient);
+    if let Some(new_sender_balance) = sender_balance.checked_sub(value) {
+        if let Some(new_recipient_balance) = recipient_balance.checked_add(value) {
+            check_max_wallet_balance(&*storage, recipient, new_recipient_balance)?;
+            let mut balances = Balances::from_storage(storage);
+            balances.set_balance(sender, new_sender_balance);
+            balances.set_balance(recipient, new_recipient_balance);
         } else {
             return Err(StdError::generic_err("Recipient balance overflow"));
         }
     } else {
         return Err(StdError::generic_err("Sender balance underflow"));
     }
+    mark_seen(storage, recipient, height);
+    Ok(())
+}
+
+/// Rejects `balance` above the maximum wallet balance
+/// unless `account` is the owner or the reward pool.
+fn check_max_wallet_balance<S: Storage>(
+    storage: &S,
+    account: &CanonicalAddr,
+    balance: TokenAmount,
+) -> StdResult<()> {
+    let storage = ReadOnlyContractStorage::from_storage(storage);
+    let constants = storage.constants()?;
+    match constants.max_wallet_balance {
+        Some(max) if balance > max && *account != constants.owner => {
+            match storage.reward_pool()? {
+                Some(pool) if pool.pool == *account => Ok(()),
+                _ => Err(StdError::generic_err(format!(
+                    "Account balance can not exceed {}",
+                    max.u128()
+                ))),
+            }
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Asks the transfer guard contract, if there is one, whether the transfer is allowed.
+fn check_transfer_guard<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    from: &HumanAddr,
+    to: &HumanAddr,
+    value: Uint128,
+) -> StdResult<()> {
+    let guard = match ReadOnlyContractStorage::from_storage(&deps.storage).transfer_guard()? {
+        Some(guard) => guard,
+        None => return Ok(()),
+    };
+
+    let request = QueryRequest::Wasm(WasmQuery::Smart {
+        contract_addr: deps.api.human_address(&guard.contract)?,
+        callback_code_hash: guard.code_hash,
+        msg: to_binary(&GuardQueryMsg::CanTransfer {
+            from: from.clone(),
+            to: to.clone(),
+            value,
+        })?,
+    });
+    match deps.querier.query::<GuardResponse>(&request) {
+        Ok(response) if response.allowed => Ok(()),
+        Ok(_) => Err(StdError::generic_err(
+            "TransferVetoed: transfer guard rejected the transfer",
+        )),
+        Err(_) if guard.fail_open => Ok(()),
+        Err(e) => Err(StdError::generic_err(format!("TransferGuardFailed: {}", e))),
+    }
+}
+
+fn check_recipient_accepts<S: Storage>(
+    storage: &S,
+    sender: &CanonicalAddr,
+    recipient: &CanonicalAddr,
+) -> StdResult<()> {
+    check_registered(storage, recipient)?;
+    if get_receive_policy(storage, recipient)?.accepts(sender) {
+        Ok(())
+    } else {
+        Err(StdError::generic_err(
+            "RecipientRefused: recipient does not accept transfers from sender",
+        ))
+    }
+}
+
+/// Rejects unregistered recipients if the contract requires registration.
+fn check_registered<S: Storage>(storage: &S, recipient: &CanonicalAddr) -> StdResult<()> {
+    let require_registration = ReadOnlyContractStorage::from_storage(storage)
+        .constants()?
+        .require_registration;
+    if require_registration && !is_registered(storage, recipient) {
+        return Err(StdError::generic_err(
+            "RecipientNotRegistered: recipient must register before receiving tokens",
+        ));
+    }
+    Ok(())
+}
+
+/// Rejects outgoing transfers and approvals of the account locked by itself.
+fn check_not_locked<S: Storage>(storage: &S, account: &CanonicalAddr) -> StdResult<()> {
+    if is_locked(storage, account) {
+        return Err(StdError::generic_err(
+            "AccountLocked: account is locked by its owner",
+        ));
+    }
+    Ok(())
+}
+
+/// Rejects transfers from `account` before the minimum holding period
+/// since its first credit has passed, unless it is the owner or the reward pool.
+fn check_holding_period<S: Storage>(
+    storage: &S,
+    account: &CanonicalAddr,
+    height: u64,
+) -> StdResult<()> {
+    let contract_storage = ReadOnlyContractStorage::from_storage(storage);
+    let constants = contract_storage.constants()?;
+    let period = match constants.min_holding_period {
+        Some(period) if *account != constants.owner => period,
+        _ => return Ok(()),
+    };
+    if let Some(pool) = contract_storage.reward_pool()? {
+        if pool.pool == *account {
+            return Ok(());
+        }
+    }
+
+    match get_first_received_height(storage, account)? {
+        Some(first_received) if height < first_received.saturating_add(period) => {
+            Err(StdError::generic_err(format!(
+                "HoldingPeriod: tokens can not be transferred before block {}",
+                first_received.saturating_add(period)
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn check_large_transfer_threshold<S: Storage>(
+    storage: &S,
+    sender: &CanonicalAddr,
+    value: TokenAmount,
+) -> StdResult<()> {
+    match get_large_transfer_threshold(storage, sender)? {
+        Some(threshold) if value > threshold => Err(StdError::generic_err(
+            "Transfer above large transfer threshold must be proposed first",
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn transfer_with_reward<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    to: HumanAddr,
+    value: Uint128,
+    reward_bps: u16,
+) -> StdResult<HandleResponse> {
+    if value.is_zero() {
+        return Err(StdError::generic_err("Can not transfer zero tokens"));
+    }
+    check_plausible_amount(&deps.storage, value)?;
+
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    check_not_locked(&deps.storage, &sender)?;
+    let recipient = deps.api.canonical_address(&to)?;
+
+    if sender == recipient {
+        return Err(StdError::generic_err("Can not sent tokens to self"));
+    }
+
+    check_large_transfer_threshold(&deps.storage, &sender, value.into())?;
+    check_holding_period(&deps.storage, &sender, env.block.height)?;
+    check_transfer_guard(deps, &env.message.sender, &to, value)?;
+
+    let reward_pool = ReadOnlyContractStorage::from_storage(&deps.storage).reward_pool()?;
+    let (pool, reward) = match reward_pool {
+        Some(reward_pool) => {
+            if reward_bps > reward_pool.max_reward_bps {
+                return Err(StdError::generic_err(format!(
+                    "Reward can not exceed {} bps",
+                    reward_pool.max_reward_bps
+                )));
+            }
+            let value = value.u128();
+            let bps = reward_bps as u128;
+            let reward = value / 10_000 * bps + value % 10_000 * bps / 10_000;
+            (Some(reward_pool.pool), TokenAmount(reward))
+        }
+        None if reward_bps == 0 => (None, TokenAmount(0)),
+        None => return Err(StdError::generic_err("Reward pool is not configured")),
+    };
+
+    let value = TokenAmount::from(value);
+    // reward never exceeds value as reward_bps is capped by max_reward_bps
+    let net_value = value
+        .checked_sub(reward)
+        .ok_or_else(|| StdError::generic_err("Reward exceeds transfer value"))?;
+
+    apply_atomic(&mut deps.storage, |storage| {
+        move_tokens(storage, &sender, &recipient, net_value, env.block.height)?;
+        match pool {
+            // a pool paying itself keeps the reward
+            Some(pool) if !reward.is_zero() && pool != sender => {
+                move_tokens(storage, &sender, &pool, reward, env.block.height)
+            }
+            _ => Ok(()),
+        }
+    })?;
 
     let res = HandleResponse {
         messages: vec![],
         log: vec![],
-        data: Some(to_binary(&HandleResult::Transfer {
+        data: Some(to_binary(&HandleResult::TransferWithReward {
             status: Status::Success,
+            reward: reward.into(),
         })?),
     };
     Ok(res)
@@ -184,13 +993,18 @@ fn transfer_from<S: Storage, A: Api, Q: Querier>(
     from: HumanAddr,
     to: HumanAddr,
     value: Uint128,
+    deadline: Option<u64>,
 ) -> StdResult<HandleResponse> {
     if value.is_zero() {
         return Err(StdError::generic_err("Can not transfer zero tokens"));
     }
 
+    check_deadline(env.block.height, deadline)?;
+    check_plausible_amount(&deps.storage, value)?;
+
     let sender = deps.api.canonical_address(&env.message.sender)?;
     let account_owner = deps.api.canonical_address(&from)?;
+    check_not_locked(&deps.storage, &account_owner)?;
     let recipient = deps.api.canonical_address(&to)?;
 
     if sender == account_owner {
@@ -205,14 +1019,36 @@ fn transfer_from<S: Storage, A: Api, Q: Querier>(
         ));
     }
 
+    check_recipient_accepts(&deps.storage, &account_owner, &recipient)?;
+    check_holding_period(&deps.storage, &account_owner, env.block.height)?;
+
     let allowance = get_allowance(&deps.storage, &account_owner, &sender)?;
-    let mut balances = Balances::from_storage(&mut deps.storage);
+    if allowance.is_expired(env.block.height) {
+        return Err(StdError::generic_err("Allowance expired"));
+    }
+    let clamp_transfer_from = ReadOnlyContractStorage::from_storage(&deps.storage)
+        .constants()?
+        .clamp_transfer_from;
+    let value = if clamp_transfer_from && allowance.amount < value.into() {
+        if allowance.amount.is_zero() {
+            return Err(StdError::generic_err("Not enough allowance"));
+        }
+        allowance.amount.into()
+    } else {
+        value
+    };
+    check_transfer_guard(deps, &from, &to, value)?;
+    update_rewards(&mut deps.storage, &account_owner)?;
+    update_rewards(&mut deps.storage, &recipient)?;
+    let balances = Balances::from_storage(&mut deps.storage);
     let account_balance = balances.balance(&account_owner);
     let recipient_balance = balances.balance(&recipient);
 
-    if let Some(new_allowance) = allowance.amount.checked_sub(value.u128()) {
-        if let Some(new_account_balance) = account_balance.checked_sub(value.u128()) {
-            if let Some(new_recipient_balance) = recipient_balance.checked_add(value.u128()) {
+    if let Some(new_allowance) = allowance.amount.checked_sub(value.into()) {
+        if let Some(new_account_balance) = account_balance.checked_sub(value.into()) {
+            if let Some(new_recipient_balance) = recipient_balance.checked_add(value.into()) {
+                check_max_wallet_balance(&deps.storage, &recipient, new_recipient_balance)?;
+                let mut balances = Balances::from_storage(&mut deps.storage);
                 balances.set_balance(&account_owner, new_account_balance);
                 balances.set_balance(&recipient, new_recipient_balance);
                 set_allowance(
@@ -221,6 +1057,7 @@ fn transfer_from<S: Storage, A: Api, Q: Querier>(
                     &sender,
                     Allowance {
                         amount: new_allowance,
+                        ..allowance
                     },
                 )?;
             } else {
@@ -232,386 +1069,6654 @@ fn transfer_from<S: Storage, A: Api, Q: Querier>(
     } else {
         return Err(StdError::generic_err("Not enough allowance"));
     }
+    mark_seen(&mut deps.storage, &recipient, env.block.height);
+
+    let tx_index = next_tx_index(&mut deps.storage, &account_owner)?;
 
     let res = HandleResponse {
         messages: vec![],
         log: vec![],
         data: Some(to_binary(&HandleResult::TransferFrom {
             status: Status::Success,
+            block_height: env.block.height,
+            tx_index,
         })?),
     };
     Ok(res)
 }
 
-fn approve<S: Storage, A: Api, Q: Querier>(
+fn queue_transfer<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    spender: HumanAddr,
+    to: HumanAddr,
     value: Uint128,
 ) -> StdResult<HandleResponse> {
     if value.is_zero() {
-        return Err(StdError::generic_err("Can not approve zero tokens"));
+        return Err(StdError::generic_err("Can not transfer zero tokens"));
     }
 
     let sender = deps.api.canonical_address(&env.message.sender)?;
-    let spender = deps.api.canonical_address(&spender)?;
+    let recipient = deps.api.canonical_address(&to)?;
 
-    if sender == spender {
-        return Err(StdError::generic_err("Can not approve to self"));
+    if sender == recipient {
+        return Err(StdError::generic_err("Can not sent tokens to self"));
     }
 
-    let mut allowance = get_allowance(&deps.storage, &sender, &spender)?;
-    allowance.amount = allowance.amount.saturating_add(value.u128());
-
-    set_allowance(&mut deps.storage, &sender, &spender, allowance)?;
+    let mut transfers = get_queued_transfers(&deps.storage, &sender)?;
+    transfers.push(PendingTransfer {
+        to: recipient,
+        value: value.into(),
+    });
+    set_queued_transfers(&mut deps.storage, &sender, &transfers)?;
 
     let res = HandleResponse {
         messages: vec![],
         log: vec![],
-        data: Some(to_binary(&HandleResult::Approve {
+        data: Some(to_binary(&HandleResult::QueueTransfer {
             status: Status::Success,
         })?),
     };
     Ok(res)
 }
 
-fn allowance<S: Storage, A: Api, Q: Querier>(
+/// Applies all transfers queued by the sender at once,
+/// crediting each recipient only once.
+fn settle_transfers<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
-    owner: HumanAddr,
-    spender: HumanAddr,
+    env: Env,
 ) -> StdResult<HandleResponse> {
-    let owner_address = deps.api.canonical_address(&owner)?;
-    let spender_address = deps.api.canonical_address(&spender)?;
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    check_not_locked(&deps.storage, &sender)?;
+    let transfers = get_queued_transfers(&deps.storage, &sender)?;
+    if transfers.is_empty() {
+        return Err(StdError::generic_err("No queued transfers to settle"));
+    }
+    check_holding_period(&deps.storage, &sender, env.block.height)?;
 
-    let allowance = get_allowance(&deps.storage, &owner_address, &spender_address)?;
+    let mut credits: Vec<PendingTransfer> = vec![];
+    for transfer in transfers {
+        match credits.iter_mut().find(|credit| credit.to == transfer.to) {
+            Some(credit) => {
+                credit.value = credit
+                    .value
+                    .checked_add(transfer.value)
+                    .ok_or_else(|| StdError::generic_err("Transfer amount overflow"))?
+            }
+            None => credits.push(transfer),
+        }
+    }
+
+    for credit in &credits {
+        let to = deps.api.human_address(&credit.to)?;
+        check_transfer_guard(deps, &env.message.sender, &to, credit.value.into())?;
+    }
+
+    apply_atomic(&mut deps.storage, |storage| {
+        for credit in &credits {
+            check_large_transfer_threshold(&*storage, &sender, credit.value)?;
+            move_tokens(storage, &sender, &credit.to, credit.value, env.block.height)?;
+        }
+        set_queued_transfers(storage, &sender, &[])
+    })?;
 
     let res = HandleResponse {
         messages: vec![],
         log: vec![],
-        data: Some(to_binary(&HandleResult::Allowance {
-            owner,
-            spender,
-            value: Uint128(allowance.amount),
+        data: Some(to_binary(&HandleResult::SettleTransfers {
+            status: Status::Success,
         })?),
     };
     Ok(res)
 }
 
-pub fn query<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-    msg: QueryMsg,
-) -> StdResult<QueryResponse> {
-    match msg {
-        QueryMsg::Name {} => query_name(deps),
-        QueryMsg::Symbol {} => query_symbol(deps),
-        QueryMsg::Decimals {} => query_decimals(deps),
-        QueryMsg::TotalSupply {} => query_total_supply(deps),
-        QueryMsg::BalanceOf { address } => query_balance_of(deps, address),
-    }
+/// Error of every allowance increase above the maximum amount.
+fn allowance_overflow() -> StdError {
+    StdError::generic_err("AllowanceOverflow: allowance exceeds maximum")
 }
 
-fn query_name<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<QueryResponse> {
-    to_binary(&QueryResult::Name {
-        name: ReadOnlyContractStorage::from_storage(&deps.storage)
+fn approve<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    spender: HumanAddr,
+    value: Uint128,
+    expiration: Option<u64>,
+) -> StdResult<HandleResponse> {
+    if value.is_zero()
+        && !ReadOnlyContractStorage::from_storage(&deps.storage)
             .constants()?
-            .name,
-    })
-}
+            .zero_approve_revokes
+    {
+        return Err(StdError::generic_err("Can not approve zero tokens"));
+    }
 
-fn query_symbol<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-) -> StdResult<QueryResponse> {
-    to_binary(&QueryResult::Symbol {
-        symbol: ReadOnlyContractStorage::from_storage(&deps.storage)
-            .constants()?
-            .symbol,
-    })
+    check_plausible_amount(&deps.storage, value)?;
+
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    check_not_locked(&deps.storage, &sender)?;
+    let spender_address = deps.api.canonical_address(&spender)?;
+
+    if sender == spender_address {
+        return Err(StdError::generic_err("Can not approve to self"));
+    }
+
+    let amount = if value.is_zero() {
+        // revoking resets allowance to the state of a never approved spender
+        remove_allowance(&mut deps.storage, &sender, &spender_address)?;
+        TokenAmount(0)
+    } else {
+        let mut allowance = get_allowance(&deps.storage, &sender, &spender_address)?;
+        allowance.amount = allowance
+            .amount
+            .checked_add(value.into())
+            .ok_or_else(allowance_overflow)?;
+        allowance.expiration = match expiration {
+            Some(expiration) => Some(expiration),
+            None => default_allowance_expiry(&deps.storage, &sender)?
+                .map(|blocks| env.block.height.saturating_add(blocks)),
+        };
+        let amount = allowance.amount;
+        set_allowance(&mut deps.storage, &sender, &spender_address, allowance)?;
+        amount
+    };
+
+    let mut logs = vec![
+        log("action", "approve"),
+        log("owner", env.message.sender.as_str()),
+        log("spender", spender.as_str()),
+    ];
+    let verbose_logging = ReadOnlyContractStorage::from_storage(&deps.storage)
+        .constants()?
+        .verbose_logging;
+    if verbose_logging {
+        logs.push(log("amount", amount.u128()));
+    }
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: logs,
+        data: Some(to_binary(&HandleResult::Approve {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
 }
 
-fn query_decimals<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-) -> StdResult<QueryResponse> {
-    to_binary(&QueryResult::Decimals {
-        decimals: ReadOnlyContractStorage::from_storage(&deps.storage)
+/// Number of blocks approvals of the `owner` without expiration stay valid for.
+fn default_allowance_expiry<S: Storage>(
+    storage: &S,
+    owner: &CanonicalAddr,
+) -> StdResult<Option<u64>> {
+    match get_default_allowance_expiry(storage, owner)? {
+        Some(blocks) => Ok(Some(blocks)),
+        None => Ok(ReadOnlyContractStorage::from_storage(storage)
             .constants()?
-            .decimals,
-    })
+            .default_allowance_expiry),
+    }
 }
 
-fn query_total_supply<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-) -> StdResult<QueryResponse> {
-    to_binary(&QueryResult::TotalSupply {
-        total_supply: ReadOnlyContractStorage::from_storage(&deps.storage)
-            .total_supply()?
-            .into(),
-    })
-}
+fn adjust_allowance<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    spender: HumanAddr,
+    delta: String,
+) -> StdResult<HandleResponse> {
+    let delta = delta
+        .parse::<i128>()
+        .map_err(|_| StdError::generic_err("Allowance delta must be a signed integer"))?;
+    if delta == 0 {
+        return Err(StdError::generic_err("Can not adjust allowance by zero"));
+    }
+    let change = TokenAmount(delta.unsigned_abs());
+    // decreases only clamp the allowance, so any amount is plausible
+    if delta > 0 {
+        check_plausible_amount(&deps.storage, change.into())?;
+    }
 
-fn query_balance_of<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-    address: HumanAddr,
-) -> StdResult<QueryResponse> {
-    let address = deps.api.canonical_address(&address)?;
-    let balances = ReadOnlyBalances::from_storage(&deps.storage);
-    let balance = balances.balance(&address);
-    to_binary(&QueryResult::BalanceOf {
-        balance: Uint128::from(balance),
-    })
-}
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    check_not_locked(&deps.storage, &sender)?;
+    let spender = deps.api.canonical_address(&spender)?;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use cosmwasm_std::testing::{mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage};
-    use cosmwasm_std::{coins, from_binary};
+    if sender == spender {
+        return Err(StdError::generic_err("Can not approve to self"));
+    }
 
-    fn initialize() -> Extern<MockStorage, MockApi, MockQuerier> {
-        let mut deps = mock_dependencies(20, &[]);
+    let mut allowance = get_allowance(&deps.storage, &sender, &spender)?;
+    allowance.amount = if delta > 0 {
+        allowance
+            .amount
+            .checked_add(change)
+            .ok_or_else(allowance_overflow)?
+    } else {
+        allowance.amount.checked_sub(change).unwrap_or_default()
+    };
+    let zero_approve_revokes = ReadOnlyContractStorage::from_storage(&deps.storage)
+        .constants()?
+        .zero_approve_revokes;
+    if allowance.amount.is_zero() && zero_approve_revokes {
+        // same state as revoking with a zero approve
+        remove_allowance(&mut deps.storage, &sender, &spender)?;
+    } else {
+        set_allowance(&mut deps.storage, &sender, &spender, allowance)?;
+    }
 
-        let msg = InitMsg {
-            name: "test".to_string(),
-            symbol: "!@#$".to_string(),
-            decimals: 69,
-        };
-        let env = mock_env("creator", &coins(1000, "earth"));
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::AdjustAllowance {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
 
-        let res = init(&mut deps, env, msg).unwrap();
-        assert_eq!(0, res.messages.len());
-        deps
+fn approve_burn<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    spender: HumanAddr,
+    value: Uint128,
+    expiration: Option<u64>,
+) -> StdResult<HandleResponse> {
+    if value.is_zero() {
+        return Err(StdError::generic_err("Can not approve zero tokens"));
     }
 
-    #[test]
-    fn initialization() {
-        let deps = initialize();
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    check_not_locked(&deps.storage, &sender)?;
+    let spender = deps.api.canonical_address(&spender)?;
 
-        let res = query(&deps, QueryMsg::TotalSupply {}).unwrap();
-        match from_binary(&res).unwrap() {
-            QueryResult::TotalSupply { total_supply } => assert_eq!(0, total_supply.u128()),
-            _ => panic!("unexpected"),
-        }
+    if sender == spender {
+        return Err(StdError::generic_err("Can not approve to self"));
     }
 
-    #[test]
-    fn handle_deposit_to() {
-        let mut deps = initialize();
-        let address = HumanAddr::from("address");
-        let handle_msg = HandleMsg::DepositTo {
-            to: address.clone(),
-            value: Uint128(69),
-        };
-        match handle(&mut deps, mock_env("creator", &[]), handle_msg) {
-            Ok(_) => {}
-            Err(e) => panic!("error: {:?}", e),
-        }
+    let mut allowance = get_burn_allowance(&deps.storage, &sender, &spender)?;
+    allowance.amount = allowance
+        .amount
+        .checked_add(value.into())
+        .ok_or_else(allowance_overflow)?;
+    allowance.expiration = match expiration {
+        Some(expiration) => Some(expiration),
+        None => default_allowance_expiry(&deps.storage, &sender)?
+            .map(|blocks| env.block.height.saturating_add(blocks)),
+    };
+    set_burn_allowance(&mut deps.storage, &sender, &spender, allowance)?;
 
-        // checking new balance
-        let res = query(&deps, QueryMsg::BalanceOf { address }).unwrap();
-        match from_binary(&res).unwrap() {
-            QueryResult::BalanceOf { balance } => assert_eq!(69, balance.u128()),
-            _ => panic!("unexpected"),
-        }
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::ApproveBurn {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
 
-        // checking new total supply
-        let res = query(&deps, QueryMsg::TotalSupply {}).unwrap();
-        match from_binary(&res).unwrap() {
-            QueryResult::TotalSupply { total_supply } => assert_eq!(69, total_supply.u128()),
-            _ => panic!("unexpected"),
-        }
+fn reassign_allowance<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    from_spender: HumanAddr,
+    to_spender: HumanAddr,
+    value: Uint128,
+) -> StdResult<HandleResponse> {
+    if value.is_zero() {
+        return Err(StdError::generic_err("Can not reassign zero tokens"));
     }
 
-    #[test]
-    fn handle_deposit_to_invalid_sender() {
-        let mut deps = initialize();
-        let address = HumanAddr::from("address");
-        let handle_msg = HandleMsg::DepositTo {
-            to: address.clone(),
-            value: Uint128(69),
-        };
-        match handle(&mut deps, mock_env("bob", &[]), handle_msg) {
-            Ok(_) => panic!("should have failed"),
-            _ => {}
-        }
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    check_not_locked(&deps.storage, &sender)?;
+    let from_spender = deps.api.canonical_address(&from_spender)?;
+    let to_spender = deps.api.canonical_address(&to_spender)?;
 
-        // checking balance
-        let res = query(&deps, QueryMsg::BalanceOf { address }).unwrap();
-        match from_binary(&res).unwrap() {
-            QueryResult::BalanceOf { balance } => assert_eq!(0, balance.u128()),
-            _ => panic!("unexpected"),
-        }
+    if from_spender == to_spender {
+        return Err(StdError::generic_err(
+            "Can not reassign allowance: from and to spenders are same",
+        ));
+    }
 
-        // checking new total supply
-        let res = query(&deps, QueryMsg::TotalSupply {}).unwrap();
-        match from_binary(&res).unwrap() {
-            QueryResult::TotalSupply { total_supply } => assert_eq!(0, total_supply.u128()),
-            _ => panic!("unexpected"),
-        }
+    if sender == to_spender {
+        return Err(StdError::generic_err("Can not approve to self"));
     }
 
-    #[test]
-    fn handle_deposit_to_invalid_founds() {
-        let mut deps = initialize();
-        let address = HumanAddr::from("address");
-        let handle_msg = HandleMsg::DepositTo {
-            to: address.clone(),
-            value: Uint128(0),
-        };
-        match handle(&mut deps, mock_env("bob", &[]), handle_msg) {
-            Ok(_) => panic!("should have failed"),
-            _ => {}
-        }
+    let mut from_allowance = get_allowance(&deps.storage, &sender, &from_spender)?;
+    let mut to_allowance = get_allowance(&deps.storage, &sender, &to_spender)?;
 
-        // checking balance
-        let res = query(&deps, QueryMsg::BalanceOf { address }).unwrap();
-        match from_binary(&res).unwrap() {
+    if let Some(new_from_allowance) = from_allowance.amount.checked_sub(value.into()) {
+        if let Some(new_to_allowance) = to_allowance.amount.checked_add(value.into()) {
+            from_allowance.amount = new_from_allowance;
+            to_allowance.amount = new_to_allowance;
+        } else {
+            return Err(allowance_overflow());
+        }
+    } else {
+        return Err(StdError::generic_err("Not enough allowance"));
+    }
+
+    set_allowance(&mut deps.storage, &sender, &from_spender, from_allowance)?;
+    set_allowance(&mut deps.storage, &sender, &to_spender, to_allowance)?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::ReassignAllowance {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+fn allowance<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    owner: HumanAddr,
+    spender: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let owner_address = deps.api.canonical_address(&owner)?;
+    let spender_address = deps.api.canonical_address(&spender)?;
+
+    let allowance = get_allowance(&deps.storage, &owner_address, &spender_address)?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::Allowance {
+            owner,
+            spender,
+            value: allowance.amount.into(),
+        })?),
+    };
+    Ok(res)
+}
+
+fn renounce_ownership<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    confirm: String,
+) -> StdResult<HandleResponse> {
+    let mut storage = ContractStorage::from_storage(&mut deps.storage);
+    let mut constants = storage.constants()?;
+
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    if sender != constants.owner {
+        return Err(StdError::generic_err(
+            "Only contract owner can renounce ownership",
+        ));
+    }
+    if confirm != constants.token_id {
+        return Err(StdError::generic_err(
+            "Confirmation does not match token id",
+        ));
+    }
+
+    // No sender resolves to an empty address, so owner checks always fail.
+    constants.owner = CanonicalAddr(Binary(vec![]));
+    // nobody can mint without an owner
+    constants.mint_admin = None;
+    storage.set_constants(&constants)?;
+    storage.set_minters(&[])?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::RenounceOwnership {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+fn set_metadata<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    metadata_uri: Option<String>,
+) -> StdResult<HandleResponse> {
+    if let Some(uri) = &metadata_uri {
+        if uri.len() > MAX_METADATA_URI_LEN {
+            return Err(StdError::generic_err(format!(
+                "Metadata URI can not be longer than {} bytes",
+                MAX_METADATA_URI_LEN
+            )));
+        }
+        if !METADATA_URI_SCHEMES
+            .iter()
+            .any(|scheme| uri.starts_with(scheme))
+        {
+            return Err(StdError::generic_err(format!(
+                "Metadata URI must start with one of {:?}",
+                METADATA_URI_SCHEMES
+            )));
+        }
+    }
+
+    let mut storage = ContractStorage::from_storage(&mut deps.storage);
+    let mut constants = storage.constants()?;
+
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    if sender != constants.owner {
+        return Err(StdError::generic_err(
+            "Only contract owner can set metadata",
+        ));
+    }
+    if constants.metadata_frozen {
+        return Err(StdError::generic_err(
+            "MetadataFrozen: metadata can not be changed",
+        ));
+    }
+
+    constants.metadata_uri = metadata_uri;
+    storage.set_constants(&constants)?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::SetMetadata {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+fn set_transfer_guard<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    guard: Option<TransferGuardInfo>,
+) -> StdResult<HandleResponse> {
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let guard = match guard {
+        Some(guard) => Some(TransferGuard {
+            contract: deps.api.canonical_address(&guard.address)?,
+            code_hash: guard.code_hash,
+            fail_open: guard.fail_open,
+        }),
+        None => None,
+    };
+
+    let mut storage = ContractStorage::from_storage(&mut deps.storage);
+    let contract_owner = storage.constants()?.owner;
+    if sender != contract_owner {
+        return Err(StdError::generic_err(
+            "Only contract owner can set transfer guard",
+        ));
+    }
+
+    storage.set_transfer_guard(&guard)?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::SetTransferGuard {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+fn distribute_rewards<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    amount: Uint128,
+) -> StdResult<HandleResponse> {
+    if amount.is_zero() {
+        return Err(StdError::generic_err("Can not distribute zero rewards"));
+    }
+
+    // escrowed tokens belong to no balance and can not claim rewards
+    let escrowed = total_escrowed(&deps.storage)?;
+    let mut storage = ContractStorage::from_storage(&mut deps.storage);
+
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    if sender != storage.constants()?.owner {
+        return Err(StdError::generic_err(
+            "Only contract owner can distribute rewards",
+        ));
+    }
+
+    let eligible_supply = storage.total_supply()?.u128().saturating_sub(escrowed.0);
+    if eligible_supply == 0 {
+        return Err(StdError::generic_err("No tokens to distribute rewards to"));
+    }
+    let increase = amount
+        .u128()
+        .checked_mul(REWARD_PRECISION)
+        .ok_or_else(|| StdError::generic_err("Rewards overflow"))?
+        / eligible_supply;
+    let reward_per_token = storage
+        .reward_per_token()?
+        .checked_add(increase)
+        .ok_or_else(|| StdError::generic_err("Rewards overflow"))?;
+    storage.set_reward_per_token(reward_per_token)?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::DistributeRewards {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+fn swap_balances<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    a: HumanAddr,
+    b: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let a = deps.api.canonical_address(&a)?;
+    let b = deps.api.canonical_address(&b)?;
+
+    let contract_owner = ReadOnlyContractStorage::from_storage(&deps.storage)
+        .constants()?
+        .owner;
+    if sender != contract_owner {
+        return Err(StdError::generic_err(
+            "Only contract owner can swap balances",
+        ));
+    }
+
+    if a == b {
+        return Err(StdError::generic_err(
+            "Can not swap balances: accounts are same",
+        ));
+    }
+
+    let balances = ReadOnlyBalances::from_storage(&deps.storage);
+    let a_balance = balances.balance(&a);
+    let b_balance = balances.balance(&b);
+    // a swap has no sender, so receive policies do not apply
+    for (from, to, balance) in &[(&b, &a, b_balance), (&a, &b, a_balance)] {
+        if !balance.is_zero() {
+            check_registered(&deps.storage, to)?;
+            check_max_wallet_balance(&deps.storage, to, *balance)?;
+            check_transfer_guard(
+                deps,
+                &deps.api.human_address(from)?,
+                &deps.api.human_address(to)?,
+                (*balance).into(),
+            )?;
+        }
+    }
+
+    update_rewards(&mut deps.storage, &a)?;
+    update_rewards(&mut deps.storage, &b)?;
+    let mut balances = Balances::from_storage(&mut deps.storage);
+    balances.set_balance(&a, b_balance);
+    balances.set_balance(&b, a_balance);
+    if !b_balance.is_zero() {
+        mark_seen(&mut deps.storage, &a, env.block.height);
+    }
+    if !a_balance.is_zero() {
+        mark_seen(&mut deps.storage, &b, env.block.height);
+    }
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::SwapBalances {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+fn set_disabled_handlers<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    handlers: Vec<HandlerId>,
+) -> StdResult<HandleResponse> {
+    let mut storage = ContractStorage::from_storage(&mut deps.storage);
+
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let contract_owner = storage.constants()?.owner;
+    if sender != contract_owner {
+        return Err(StdError::generic_err(
+            "Only contract owner can disable handlers",
+        ));
+    }
+
+    storage.set_disabled_handlers(&handlers)?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::SetDisabledHandlers {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+fn batch_burn<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    actions: Vec<BurnAction>,
+) -> StdResult<HandleResponse> {
+    if actions.is_empty() {
+        return Err(StdError::generic_err("Can not burn empty batch"));
+    }
+
+    let constants = ReadOnlyContractStorage::from_storage(&deps.storage).constants()?;
+    if actions.len() > constants.max_batch_size as usize {
+        return Err(StdError::generic_err(format!(
+            "BatchTooLarge: batch can not contain more than {} actions",
+            constants.max_batch_size
+        )));
+    }
+
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    if sender != constants.owner {
+        return Err(StdError::generic_err(
+            "Only contract owner can batch burn tokens",
+        ));
+    }
+
+    let api = &deps.api;
+    apply_atomic(&mut deps.storage, |storage| {
+        for action in actions {
+            if action.value.is_zero() {
+                return Err(StdError::generic_err("Can not burn zero tokens"));
+            }
+            let account = api.canonical_address(&action.owner)?;
+            burn_tokens(storage, &account, action.value.into())?;
+        }
+        Ok(())
+    })?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::BatchBurn {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+fn force_redeem_all<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    accounts: Vec<HumanAddr>,
+) -> StdResult<HandleResponse> {
+    if accounts.is_empty() {
+        return Err(StdError::generic_err("Can not redeem empty batch"));
+    }
+
+    let constants = ReadOnlyContractStorage::from_storage(&deps.storage).constants()?;
+    if accounts.len() > constants.max_batch_size as usize {
+        return Err(StdError::generic_err(format!(
+            "BatchTooLarge: batch can not contain more than {} accounts",
+            constants.max_batch_size
+        )));
+    }
+
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    if sender != constants.owner {
+        return Err(StdError::generic_err(
+            "Only contract owner can force redeem balances",
+        ));
+    }
+
+    let reserve = constants
+        .reserve
+        .ok_or_else(|| StdError::generic_err("Contract has no native reserves to redeem"))?;
+
+    let api = &deps.api;
+    let mut messages = vec![];
+    let mut redeemed = TokenAmount(0);
+    apply_atomic(&mut deps.storage, |storage| {
+        for account in accounts {
+            let account_owner = api.canonical_address(&account)?;
+            let balance = ReadOnlyBalances::from_storage(&*storage).balance(&account_owner);
+            if balance.is_zero() {
+                continue;
+            }
+            burn_tokens(storage, &account_owner, balance)?;
+            redeemed = redeemed.saturating_add(balance);
+
+            let amount = balance
+                .u128()
+                .checked_mul(reserve.backing_ratio_bps as u128)
+                .ok_or_else(|| StdError::generic_err("Redeemed reserves overflow"))?
+                / 10_000;
+            if amount != 0 {
+                messages.push(CosmosMsg::Bank(BankMsg::Send {
+                    from_address: env.contract.address.clone(),
+                    to_address: account,
+                    amount: vec![Coin {
+                        denom: reserve.denom.clone(),
+                        amount: Uint128(amount),
+                    }],
+                }));
+            }
+        }
+        Ok(())
+    })?;
+
+    let res = HandleResponse {
+        messages,
+        log: vec![],
+        data: Some(to_binary(&HandleResult::ForceRedeemAll {
+            status: Status::Success,
+            redeemed: redeemed.into(),
+        })?),
+    };
+    Ok(res)
+}
+
+fn set_reward_pool<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    pool: HumanAddr,
+    max_reward_bps: u16,
+) -> StdResult<HandleResponse> {
+    if max_reward_bps > 10_000 {
+        return Err(StdError::generic_err("Reward can not exceed 10000 bps"));
+    }
+
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let pool = deps.api.canonical_address(&pool)?;
+
+    let mut storage = ContractStorage::from_storage(&mut deps.storage);
+    let contract_owner = storage.constants()?.owner;
+    if sender != contract_owner {
+        return Err(StdError::generic_err(
+            "Only contract owner can set reward pool",
+        ));
+    }
+
+    storage.set_reward_pool(&Some(RewardPool {
+        pool,
+        max_reward_bps,
+    }))?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::SetRewardPool {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+fn set_account_guardian<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    guardian: Option<HumanAddr>,
+) -> StdResult<HandleResponse> {
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let guardian = match guardian {
+        Some(guardian) => Some(deps.api.canonical_address(&guardian)?),
+        None => None,
+    };
+
+    if guardian.as_ref() == Some(&sender) {
+        return Err(StdError::generic_err("Can not set self as guardian"));
+    }
+
+    set_guardian(&mut deps.storage, &sender, guardian.as_ref())?;
+    // recovery started by previous guardian is no longer valid
+    set_recovery(&mut deps.storage, &sender, None)?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::SetGuardian {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+fn set_account_fallback_recipient<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    fallback: Option<HumanAddr>,
+) -> StdResult<HandleResponse> {
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let fallback = match fallback {
+        Some(fallback) => Some(deps.api.canonical_address(&fallback)?),
+        None => None,
+    };
+
+    if fallback.as_ref() == Some(&sender) {
+        return Err(StdError::generic_err(
+            "Can not set self as fallback recipient",
+        ));
+    }
+
+    set_fallback_recipient(&mut deps.storage, &sender, fallback.as_ref())?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::SetFallbackRecipient {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+fn initiate_recovery<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    account: HumanAddr,
+    to: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let account = deps.api.canonical_address(&account)?;
+    let to = deps.api.canonical_address(&to)?;
+
+    if get_guardian(&deps.storage, &account)? != Some(sender) {
+        return Err(StdError::generic_err(
+            "Only account guardian can initiate recovery",
+        ));
+    }
+
+    if account == to {
+        return Err(StdError::generic_err(
+            "Can not recover tokens: account and to addresses are same",
+        ));
+    }
+
+    let recovery_delay = ReadOnlyContractStorage::from_storage(&deps.storage)
+        .constants()?
+        .recovery_delay;
+    let recovery = Recovery {
+        to,
+        unlock_height: env.block.height.saturating_add(recovery_delay),
+    };
+    set_recovery(&mut deps.storage, &account, Some(&recovery))?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::InitiateRecovery {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+fn cancel_recovery<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+
+    if get_recovery(&deps.storage, &sender)?.is_none() {
+        return Err(StdError::generic_err("No recovery in progress"));
+    }
+    set_recovery(&mut deps.storage, &sender, None)?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::CancelRecovery {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+fn finalize_recovery<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    account: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let account = deps.api.canonical_address(&account)?;
+
+    if get_guardian(&deps.storage, &account)? != Some(sender) {
+        return Err(StdError::generic_err(
+            "Only account guardian can finalize recovery",
+        ));
+    }
+
+    let recovery = get_recovery(&deps.storage, &account)?
+        .ok_or_else(|| StdError::generic_err("No recovery in progress"))?;
+    if env.block.height < recovery.unlock_height {
+        return Err(StdError::generic_err(format!(
+            "Recovery can not be finalized before height {}",
+            recovery.unlock_height
+        )));
+    }
+
+    let account_balance = ReadOnlyBalances::from_storage(&deps.storage).balance(&account);
+    if !account_balance.is_zero() {
+        check_transfer_guard(
+            deps,
+            &deps.api.human_address(&account)?,
+            &deps.api.human_address(&recovery.to)?,
+            account_balance.into(),
+        )?;
+        move_tokens(
+            &mut deps.storage,
+            &account,
+            &recovery.to,
+            account_balance,
+            env.block.height,
+        )?;
+    }
+    set_recovery(&mut deps.storage, &account, None)?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::FinalizeRecovery {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+fn set_minters<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    minters: Vec<MinterInfo>,
+) -> StdResult<HandleResponse> {
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let minters = minters
+        .into_iter()
+        .map(|minter| {
+            Ok(Minter {
+                address: deps.api.canonical_address(&minter.address)?,
+                expires_at: minter.expires_at,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let mut storage = ContractStorage::from_storage(&mut deps.storage);
+    let constants = storage.constants()?;
+    if constants.owner.is_empty() {
+        return Err(StdError::generic_err(
+            "MintingDisabled: ownership was renounced",
+        ));
+    }
+    if sender != constants.owner && Some(sender) != constants.mint_admin {
+        return Err(StdError::generic_err(
+            "Only contract owner or mint admin can set minters",
+        ));
+    }
+
+    storage.set_minters(&minters)?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::SetMinters {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+fn set_mint_admin<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    mint_admin: Option<HumanAddr>,
+) -> StdResult<HandleResponse> {
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let mint_admin = match mint_admin {
+        Some(mint_admin) => Some(deps.api.canonical_address(&mint_admin)?),
+        None => None,
+    };
+
+    let mut storage = ContractStorage::from_storage(&mut deps.storage);
+    let mut constants = storage.constants()?;
+    if sender != constants.owner {
+        return Err(StdError::generic_err(
+            "Only contract owner can set mint admin",
+        ));
+    }
+
+    constants.mint_admin = mint_admin;
+    storage.set_constants(&constants)?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::SetMintAdmin {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+fn set_account_default_allowance_expiry<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    blocks: Option<u64>,
+) -> StdResult<HandleResponse> {
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    set_default_allowance_expiry(&mut deps.storage, &sender, blocks)?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::SetDefaultAllowanceExpiry {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+fn set_account_large_transfer_threshold<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    threshold: Option<Uint128>,
+) -> StdResult<HandleResponse> {
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    set_large_transfer_threshold(&mut deps.storage, &sender, threshold.map(TokenAmount::from))?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::SetLargeTransferThreshold {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+fn propose_large_transfer<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    to: HumanAddr,
+    value: Uint128,
+) -> StdResult<HandleResponse> {
+    if value.is_zero() {
+        return Err(StdError::generic_err("Can not transfer zero tokens"));
+    }
+
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let recipient = deps.api.canonical_address(&to)?;
+
+    if sender == recipient {
+        return Err(StdError::generic_err("Can not sent tokens to self"));
+    }
+
+    let transfer = PendingTransfer {
+        to: recipient,
+        value: value.into(),
+    };
+    let id = add_pending_transfer(&mut deps.storage, &sender, &transfer)?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::ProposeLargeTransfer {
+            status: Status::Success,
+            id,
+        })?),
+    };
+    Ok(res)
+}
+
+fn confirm_large_transfer<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    id: u64,
+) -> StdResult<HandleResponse> {
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    check_not_locked(&deps.storage, &sender)?;
+
+    let transfer = take_pending_transfer(&mut deps.storage, &sender, id)?
+        .ok_or_else(|| StdError::generic_err(format!("No pending transfer with id {}", id)))?;
+    check_holding_period(&deps.storage, &sender, env.block.height)?;
+    let to = deps.api.human_address(&transfer.to)?;
+    check_transfer_guard(deps, &env.message.sender, &to, transfer.value.into())?;
+    move_tokens(
+        &mut deps.storage,
+        &sender,
+        &transfer.to,
+        transfer.value,
+        env.block.height,
+    )?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::ConfirmLargeTransfer {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+fn create_escrow<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    to: HumanAddr,
+    value: Uint128,
+    release_height: u64,
+) -> StdResult<HandleResponse> {
+    if value.is_zero() {
+        return Err(StdError::generic_err("Can not escrow zero tokens"));
+    }
+    if release_height <= env.block.height {
+        return Err(StdError::generic_err(
+            "Escrow release height must be in the future",
+        ));
+    }
+    check_plausible_amount(&deps.storage, value)?;
+
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let recipient = deps.api.canonical_address(&to)?;
+
+    if sender == recipient {
+        return Err(StdError::generic_err("Can not escrow tokens to self"));
+    }
+    check_not_locked(&deps.storage, &sender)?;
+    check_large_transfer_threshold(&deps.storage, &sender, value.into())?;
+    check_holding_period(&deps.storage, &sender, env.block.height)?;
+    check_transfer_guard(deps, &env.message.sender, &to, value)?;
+    check_recipient_accepts(&deps.storage, &sender, &recipient)?;
+
+    let value: TokenAmount = value.into();
+    update_rewards(&mut deps.storage, &sender)?;
+    let mut balances = Balances::from_storage(&mut deps.storage);
+    let new_sender_balance = balances
+        .balance(&sender)
+        .checked_sub(value)
+        .ok_or_else(|| StdError::generic_err("Sender balance underflow"))?;
+    balances.set_balance(&sender, new_sender_balance);
+
+    let id = add_escrow(
+        &mut deps.storage,
+        &Escrow {
+            sender,
+            recipient,
+            value,
+            release_height,
+        },
+    )?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::CreateEscrow {
+            status: Status::Success,
+            id,
+        })?),
+    };
+    Ok(res)
+}
+
+/// Transfers the escrow to its recipient. Either party can release it
+/// once the release height is reached.
+fn release_escrow<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    id: u64,
+) -> StdResult<HandleResponse> {
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let escrow = get_escrow(&deps.storage, id)?
+        .ok_or_else(|| StdError::generic_err(format!("No escrow with id {}", id)))?;
+    if sender != escrow.sender && sender != escrow.recipient {
+        return Err(StdError::generic_err(
+            "Only escrow sender or recipient can release it",
+        ));
+    }
+    if env.block.height < escrow.release_height {
+        return Err(StdError::generic_err(format!(
+            "Escrow can not be released before height {}",
+            escrow.release_height
+        )));
+    }
+    check_transfer_guard(
+        deps,
+        &deps.api.human_address(&escrow.sender)?,
+        &deps.api.human_address(&escrow.recipient)?,
+        escrow.value.into(),
+    )?;
+
+    // the escrow goes back to its sender and then moves as a regular transfer,
+    // so the recipient receive policy is checked as of release time
+    apply_atomic(&mut deps.storage, |storage| {
+        remove_escrow(storage, id)?;
+        update_rewards(storage, &escrow.sender)?;
+        let sender_balance = Balances::from_storage(storage)
+            .balance(&escrow.sender)
+            .checked_add(escrow.value)
+            .ok_or_else(|| StdError::generic_err("Sender balance overflow"))?;
+        Balances::from_storage(storage).set_balance(&escrow.sender, sender_balance);
+        move_tokens(
+            storage,
+            &escrow.sender,
+            &escrow.recipient,
+            escrow.value,
+            env.block.height,
+        )
+    })?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::ReleaseEscrow {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+/// Returns the escrow to its sender while it can not be released yet.
+fn refund_escrow<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    id: u64,
+) -> StdResult<HandleResponse> {
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let escrow = get_escrow(&deps.storage, id)?
+        .ok_or_else(|| StdError::generic_err(format!("No escrow with id {}", id)))?;
+    if sender != escrow.sender {
+        return Err(StdError::generic_err("Only escrow sender can refund it"));
+    }
+    if env.block.height >= escrow.release_height {
+        return Err(StdError::generic_err(format!(
+            "Escrow can not be refunded from height {}",
+            escrow.release_height
+        )));
+    }
+    // escrowed tokens are held by the contract until refunded
+    check_transfer_guard(
+        deps,
+        &env.contract.address,
+        &deps.api.human_address(&escrow.sender)?,
+        escrow.value.into(),
+    )?;
+
+    apply_atomic(&mut deps.storage, |storage| {
+        remove_escrow(storage, id)?;
+        credit_escrow(storage, &escrow.sender, escrow.value, env.block.height)
+    })?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::RefundEscrow {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+fn credit_escrow<S: Storage>(
+    storage: &mut S,
+    account: &CanonicalAddr,
+    value: TokenAmount,
+    height: u64,
+) -> StdResult<()> {
+    update_rewards(storage, account)?;
+    let new_balance = Balances::from_storage(storage)
+        .balance(account)
+        .checked_add(value)
+        .ok_or_else(|| StdError::generic_err("Recipient balance overflow"))?;
+    check_max_wallet_balance(&*storage, account, new_balance)?;
+    Balances::from_storage(storage).set_balance(account, new_balance);
+    mark_seen(storage, account, height);
+    Ok(())
+}
+
+fn set_tag<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    account: HumanAddr,
+    tag: Option<String>,
+) -> StdResult<HandleResponse> {
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let owner = ReadOnlyContractStorage::from_storage(&deps.storage)
+        .constants()?
+        .owner;
+    if sender != owner {
+        return Err(StdError::generic_err(
+            "Only contract owner can tag accounts",
+        ));
+    }
+    if matches!(&tag, Some(tag) if tag.is_empty()) {
+        return Err(StdError::generic_err("Tag can not be empty"));
+    }
+
+    let account = deps.api.canonical_address(&account)?;
+    set_account_tag(&mut deps.storage, &account, tag.as_deref())?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::SetAccountTag {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+fn reserve_symbols<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    symbols: Vec<String>,
+) -> StdResult<HandleResponse> {
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let mut storage = ContractStorage::from_storage(&mut deps.storage);
+    if sender != storage.constants()?.owner {
+        return Err(StdError::generic_err(
+            "Only contract owner can reserve symbols",
+        ));
+    }
+    if symbols.iter().any(|symbol| symbol.is_empty()) {
+        return Err(StdError::generic_err("Reserved symbol can not be empty"));
+    }
+
+    let mut reserved_symbols = storage.reserved_symbols()?;
+    for symbol in symbols {
+        if !is_reserved_symbol(&reserved_symbols, &symbol) {
+            reserved_symbols.push(symbol.to_uppercase());
+        }
+    }
+    storage.set_reserved_symbols(&reserved_symbols)?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::ReserveSymbols {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+fn set_owner_query_key<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    key: String,
+) -> StdResult<HandleResponse> {
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let mut storage = ContractStorage::from_storage(&mut deps.storage);
+    if sender != storage.constants()?.owner {
+        return Err(StdError::generic_err(
+            "Only contract owner can set owner query key",
+        ));
+    }
+    if key.is_empty() {
+        return Err(StdError::generic_err("Owner query key can not be empty"));
+    }
+    storage.set_owner_query_key_hash(&Sha256::digest(key.as_bytes()));
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::SetOwnerQueryKey {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+/// Queries carry no sender, owner only queries are authenticated
+/// with the key set by SetOwnerQueryKey.
+fn check_owner_query_key<S: Storage>(storage: &S, owner_key: &str) -> StdResult<()> {
+    match ReadOnlyContractStorage::from_storage(storage).owner_query_key_hash() {
+        Some(hash) if hash == Sha256::digest(owner_key.as_bytes()).as_slice() => Ok(()),
+        _ => Err(StdError::generic_err("Unauthorized: wrong owner query key")),
+    }
+}
+
+/// Checks `symbol` against `RESERVED_SYMBOLS` and `reserved_symbols`, ignoring case.
+fn is_reserved_symbol(reserved_symbols: &[String], symbol: &str) -> bool {
+    RESERVED_SYMBOLS
+        .iter()
+        .copied()
+        .chain(reserved_symbols.iter().map(String::as_str))
+        .any(|reserved| reserved.eq_ignore_ascii_case(symbol))
+}
+
+fn query_symbol_registry<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    registry: &SymbolRegistryInfo,
+    symbol: &str,
+) -> StdResult<bool> {
+    let request = QueryRequest::Wasm(WasmQuery::Smart {
+        contract_addr: registry.address.clone(),
+        callback_code_hash: registry.code_hash.clone(),
+        msg: to_binary(&QueryMsg::IsReservedSymbol {
+            symbol: symbol.to_string(),
+        })?,
+    });
+    match deps.querier.query::<QueryResult>(&request) {
+        Ok(QueryResult::IsReservedSymbol { reserved }) => Ok(reserved),
+        Ok(_) => Err(StdError::generic_err(
+            "SymbolRegistryFailed: unexpected response",
+        )),
+        Err(e) => Err(StdError::generic_err(format!(
+            "SymbolRegistryFailed: {}",
+            e
+        ))),
+    }
+}
+
+fn set_account_receive_policy<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    allowed_senders: Option<Vec<HumanAddr>>,
+) -> StdResult<HandleResponse> {
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let policy = match allowed_senders {
+        Some(senders) => ReceivePolicy::Allowlist(
+            senders
+                .iter()
+                .map(|sender| deps.api.canonical_address(sender))
+                .collect::<StdResult<Vec<_>>>()?,
+        ),
+        None => ReceivePolicy::AcceptAll,
+    };
+
+    set_receive_policy(&mut deps.storage, &sender, &policy)?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::SetReceivePolicy {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+fn register<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    register_account(&mut deps.storage, &sender);
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::RegisterAccount {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+fn set_account_locked<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    locked: bool,
+) -> StdResult<HandleResponse> {
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    set_locked(&mut deps.storage, &sender, locked);
+
+    let result = if locked {
+        HandleResult::LockMyAccount {
+            status: Status::Success,
+        }
+    } else {
+        HandleResult::UnlockMyAccount {
+            status: Status::Success,
+        }
+    };
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&result)?),
+    };
+    Ok(res)
+}
+
+fn register_claim_code<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    code: String,
+    amount: Uint128,
+) -> StdResult<HandleResponse> {
+    if amount.is_zero() {
+        return Err(StdError::generic_err("Can not grant zero tokens"));
+    }
+
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let contract_owner = ReadOnlyContractStorage::from_storage(&deps.storage)
+        .constants()?
+        .owner;
+    if sender != contract_owner {
+        return Err(StdError::generic_err(
+            "Only contract owner can register claim codes",
+        ));
+    }
+
+    let code_hash = claim_code_hash(&code);
+    if get_claim_code(&deps.storage, &code_hash)?.is_some() {
+        return Err(StdError::generic_err("Claim code is already registered"));
+    }
+    set_claim_code(
+        &mut deps.storage,
+        &code_hash,
+        &ClaimCode {
+            amount: amount.into(),
+            claimed: false,
+        },
+    )?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::RegisterClaimCode {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+fn claim_with_code<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    code: String,
+) -> StdResult<HandleResponse> {
+    let code_hash = claim_code_hash(&code);
+    let claim_code = match get_claim_code(&deps.storage, &code_hash)? {
+        Some(claim_code) if claim_code.claimed => {
+            return Err(StdError::generic_err("Claim code is already used"))
+        }
+        Some(claim_code) => claim_code,
+        None => return Err(StdError::generic_err("Invalid claim code")),
+    };
+    set_claim_code(
+        &mut deps.storage,
+        &code_hash,
+        &ClaimCode {
+            claimed: true,
+            ..claim_code
+        },
+    )?;
+
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    mint_tokens(deps, &sender, claim_code.amount, env.block.height)?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::ClaimWithCode {
+            status: Status::Success,
+            amount: claim_code.amount.into(),
+        })?),
+    };
+    Ok(res)
+}
+
+/// SHA-256 of the claim code, the code itself is never stored.
+fn claim_code_hash(code: &str) -> Vec<u8> {
+    Sha256::digest(code.as_bytes()).to_vec()
+}
+
+#[cfg(feature = "debug")]
+fn debug_set_balance<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+    value: Uint128,
+) -> StdResult<HandleResponse> {
+    let account = deps.api.canonical_address(&address)?;
+    update_rewards(&mut deps.storage, &account)?;
+
+    let mut balances = Balances::from_storage(&mut deps.storage);
+    let old_balance = balances.balance(&account);
+    balances.set_balance(&account, value.into());
+    mark_seen(&mut deps.storage, &account, env.block.height);
+
+    // keeping total supply equal to the sum of balances
+    let mut storage = ContractStorage::from_storage(&mut deps.storage);
+    let total_supply = storage.total_supply()?;
+    let new_total = total_supply
+        .checked_sub(old_balance)
+        .and_then(|total| total.checked_add(value.into()))
+        .ok_or_else(|| StdError::generic_err("Total supply overflow"))?;
+    storage.set_total_supply(new_total)?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleResult::DebugSetBalance {
+            status: Status::Success,
+        })?),
+    };
+    Ok(res)
+}
+
+pub fn query<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: QueryMsg,
+) -> StdResult<QueryResponse> {
+    match msg {
+        QueryMsg::Name {} => query_name(deps),
+        QueryMsg::Symbol {} => query_symbol(deps),
+        QueryMsg::Decimals {} => query_decimals(deps),
+        QueryMsg::TotalSupply {} => query_total_supply(deps),
+        QueryMsg::BalanceOf { address } => query_balance_of(deps, address),
+        QueryMsg::BalanceDetail { address } => query_balance_detail(deps, address),
+        QueryMsg::ConvertAmount {
+            amount,
+            target_decimals,
+            rounding,
+        } => query_convert_amount(deps, amount, target_decimals, rounding),
+        QueryMsg::AllowancesBatch {
+            pairs,
+            current_height,
+        } => query_allowances_batch(deps, pairs, current_height),
+        QueryMsg::TokenId {} => query_token_id(deps),
+        QueryMsg::MetadataHash {} => query_metadata_hash(deps),
+        QueryMsg::RawStorage { key, owner_key } => query_raw_storage(deps, key, owner_key),
+        QueryMsg::Tips {} => query_tips(deps),
+        QueryMsg::PullableAmount {
+            owner,
+            spender,
+            current_height,
+        } => query_pullable_amount(deps, owner, spender, current_height),
+        QueryMsg::SupportedFeatures {} => query_supported_features(deps),
+        QueryMsg::MetadataUri {} => query_metadata_uri(deps),
+        QueryMsg::CreationInfo {} => query_creation_info(deps),
+        QueryMsg::SupplyStats {} => query_supply_stats(deps),
+        QueryMsg::PendingRewards { address } => query_pending_rewards(deps, address),
+        QueryMsg::MintAdmin {} => query_mint_admin(deps),
+        QueryMsg::AllowanceDetail {
+            owner,
+            spender,
+            current_height,
+        } => query_allowance_detail(deps, owner, spender, current_height),
+        QueryMsg::TotalApprovedBy {
+            owner,
+            current_height,
+            owner_key,
+        } => query_total_approved_by(deps, owner, current_height, owner_key),
+        QueryMsg::ExpiredAllowances {
+            current_height,
+            start_after,
+            limit,
+        } => query_expired_allowances(deps, current_height, start_after, limit),
+        QueryMsg::CanTransfer {
+            from,
+            to,
+            value,
+            current_height,
+            deadline,
+        } => query_can_transfer(deps, from, to, value, current_height, deadline),
+        QueryMsg::Ownership {} => query_ownership(deps),
+        QueryMsg::FormattedBalance {
+            address,
+            display_decimals,
+        } => query_formatted_balance(deps, address, display_decimals),
+        QueryMsg::MintersDetail { start_after, limit } => {
+            query_minters_detail(deps, start_after, limit)
+        }
+        QueryMsg::PricedValue { address } => query_priced_value(deps, address),
+        QueryMsg::SchemaVersion {} => query_schema_version(deps),
+        QueryMsg::BalanceByTag { tag, owner_key } => query_balance_by_tag(deps, tag, owner_key),
+        QueryMsg::Parameters {} => query_parameters(deps),
+        QueryMsg::IsReservedSymbol { symbol } => query_is_reserved_symbol(deps, symbol),
+    }
+}
+
+fn query_name<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<QueryResponse> {
+    to_binary(&QueryResult::Name {
+        name: ReadOnlyContractStorage::from_storage(&deps.storage)
+            .constants()?
+            .name,
+    })
+}
+
+fn query_symbol<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<QueryResponse> {
+    to_binary(&QueryResult::Symbol {
+        symbol: ReadOnlyContractStorage::from_storage(&deps.storage)
+            .constants()?
+            .symbol,
+    })
+}
+
+fn query_decimals<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<QueryResponse> {
+    to_binary(&QueryResult::Decimals {
+        decimals: ReadOnlyContractStorage::from_storage(&deps.storage)
+            .constants()?
+            .decimals,
+    })
+}
+
+fn query_token_id<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<QueryResponse> {
+    to_binary(&QueryResult::TokenId {
+        token_id: ReadOnlyContractStorage::from_storage(&deps.storage)
+            .constants()?
+            .token_id,
+    })
+}
+
+fn query_metadata_uri<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<QueryResponse> {
+    to_binary(&QueryResult::MetadataUri {
+        metadata_uri: ReadOnlyContractStorage::from_storage(&deps.storage)
+            .constants()?
+            .metadata_uri,
+    })
+}
+
+fn query_mint_admin<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<QueryResponse> {
+    let mint_admin = match ReadOnlyContractStorage::from_storage(&deps.storage)
+        .constants()?
+        .mint_admin
+    {
+        Some(mint_admin) => Some(deps.api.human_address(&mint_admin)?),
+        None => None,
+    };
+    to_binary(&QueryResult::MintAdmin { mint_admin })
+}
+
+fn query_ownership<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<QueryResponse> {
+    let owner = ReadOnlyContractStorage::from_storage(&deps.storage)
+        .constants()?
+        .owner;
+    // renounced ownership leaves an empty owner address
+    let owner = if owner.is_empty() {
+        None
+    } else {
+        Some(deps.api.human_address(&owner)?)
+    };
+    to_binary(&QueryResult::Ownership {
+        renounced: owner.is_none(),
+        owner,
+    })
+}
+
+fn query_minters_detail<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    start_after: Option<HumanAddr>,
+    limit: Option<u32>,
+) -> StdResult<QueryResponse> {
+    let limit = limit.unwrap_or(MAX_PAGE_LIMIT).min(MAX_PAGE_LIMIT).max(1) as usize;
+    let mut minters = ReadOnlyContractStorage::from_storage(&deps.storage)
+        .minters()?
+        .into_iter()
+        .map(|minter| {
+            Ok(MinterInfo {
+                address: deps.api.human_address(&minter.address)?,
+                expires_at: minter.expires_at,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    minters.sort_by(|a, b| a.address.as_str().cmp(b.address.as_str()));
+
+    let minters = minters
+        .into_iter()
+        .filter(|minter| match &start_after {
+            Some(start_after) => minter.address.as_str() > start_after.as_str(),
+            None => true,
+        })
+        .take(limit)
+        .collect();
+    to_binary(&QueryResult::MintersDetail { minters })
+}
+
+fn query_creation_info<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<QueryResponse> {
+    let constants = ReadOnlyContractStorage::from_storage(&deps.storage).constants()?;
+    to_binary(&QueryResult::CreationInfo {
+        height: constants.created_height,
+        time: constants.created_time,
+    })
+}
+
+fn query_is_reserved_symbol<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    symbol: String,
+) -> StdResult<QueryResponse> {
+    let reserved_symbols =
+        ReadOnlyContractStorage::from_storage(&deps.storage).reserved_symbols()?;
+    to_binary(&QueryResult::IsReservedSymbol {
+        reserved: is_reserved_symbol(&reserved_symbols, &symbol),
+    })
+}
+
+fn query_parameters<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<QueryResponse> {
+    let storage = ReadOnlyContractStorage::from_storage(&deps.storage);
+    let constants = storage.constants()?;
+    let price_oracle = match constants.price_oracle {
+        Some(oracle) => Some(PriceOracleInfo {
+            address: deps.api.human_address(&oracle.contract)?,
+            code_hash: oracle.code_hash,
+        }),
+        None => None,
+    };
+    let transfer_guard = match storage.transfer_guard()? {
+        Some(guard) => Some(TransferGuardInfo {
+            address: deps.api.human_address(&guard.contract)?,
+            code_hash: guard.code_hash,
+            fail_open: guard.fail_open,
+        }),
+        None => None,
+    };
+    let reward_pool = match storage.reward_pool()? {
+        Some(reward_pool) => Some(RewardPoolInfo {
+            pool: deps.api.human_address(&reward_pool.pool)?,
+            max_reward_bps: reward_pool.max_reward_bps,
+        }),
+        None => None,
+    };
+    let mint_admin = match &constants.mint_admin {
+        Some(mint_admin) => Some(deps.api.human_address(mint_admin)?),
+        None => None,
+    };
+    to_binary(&QueryResult::Parameters {
+        parameters: Parameters {
+            decimals: constants.decimals,
+            reserve: constants.reserve,
+            verbose_logging: constants.verbose_logging,
+            recovery_delay: constants.recovery_delay,
+            max_batch_size: constants.max_batch_size,
+            reject_value_above_supply: constants.reject_value_above_supply,
+            max_wallet_balance: constants.max_wallet_balance.map(Uint128::from),
+            treat_zero_address_as_burn: constants.treat_zero_address_as_burn,
+            metadata_frozen: constants.metadata_frozen,
+            require_registration: constants.require_registration,
+            default_allowance_expiry: constants.default_allowance_expiry,
+            min_holding_period: constants.min_holding_period,
+            zero_approve_revokes: constants.zero_approve_revokes,
+            clamp_transfer_from: constants.clamp_transfer_from,
+            dust_threshold: constants.burn_dust_threshold.map(Uint128::from),
+            min_total_supply: constants.min_total_supply.into(),
+            emit_sequence: constants.emit_sequence,
+            price_oracle,
+            transfer_guard,
+            reward_pool,
+            metadata_uri: constants.metadata_uri,
+            mint_admin,
+            disabled_handlers: storage.disabled_handlers()?,
+        },
+    })
+}
+
+fn query_balance_by_tag<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    tag: String,
+    owner_key: String,
+) -> StdResult<QueryResponse> {
+    check_owner_query_key(&deps.storage, &owner_key)?;
+    let accounts = get_tag_accounts(&deps.storage, &tag)?;
+    let balances = ReadOnlyBalances::from_storage(&deps.storage);
+    let mut balance = TokenAmount(0);
+    for account in &accounts {
+        balance = balance
+            .checked_add(balances.balance(account))
+            .ok_or_else(|| StdError::generic_err("Tagged balance overflow"))?;
+    }
+    to_binary(&QueryResult::BalanceByTag {
+        accounts: accounts.len() as u32,
+        balance: balance.into(),
+    })
+}
+
+fn query_schema_version<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<QueryResponse> {
+    to_binary(&QueryResult::SchemaVersion {
+        version: ReadOnlyContractStorage::from_storage(&deps.storage).schema_version()?,
+    })
+}
+
+fn query_metadata_hash<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<QueryResponse> {
+    let constants = ReadOnlyContractStorage::from_storage(&deps.storage).constants()?;
+    to_binary(&QueryResult::MetadataHash {
+        hash: metadata_hash(&constants.name, &constants.symbol, constants.decimals),
+    })
+}
+
+/// Hex encoded SHA-256 of `name || 0x00 || symbol || 0x00 || decimals`.
+fn metadata_hash(name: &str, symbol: &str, decimals: u8) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(name.as_bytes());
+    hasher.update(&[0u8]);
+    hasher.update(symbol.as_bytes());
+    hasher.update(&[0u8]);
+    hasher.update(&[decimals]);
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn query_total_supply<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<QueryResponse> {
+    to_binary(&QueryResult::TotalSupply {
+        total_supply: ReadOnlyContractStorage::from_storage(&deps.storage)
+            .total_supply()?
+            .into(),
+    })
+}
+
+fn query_supply_stats<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<QueryResponse> {
+    let storage = ReadOnlyContractStorage::from_storage(&deps.storage);
+    let decimals = storage.constants()?.decimals;
+    let stat = |amount: TokenAmount| FormattedAmount {
+        amount: amount.into(),
+        formatted: format_amount(amount.u128(), decimals),
+    };
+    to_binary(&QueryResult::SupplyStats {
+        total_supply: stat(storage.total_supply()?),
+        minted: stat(storage.total_minted()?),
+        burned: stat(storage.total_burned()?),
+    })
+}
+
+/// Formats `amount` as a decimal number with `decimals` fractional digits.
+fn format_amount(amount: u128, decimals: u8) -> String {
+    let decimals = decimals as usize;
+    let digits = format!("{:0>width$}", amount, width = decimals + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - decimals);
+    if fraction.is_empty() {
+        integer.to_string()
+    } else {
+        format!("{}.{}", integer, fraction)
+    }
+}
+
+/// Formats `amount` with only the first `display_decimals` of `decimals`
+/// fractional digits, truncating the rest.
+fn format_amount_truncated(amount: u128, decimals: u8, display_decimals: u8) -> StdResult<String> {
+    if display_decimals > decimals {
+        return Err(StdError::generic_err(format!(
+            "Display decimals can not exceed token decimals {}",
+            decimals
+        )));
+    }
+    let truncated = 10u128
+        .checked_pow((decimals - display_decimals) as u32)
+        .map_or(0, |divisor| amount / divisor);
+    Ok(format_amount(truncated, display_decimals))
+}
+
+fn query_formatted_balance<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: HumanAddr,
+    display_decimals: Option<u8>,
+) -> StdResult<QueryResponse> {
+    let decimals = ReadOnlyContractStorage::from_storage(&deps.storage)
+        .constants()?
+        .decimals;
+    let address = deps.api.canonical_address(&address)?;
+    let balance = ReadOnlyBalances::from_storage(&deps.storage).balance(&address);
+    let formatted = format_amount_truncated(
+        balance.u128(),
+        decimals,
+        display_decimals.unwrap_or(decimals),
+    )?;
+    to_binary(&QueryResult::FormattedBalance {
+        balance: FormattedAmount {
+            amount: balance.into(),
+            formatted,
+        },
+    })
+}
+
+fn query_balance_of<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: HumanAddr,
+) -> StdResult<QueryResponse> {
+    let address = deps.api.canonical_address(&address)?;
+    let balances = ReadOnlyBalances::from_storage(&deps.storage);
+    let balance = balances.balance(&address);
+    to_binary(&QueryResult::BalanceOf {
+        balance: Uint128::from(balance),
+    })
+}
+
+fn query_priced_value<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: HumanAddr,
+) -> StdResult<QueryResponse> {
+    let constants = ReadOnlyContractStorage::from_storage(&deps.storage).constants()?;
+    let oracle = constants
+        .price_oracle
+        .ok_or_else(|| StdError::generic_err("Price oracle is not configured"))?;
+
+    let address = deps.api.canonical_address(&address)?;
+    let balance = ReadOnlyBalances::from_storage(&deps.storage).balance(&address);
+
+    let request = QueryRequest::Wasm(WasmQuery::Smart {
+        contract_addr: deps.api.human_address(&oracle.contract)?,
+        callback_code_hash: oracle.code_hash,
+        msg: to_binary(&OracleQueryMsg::Price {})?,
+    });
+    let price = deps
+        .querier
+        .query::<PriceResponse>(&request)
+        .map_err(|e| StdError::generic_err(format!("OracleQueryFailed: {}", e)))?
+        .price;
+
+    let value = balance
+        .u128()
+        .checked_mul(price.u128())
+        .ok_or_else(|| StdError::generic_err("Priced value overflow"))?;
+    let unit = 10u128
+        .checked_pow(constants.decimals as u32)
+        .ok_or_else(|| StdError::generic_err("Priced value overflow: too many decimals"))?;
+    let value = value / unit;
+    to_binary(&QueryResult::PricedValue {
+        balance: balance.into(),
+        price,
+        value: Uint128(value),
+    })
+}
+
+fn query_pending_rewards<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: HumanAddr,
+) -> StdResult<QueryResponse> {
+    let address = deps.api.canonical_address(&address)?;
+    let reward_per_token =
+        ReadOnlyContractStorage::from_storage(&deps.storage).reward_per_token()?;
+    let balance = ReadOnlyBalances::from_storage(&deps.storage).balance(&address);
+    let debt = get_reward_debt(&deps.storage, &address)?;
+    to_binary(&QueryResult::PendingRewards {
+        rewards: pending_rewards(balance, reward_per_token, &debt)?.into(),
+    })
+}
+
+fn query_balance_detail<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: HumanAddr,
+) -> StdResult<QueryResponse> {
+    let address = deps.api.canonical_address(&address)?;
+    let balances = ReadOnlyBalances::from_storage(&deps.storage);
+    let balance = balances.balance(&address);
+    to_binary(&QueryResult::BalanceDetail {
+        balance: Uint128::from(balance),
+        ever_held: is_seen(&deps.storage, &address),
+    })
+}
+
+fn query_convert_amount<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    amount: Uint128,
+    target_decimals: u8,
+    rounding: Rounding,
+) -> StdResult<QueryResponse> {
+    let decimals = ReadOnlyContractStorage::from_storage(&deps.storage)
+        .constants()?
+        .decimals;
+    let (amount, precision_lost) =
+        convert_amount(amount.u128(), decimals, target_decimals, rounding)?;
+    to_binary(&QueryResult::ConvertAmount {
+        amount: Uint128(amount),
+        precision_lost,
+    })
+}
+
+/// Rescales `amount` from `decimals` to `target_decimals`.
+/// Returns converted amount and whether precision was lost during conversion.
+fn convert_amount(
+    amount: u128,
+    decimals: u8,
+    target_decimals: u8,
+    rounding: Rounding,
+) -> StdResult<(u128, bool)> {
+    if target_decimals >= decimals {
+        let amount = 10u128
+            .checked_pow((target_decimals - decimals) as u32)
+            .and_then(|factor| amount.checked_mul(factor))
+            .ok_or_else(|| StdError::generic_err("Converted amount overflow"))?;
+        Ok((amount, false))
+    } else {
+        let (quotient, remainder) = match 10u128.checked_pow((decimals - target_decimals) as u32) {
+            Some(divisor) => (amount / divisor, amount % divisor),
+            None => (0, amount),
+        };
+        let precision_lost = remainder != 0;
+        match rounding {
+            Rounding::Up if precision_lost => Ok((quotient + 1, precision_lost)),
+            _ => Ok((quotient, precision_lost)),
+        }
+    }
+}
+
+fn query_allowances_batch<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    pairs: Vec<AllowancePair>,
+    current_height: u64,
+) -> StdResult<QueryResponse> {
+    if pairs.len() > MAX_ALLOWANCES_BATCH {
+        return Err(StdError::generic_err(format!(
+            "Can not query more than {} allowances at once",
+            MAX_ALLOWANCES_BATCH
+        )));
+    }
+
+    let mut allowances = Vec::with_capacity(pairs.len());
+    let mut expirations = Vec::with_capacity(pairs.len());
+    for pair in pairs {
+        let owner = deps.api.canonical_address(&pair.owner)?;
+        let spender = deps.api.canonical_address(&pair.spender)?;
+        let allowance = get_allowance(&deps.storage, &owner, &spender)?;
+        allowances.push(unexpired_amount(&allowance, current_height).into());
+        expirations.push(allowance.expiration);
+    }
+
+    to_binary(&QueryResult::AllowancesBatch {
+        allowances,
+        expirations,
+    })
+}
+
+fn query_pullable_amount<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    owner: HumanAddr,
+    spender: HumanAddr,
+    current_height: u64,
+) -> StdResult<QueryResponse> {
+    let owner = deps.api.canonical_address(&owner)?;
+    let spender = deps.api.canonical_address(&spender)?;
+    let balance = ReadOnlyBalances::from_storage(&deps.storage).balance(&owner);
+    let allowance = get_allowance(&deps.storage, &owner, &spender)?;
+    let amount = unexpired_amount(&allowance, current_height);
+    to_binary(&QueryResult::PullableAmount {
+        pullable: balance.min(amount).into(),
+        balance: balance.into(),
+        allowance: amount.into(),
+        expiration: allowance.expiration,
+    })
+}
+
+/// Amount of the allowance usable at `height`, zero once it expired.
+fn unexpired_amount(allowance: &Allowance, height: u64) -> TokenAmount {
+    if allowance.is_expired(height) {
+        TokenAmount(0)
+    } else {
+        allowance.amount
+    }
+}
+
+fn query_expired_allowances<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    current_height: u64,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<QueryResponse> {
+    let start = start_after.map_or(0, |index| index.saturating_add(1));
+    let limit = limit.unwrap_or(MAX_PAGE_LIMIT).min(MAX_PAGE_LIMIT).max(1) as u64;
+    let count = allowance_count(&deps.storage)?;
+    let end = start.saturating_add(limit).min(count);
+
+    let mut allowances = vec![];
+    for index in start..end {
+        if let Some(entry) = get_allowance_entry(&deps.storage, index)? {
+            let allowance = get_allowance(&deps.storage, &entry.owner, &entry.spender)?;
+            if allowance.is_expired(current_height) {
+                allowances.push(AllowancePair {
+                    owner: deps.api.human_address(&entry.owner)?,
+                    spender: deps.api.human_address(&entry.spender)?,
+                });
+            }
+        }
+    }
+
+    to_binary(&QueryResult::ExpiredAllowances {
+        allowances,
+        next: if end < count { Some(end - 1) } else { None },
+    })
+}
+
+fn query_allowance_detail<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    owner: HumanAddr,
+    spender: HumanAddr,
+    current_height: u64,
+) -> StdResult<QueryResponse> {
+    let owner = deps.api.canonical_address(&owner)?;
+    let spender = deps.api.canonical_address(&spender)?;
+    let balance = ReadOnlyBalances::from_storage(&deps.storage).balance(&owner);
+    let allowance = unexpired_amount(
+        &get_allowance(&deps.storage, &owner, &spender)?,
+        current_height,
+    );
+    to_binary(&QueryResult::AllowanceDetail {
+        allowance: allowance.into(),
+        balance: balance.into(),
+        ratio_bps: allowance_ratio_bps(allowance.u128(), balance.u128()),
+    })
+}
+
+/// Ratio of `allowance` to `balance` in basis points capped at 10000.
+fn allowance_ratio_bps(allowance: u128, balance: u128) -> u16 {
+    if allowance == 0 {
+        0
+    } else if allowance >= balance {
+        10_000
+    } else {
+        let ratio = match allowance.checked_mul(10_000) {
+            Some(scaled) => scaled / balance,
+            // dropping low bits of both amounts keeps their ratio close enough
+            None => (allowance >> 14) * 10_000 / (balance >> 14),
+        };
+        // allowance is below balance, so the ratio is below 10000
+        ratio.min(9_999) as u16
+    }
+}
+
+fn query_total_approved_by<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    owner: HumanAddr,
+    current_height: u64,
+    owner_key: String,
+) -> StdResult<QueryResponse> {
+    check_owner_query_key(&deps.storage, &owner_key)?;
+    let owner = deps.api.canonical_address(&owner)?;
+    let mut total = TokenAmount(0);
+    for spender in get_allowance_spenders(&deps.storage, &owner)? {
+        let allowance = get_allowance(&deps.storage, &owner, &spender)?;
+        if !allowance.is_expired(current_height) {
+            total = total.saturating_add(allowance.amount);
+        }
+    }
+    to_binary(&QueryResult::TotalApprovedBy {
+        total: total.into(),
+    })
+}
+
+fn query_can_transfer<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    from: HumanAddr,
+    to: HumanAddr,
+    value: Uint128,
+    current_height: u64,
+    deadline: Option<u64>,
+) -> StdResult<QueryResponse> {
+    let reason = match check_transfer(deps, &from, &to, value, current_height, deadline) {
+        Ok(()) => None,
+        Err(StdError::GenericErr { msg, .. }) => Some(msg),
+        Err(e) => Some(e.to_string()),
+    };
+    to_binary(&QueryResult::CanTransfer {
+        allowed: reason.is_none(),
+        reason,
+    })
+}
+
+/// Runs the Transfer handler checks and token move without changing any state.
+fn check_transfer<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    from: &HumanAddr,
+    to: &HumanAddr,
+    value: Uint128,
+    height: u64,
+    deadline: Option<u64>,
+) -> StdResult<()> {
+    let storage = ReadOnlyContractStorage::from_storage(&deps.storage);
+    if storage.disabled_handlers()?.contains(&HandlerId::Transfer) {
+        return Err(StdError::generic_err(format!(
+            "HandlerDisabled: {:?} handler is disabled",
+            HandlerId::Transfer
+        )));
+    }
+    check_deadline(height, deadline)?;
+
+    let (sender, target) = check_transfer_target(deps, from, to, value, height)?;
+    dry_run(&deps.storage, |storage| match target {
+        TransferTarget::Account(recipient) | TransferTarget::Fallback(recipient) => {
+            move_tokens(storage, &sender, &recipient, value.into(), height)
+        }
+        TransferTarget::Burn => burn_tokens(storage, &sender, value.into()),
+    })
+}
+
+fn query_supported_features<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<QueryResponse> {
+    let storage = ReadOnlyContractStorage::from_storage(&deps.storage);
+    let constants = storage.constants()?;
+    to_binary(&QueryResult::SupportedFeatures {
+        reserve: constants.reserve.is_some(),
+        minters: !storage.minters()?.is_empty(),
+        reward_pool: storage.reward_pool()?.is_some(),
+        verbose_logging: constants.verbose_logging,
+        disabled_handlers: !storage.disabled_handlers()?.is_empty(),
+        debug: cfg!(feature = "debug"),
+        transfer_guard: storage.transfer_guard()?.is_some(),
+        price_oracle: constants.price_oracle.is_some(),
+        max_wallet_balance: constants.max_wallet_balance.is_some(),
+        require_registration: constants.require_registration,
+        min_holding_period: constants.min_holding_period.is_some(),
+        sequence_logging: constants.emit_sequence,
+    })
+}
+
+fn query_tips<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<QueryResponse> {
+    to_binary(&QueryResult::Tips {
+        tips: ReadOnlyContractStorage::from_storage(&deps.storage).tips()?,
+    })
+}
+
+fn query_raw_storage<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    key: String,
+    owner_key: String,
+) -> StdResult<QueryResponse> {
+    check_owner_query_key(&deps.storage, &owner_key)?;
+    let raw_key = match key.as_str() {
+        "constants" => KEY_CONSTANTS,
+        "total_supply" => KEY_TOTAL_SUPPLY,
+        _ => {
+            return Err(StdError::generic_err(format!(
+                "Key {} can not be queried",
+                key
+            )))
+        }
+    };
+    let data = ReadOnlyContractStorage::from_storage(&deps.storage)
+        .raw(raw_key)
+        .ok_or_else(|| StdError::generic_err(format!("No value stored under {}", key)))?;
+    to_binary(&QueryResult::RawStorage { data: Binary(data) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msg::{InitialAllowance, ReserveConfig};
+    use crate::state::StorageKeys;
+    use cosmwasm_std::testing::{
+        mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR,
+    };
+    use cosmwasm_std::{coins, from_binary, from_slice, Empty, QuerierResult, ReadonlyStorage};
+    use cosmwasm_storage::ReadonlyPrefixedStorage;
+    use std::cell::Cell;
+
+    fn initialize() -> Extern<MockStorage, MockApi, MockQuerier> {
+        let mut deps = mock_dependencies(20, &[]);
+
+        let msg = InitMsg {
+            name: "test".to_string(),
+            symbol: "!@#$".to_string(),
+            decimals: 69,
+            ..Default::default()
+        };
+        let env = mock_env("creator", &coins(1000, "earth"));
+
+        let res = init(&mut deps, env, msg).unwrap();
+        assert_eq!(0, res.messages.len());
+        deps
+    }
+
+    fn initialize_with(msg: InitMsg) -> Extern<MockStorage, MockApi, MockQuerier> {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            name: "test".to_string(),
+            symbol: "!@#$".to_string(),
+            decimals: 6,
+            ..msg
+        };
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        deps
+    }
+
+    fn deposit(deps: &mut Extern<MockStorage, MockApi, MockQuerier>, to: &str, value: u128) {
+        let handle_msg = HandleMsg::DepositTo {
+            to: HumanAddr::from(to),
+            value: Uint128(value),
+        };
+        match handle(deps, mock_env("creator", &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+    }
+
+    fn balance_of(deps: &Extern<MockStorage, MockApi, MockQuerier>, address: &str) -> u128 {
+        let query_msg = QueryMsg::BalanceOf {
+            address: HumanAddr::from(address),
+        };
+        match from_binary(&query(deps, query_msg).unwrap()).unwrap() {
+            QueryResult::BalanceOf { balance } => balance.u128(),
+            _ => panic!("unexpected"),
+        }
+    }
+
+    fn allowance_of(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        owner: &str,
+        spender: &str,
+    ) -> u128 {
+        let handle_msg = HandleMsg::Allowance {
+            owner: HumanAddr::from(owner),
+            spender: HumanAddr::from(spender),
+        };
+        let res = handle(deps, mock_env(owner, &[]), handle_msg).unwrap();
+        match from_binary(&res.data.unwrap()).unwrap() {
+            HandleResult::Allowance { value, .. } => value.u128(),
+            _ => panic!("unexpected"),
+        }
+    }
+
+    fn approve_to(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        owner: &str,
+        spender: &str,
+        value: u128,
+    ) {
+        let handle_msg = HandleMsg::Approve {
+            spender: HumanAddr::from(spender),
+            value: Uint128(value),
+            expiration: None,
+        };
+        match handle(deps, mock_env(owner, &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+    }
+
+    fn total_supply(deps: &Extern<MockStorage, MockApi, MockQuerier>) -> u128 {
+        match from_binary(&query(deps, QueryMsg::TotalSupply {}).unwrap()).unwrap() {
+            QueryResult::TotalSupply { total_supply } => total_supply.u128(),
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn initialization() {
+        let deps = initialize();
+
+        let res = query(&deps, QueryMsg::TotalSupply {}).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryResult::TotalSupply { total_supply } => assert_eq!(0, total_supply.u128()),
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn handle_deposit_to() {
+        let mut deps = initialize();
+        let address = HumanAddr::from("address");
+        let handle_msg = HandleMsg::DepositTo {
+            to: address.clone(),
+            value: Uint128(69),
+        };
+        match handle(&mut deps, mock_env("creator", &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+
+        // checking new balance
+        let res = query(&deps, QueryMsg::BalanceOf { address }).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryResult::BalanceOf { balance } => assert_eq!(69, balance.u128()),
+            _ => panic!("unexpected"),
+        }
+
+        // checking new total supply
+        let res = query(&deps, QueryMsg::TotalSupply {}).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryResult::TotalSupply { total_supply } => assert_eq!(69, total_supply.u128()),
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn handle_deposit_to_invalid_sender() {
+        let mut deps = initialize();
+        let address = HumanAddr::from("address");
+        let handle_msg = HandleMsg::DepositTo {
+            to: address.clone(),
+            value: Uint128(69),
+        };
+        match handle(&mut deps, mock_env("bob", &[]), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            _ => {}
+        }
+
+        // checking balance
+        let res = query(&deps, QueryMsg::BalanceOf { address }).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryResult::BalanceOf { balance } => assert_eq!(0, balance.u128()),
+            _ => panic!("unexpected"),
+        }
+
+        // checking new total supply
+        let res = query(&deps, QueryMsg::TotalSupply {}).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryResult::TotalSupply { total_supply } => assert_eq!(0, total_supply.u128()),
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn handle_deposit_to_invalid_founds() {
+        let mut deps = initialize();
+        let address = HumanAddr::from("address");
+        let handle_msg = HandleMsg::DepositTo {
+            to: address.clone(),
+            value: Uint128(0),
+        };
+        match handle(&mut deps, mock_env("bob", &[]), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            _ => {}
+        }
+
+        // checking balance
+        let res = query(&deps, QueryMsg::BalanceOf { address }).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryResult::BalanceOf { balance } => assert_eq!(0, balance.u128()),
+            _ => panic!("unexpected"),
+        }
+
+        // checking new total supply
+        let res = query(&deps, QueryMsg::TotalSupply {}).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryResult::TotalSupply { total_supply } => assert_eq!(0, total_supply.u128()),
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn handle_burn_from() {
+        let mut deps = initialize();
+        let address = HumanAddr::from("address");
+        let handle_msg = HandleMsg::DepositTo {
+            to: address.clone(),
+            value: Uint128(69),
+        };
+        match handle(&mut deps, mock_env("creator", &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+
+        let handle_msg = HandleMsg::BurnFrom {
+            from: address.clone(),
+            value: Uint128(9),
+        };
+        match handle(&mut deps, mock_env("creator", &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+
+        // checking new balance
+        let res = query(&deps, QueryMsg::BalanceOf { address }).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryResult::BalanceOf { balance } => assert_eq!(60, balance.u128()),
+            _ => panic!("unexpected"),
+        }
+
+        // checking new total supply
+        let res = query(&deps, QueryMsg::TotalSupply {}).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryResult::TotalSupply { total_supply } => assert_eq!(60, total_supply.u128()),
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn handle_burn_from_owner() {
+        let mut deps = initialize();
+        let address = HumanAddr::from("address");
+        let handle_msg = HandleMsg::DepositTo {
+            to: address.clone(),
+            value: Uint128(69),
+        };
+        match handle(&mut deps, mock_env("creator", &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+
+        let handle_msg = HandleMsg::BurnFrom {
+            from: address.clone(),
+            value: Uint128(9),
+        };
+        match handle(&mut deps, mock_env(address.clone(), &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+
+        // checking new balance
+        let res = query(&deps, QueryMsg::BalanceOf { address }).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryResult::BalanceOf { balance } => assert_eq!(60, balance.u128()),
+            _ => panic!("unexpected"),
+        }
+
+        // checking new total supply
+        let res = query(&deps, QueryMsg::TotalSupply {}).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryResult::TotalSupply { total_supply } => assert_eq!(60, total_supply.u128()),
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn handle_burn_from_invalid_sender() {
+        let mut deps = initialize();
+        let address = HumanAddr::from("address");
+        let handle_msg = HandleMsg::DepositTo {
+            to: address.clone(),
+            value: Uint128(69),
+        };
+        match handle(&mut deps, mock_env("creator", &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+
+        let handle_msg = HandleMsg::BurnFrom {
+            from: address.clone(),
+            value: Uint128(9),
+        };
+        match handle(&mut deps, mock_env("bob", &[]), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(_) => {}
+        }
+
+        // checking balance
+        let res = query(&deps, QueryMsg::BalanceOf { address }).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryResult::BalanceOf { balance } => assert_eq!(69, balance.u128()),
+            _ => panic!("unexpected"),
+        }
+
+        // checking new total supply
+        let res = query(&deps, QueryMsg::TotalSupply {}).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryResult::TotalSupply { total_supply } => assert_eq!(69, total_supply.u128()),
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn handle_burn_from_invalid_founds() {
+        let mut deps = initialize();
+        let address = HumanAddr::from("address");
+        let handle_msg = HandleMsg::DepositTo {
+            to: address.clone(),
+            value: Uint128(0),
+        };
+        match handle(&mut deps, mock_env("creator", &[]), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(_) => {}
+        }
+
+        // checking balance
+        let res = query(&deps, QueryMsg::BalanceOf { address }).unwrap();
+        match from_binary(&res).unwrap() {
             QueryResult::BalanceOf { balance } => assert_eq!(0, balance.u128()),
             _ => panic!("unexpected"),
         }
 
-        // checking new total supply
-        let res = query(&deps, QueryMsg::TotalSupply {}).unwrap();
-        match from_binary(&res).unwrap() {
-            QueryResult::TotalSupply { total_supply } => assert_eq!(0, total_supply.u128()),
-            _ => panic!("unexpected"),
+        // checking new total supply
+        let res = query(&deps, QueryMsg::TotalSupply {}).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryResult::TotalSupply { total_supply } => assert_eq!(0, total_supply.u128()),
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn handle_disabled_handlers() {
+        let mut deps = initialize();
+        let address = HumanAddr::from("address");
+        let handle_msg = HandleMsg::DepositTo {
+            to: address.clone(),
+            value: Uint128(69),
+        };
+        match handle(&mut deps, mock_env("creator", &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+
+        let handle_msg = HandleMsg::SetDisabledHandlers {
+            handlers: vec![HandlerId::Transfer],
+        };
+        match handle(&mut deps, mock_env("bob", &[]), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(_) => {}
+        }
+
+        let handle_msg = HandleMsg::SetDisabledHandlers {
+            handlers: vec![HandlerId::Transfer],
+        };
+        match handle(&mut deps, mock_env("creator", &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+
+        let handle_msg = HandleMsg::Transfer {
+            to: HumanAddr::from("bob"),
+            value: Uint128(9),
+            log_note: None,
+            deadline: None,
+        };
+        match handle(&mut deps, mock_env(address.clone(), &[]), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("HandlerDisabled")),
+        }
+
+        let handle_msg = HandleMsg::Approve {
+            spender: HumanAddr::from("bob"),
+            value: Uint128(9),
+            expiration: None,
+        };
+        match handle(&mut deps, mock_env(address.clone(), &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+
+        let handle_msg = HandleMsg::SetDisabledHandlers { handlers: vec![] };
+        match handle(&mut deps, mock_env("creator", &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+
+        let handle_msg = HandleMsg::Transfer {
+            to: HumanAddr::from("bob"),
+            value: Uint128(9),
+            log_note: None,
+            deadline: None,
+        };
+        match handle(&mut deps, mock_env(address.clone(), &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+
+        // checking new balance
+        let res = query(&deps, QueryMsg::BalanceOf { address }).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryResult::BalanceOf { balance } => assert_eq!(60, balance.u128()),
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn handle_transfer_receipts() {
+        let mut deps = initialize();
+        let address = HumanAddr::from("address");
+        let handle_msg = HandleMsg::DepositTo {
+            to: address.clone(),
+            value: Uint128(69),
+        };
+        match handle(&mut deps, mock_env("creator", &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+
+        let mut env = mock_env(address.clone(), &[]);
+        env.block.height = 420;
+
+        for expected_index in 0..2 {
+            let handle_msg = HandleMsg::Transfer {
+                to: HumanAddr::from("bob"),
+                value: Uint128(9),
+                log_note: None,
+                deadline: None,
+            };
+            let res = handle(&mut deps, env.clone(), handle_msg).unwrap();
+            match from_binary(&res.data.unwrap()).unwrap() {
+                HandleResult::Transfer {
+                    status,
+                    block_height,
+                    tx_index,
+                } => {
+                    assert_eq!(Status::Success, status);
+                    assert_eq!(420, block_height);
+                    assert_eq!(expected_index, tx_index);
+                }
+                _ => panic!("unexpected"),
+            }
+        }
+    }
+
+    #[test]
+    fn init_reserved_symbol() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            name: "test".to_string(),
+            symbol: "usdc".to_string(),
+            decimals: 6,
+            ..Default::default()
+        };
+        match init(&mut deps, mock_env("creator", &[]), msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("ReservedSymbol")),
+        }
+
+        let msg = InitMsg {
+            name: "test".to_string(),
+            symbol: "USDX".to_string(),
+            decimals: 6,
+            ..Default::default()
+        };
+        match init(&mut deps, mock_env("creator", &[]), msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+    }
+
+    /// Answers queries to "factory" with the registry contract.
+    struct RegistryQuerier<'a> {
+        registry: &'a Extern<MockStorage, MockApi, MockQuerier>,
+    }
+
+    impl Querier for RegistryQuerier<'_> {
+        fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+            let request: QueryRequest<Empty> = from_slice(bin_request).unwrap();
+            match request {
+                QueryRequest::Wasm(WasmQuery::Smart {
+                    contract_addr, msg, ..
+                }) => {
+                    assert_eq!(HumanAddr::from("factory"), contract_addr);
+                    Ok(query(self.registry, from_binary(&msg).unwrap()))
+                }
+                _ => panic!("unexpected"),
+            }
+        }
+    }
+
+    #[test]
+    fn init_symbol_registry() {
+        let mut factory = initialize();
+        let reserve_msg = HandleMsg::ReserveSymbols {
+            symbols: vec!["acme".to_string()],
+        };
+        match handle(&mut factory, mock_env("alice", &[]), reserve_msg.clone()) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("Only contract owner")),
+        }
+
+        let init_token = |factory: &Extern<MockStorage, MockApi, MockQuerier>, symbol: &str| {
+            let mut deps = Extern {
+                storage: MockStorage::new(),
+                api: MockApi::new(20),
+                querier: RegistryQuerier { registry: factory },
+            };
+            let msg = InitMsg {
+                name: "test".to_string(),
+                symbol: symbol.to_string(),
+                decimals: 6,
+                symbol_registry: Some(SymbolRegistryInfo {
+                    address: HumanAddr::from("factory"),
+                    code_hash: "hash".to_string(),
+                }),
+                ..Default::default()
+            };
+            init(&mut deps, mock_env("creator", &[]), msg)
+        };
+
+        // inits before the symbol is reserved succeed
+        assert!(init_token(&factory, "ACME").is_ok());
+        handle(&mut factory, mock_env("creator", &[]), reserve_msg).unwrap();
+
+        // subsequent inits are rejected regardless of case
+        for symbol in &["ACME", "Acme", "USDC"] {
+            match init_token(&factory, symbol) {
+                Ok(_) => panic!("should have failed"),
+                Err(e) => assert!(e.to_string().contains("ReservedSymbol")),
+            }
+        }
+        assert!(init_token(&factory, "ACMX").is_ok());
+
+        // the registry answers the same check directly
+        let query_msg = QueryMsg::IsReservedSymbol {
+            symbol: "ACME".to_string(),
+        };
+        match from_binary(&query(&factory, query_msg).unwrap()).unwrap() {
+            QueryResult::IsReservedSymbol { reserved } => assert!(reserved),
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn query_balance_detail_ever_held() {
+        let mut deps = initialize();
+        let address = HumanAddr::from("address");
+
+        // never seen address
+        let res = query(
+            &deps,
+            QueryMsg::BalanceDetail {
+                address: address.clone(),
+            },
+        )
+        .unwrap();
+        match from_binary(&res).unwrap() {
+            QueryResult::BalanceDetail { balance, ever_held } => {
+                assert_eq!(0, balance.u128());
+                assert!(!ever_held);
+            }
+            _ => panic!("unexpected"),
+        }
+
+        let handle_msg = HandleMsg::DepositTo {
+            to: address.clone(),
+            value: Uint128(69),
+        };
+        match handle(&mut deps, mock_env("creator", &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+
+        // held address
+        let res = query(
+            &deps,
+            QueryMsg::BalanceDetail {
+                address: address.clone(),
+            },
+        )
+        .unwrap();
+        match from_binary(&res).unwrap() {
+            QueryResult::BalanceDetail { balance, ever_held } => {
+                assert_eq!(69, balance.u128());
+                assert!(ever_held);
+            }
+            _ => panic!("unexpected"),
+        }
+
+        let handle_msg = HandleMsg::BurnFrom {
+            from: address.clone(),
+            value: Uint128(69),
+        };
+        match handle(&mut deps, mock_env("creator", &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+
+        // burned to zero address
+        let res = query(&deps, QueryMsg::BalanceDetail { address }).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryResult::BalanceDetail { balance, ever_held } => {
+                assert_eq!(0, balance.u128());
+                assert!(ever_held);
+            }
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn handle_deposit_to_reserves() {
+        let mut deps = mock_dependencies(20, &coins(1000, "uscrt"));
+        let msg = InitMsg {
+            name: "test".to_string(),
+            symbol: "!@#$".to_string(),
+            decimals: 6,
+            reserve: Some(ReserveConfig {
+                denom: "uscrt".to_string(),
+                backing_ratio_bps: 5_000,
+            }),
+            ..Default::default()
+        };
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+
+        let address = HumanAddr::from("address");
+        let handle_msg = HandleMsg::DepositTo {
+            to: address.clone(),
+            value: Uint128(2000),
+        };
+        match handle(&mut deps, mock_env("creator", &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+
+        let handle_msg = HandleMsg::DepositTo {
+            to: address.clone(),
+            value: Uint128(2),
+        };
+        match handle(&mut deps, mock_env("creator", &[]), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("Undercollateralized")),
+        }
+
+        // checking total supply
+        let res = query(&deps, QueryMsg::TotalSupply {}).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryResult::TotalSupply { total_supply } => assert_eq!(2000, total_supply.u128()),
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn handle_force_redeem_all() {
+        let mut deps = mock_dependencies(20, &coins(1000, "uscrt"));
+        let msg = InitMsg {
+            name: "test".to_string(),
+            symbol: "!@#$".to_string(),
+            decimals: 6,
+            reserve: Some(ReserveConfig {
+                denom: "uscrt".to_string(),
+                backing_ratio_bps: 5_000,
+            }),
+            ..Default::default()
+        };
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        deposit(&mut deps, "alice", 1000);
+        deposit(&mut deps, "bob", 501);
+        deposit(&mut deps, "carol", 300);
+
+        let handle_msg = HandleMsg::ForceRedeemAll {
+            accounts: vec![HumanAddr::from("alice"), HumanAddr::from("bob")],
+        };
+        match handle(&mut deps, mock_env("alice", &[]), handle_msg.clone()) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("Only contract owner")),
+        }
+
+        let res = handle(&mut deps, mock_env("creator", &[]), handle_msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                CosmosMsg::Bank(BankMsg::Send {
+                    from_address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                    to_address: HumanAddr::from("alice"),
+                    amount: coins(500, "uscrt"),
+                }),
+                CosmosMsg::Bank(BankMsg::Send {
+                    from_address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                    to_address: HumanAddr::from("bob"),
+                    amount: coins(250, "uscrt"),
+                }),
+            ]
+        );
+        match from_binary(&res.data.unwrap()).unwrap() {
+            HandleResult::ForceRedeemAll { redeemed, .. } => assert_eq!(1501, redeemed.u128()),
+            _ => panic!("unexpected"),
+        }
+        assert_eq!(0, balance_of(&deps, "alice"));
+        assert_eq!(0, balance_of(&deps, "bob"));
+        assert_eq!(300, balance_of(&deps, "carol"));
+        assert_eq!(300, total_supply(&deps));
+    }
+
+    #[test]
+    fn handle_force_redeem_all_without_reserve() {
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 100);
+
+        let handle_msg = HandleMsg::ForceRedeemAll {
+            accounts: vec![HumanAddr::from("alice")],
+        };
+        match handle(&mut deps, mock_env("creator", &[]), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("no native reserves")),
+        }
+        assert_eq!(100, balance_of(&deps, "alice"));
+    }
+
+    #[test]
+    fn query_convert_amount_decimals() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            name: "test".to_string(),
+            symbol: "!@#$".to_string(),
+            decimals: 6,
+            ..Default::default()
+        };
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+
+        // 6 -> 18
+        let query_msg = QueryMsg::ConvertAmount {
+            amount: Uint128(1_500_000),
+            target_decimals: 18,
+            rounding: Rounding::Down,
+        };
+        let res = query(&deps, query_msg).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryResult::ConvertAmount {
+                amount,
+                precision_lost,
+            } => {
+                assert_eq!(1_500_000_000_000_000_000, amount.u128());
+                assert!(!precision_lost);
+            }
+            _ => panic!("unexpected"),
+        }
+
+        // 6 -> 18 overflow
+        let query_msg = QueryMsg::ConvertAmount {
+            amount: Uint128(u128::MAX),
+            target_decimals: 18,
+            rounding: Rounding::Down,
+        };
+        assert!(query(&deps, query_msg).is_err());
+    }
+
+    #[test]
+    fn convert_amount_scale_down() {
+        // 18 -> 6 without remainder
+        assert_eq!(
+            (1_500_000, false),
+            convert_amount(1_500_000_000_000_000_000, 18, 6, Rounding::Down).unwrap()
+        );
+
+        // 18 -> 6 with remainder
+        assert_eq!(
+            (1_500_000, true),
+            convert_amount(1_500_000_000_000_000_001, 18, 6, Rounding::Down).unwrap()
+        );
+        assert_eq!(
+            (1_500_001, true),
+            convert_amount(1_500_000_000_000_000_001, 18, 6, Rounding::Up).unwrap()
+        );
+    }
+
+    #[test]
+    fn handle_batch_burn() {
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 50);
+        deposit(&mut deps, "bob", 30);
+
+        let handle_msg = HandleMsg::BatchBurn {
+            actions: vec![
+                BurnAction {
+                    owner: HumanAddr::from("alice"),
+                    value: Uint128(20),
+                },
+                BurnAction {
+                    owner: HumanAddr::from("bob"),
+                    value: Uint128(10),
+                },
+            ],
+        };
+        match handle(&mut deps, mock_env("creator", &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+
+        assert_eq!(30, balance_of(&deps, "alice"));
+        assert_eq!(20, balance_of(&deps, "bob"));
+        assert_eq!(50, total_supply(&deps));
+    }
+
+    #[test]
+    fn handle_batch_burn_underflow() {
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 50);
+        deposit(&mut deps, "bob", 30);
+
+        let handle_msg = HandleMsg::BatchBurn {
+            actions: vec![
+                BurnAction {
+                    owner: HumanAddr::from("alice"),
+                    value: Uint128(20),
+                },
+                BurnAction {
+                    owner: HumanAddr::from("bob"),
+                    value: Uint128(31),
+                },
+            ],
+        };
+        match handle(&mut deps, mock_env("creator", &[]), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(_) => {}
+        }
+
+        assert_eq!(50, balance_of(&deps, "alice"));
+        assert_eq!(30, balance_of(&deps, "bob"));
+        assert_eq!(80, total_supply(&deps));
+    }
+
+    #[test]
+    fn handle_batch_burn_max_batch_size() {
+        let mut deps = initialize_with(InitMsg {
+            max_batch_size: Some(2),
+            ..Default::default()
+        });
+        deposit(&mut deps, "alice", 50);
+
+        let burn_batch = |size: usize| HandleMsg::BatchBurn {
+            actions: vec![
+                BurnAction {
+                    owner: HumanAddr::from("alice"),
+                    value: Uint128(1),
+                };
+                size
+            ],
+        };
+        match handle(&mut deps, mock_env("creator", &[]), burn_batch(3)) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("BatchTooLarge")),
+        }
+        assert_eq!(50, balance_of(&deps, "alice"));
+
+        match handle(&mut deps, mock_env("creator", &[]), burn_batch(2)) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+        assert_eq!(48, balance_of(&deps, "alice"));
+    }
+
+    #[test]
+    fn init_max_batch_size_bound() {
+        let mut deps = mock_dependencies(20, &[]);
+        let init_msg = InitMsg {
+            name: "test".to_string(),
+            symbol: "TST".to_string(),
+            max_batch_size: Some(MAX_BATCH_SIZE + 1),
+            ..Default::default()
+        };
+        assert!(init(&mut deps, mock_env("creator", &[]), init_msg).is_err());
+    }
+
+    #[test]
+    fn handle_transfer_log_note() {
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 69);
+
+        let handle_msg = HandleMsg::Transfer {
+            to: HumanAddr::from("bob"),
+            value: Uint128(9),
+            log_note: Some("for pizza".to_string()),
+            deadline: None,
+        };
+        let res = handle(&mut deps, mock_env("alice", &[]), handle_msg).unwrap();
+        assert_eq!(vec![log("note", "for pizza")], res.log);
+        assert_eq!(60, balance_of(&deps, "alice"));
+        assert_eq!(9, balance_of(&deps, "bob"));
+
+        let handle_msg = HandleMsg::Transfer {
+            to: HumanAddr::from("bob"),
+            value: Uint128(9),
+            log_note: Some("a".repeat(MAX_LOG_NOTE_LEN + 1)),
+            deadline: None,
+        };
+        match handle(&mut deps, mock_env("alice", &[]), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(_) => {}
+        }
+        assert_eq!(60, balance_of(&deps, "alice"));
+    }
+
+    #[test]
+    fn handle_approve_logs() {
+        let handle_msg = HandleMsg::Approve {
+            spender: HumanAddr::from("bob"),
+            value: Uint128(9),
+            expiration: None,
+        };
+
+        let mut deps = initialize();
+        let res = handle(&mut deps, mock_env("alice", &[]), handle_msg.clone()).unwrap();
+        assert_eq!(
+            vec![
+                log("action", "approve"),
+                log("owner", "alice"),
+                log("spender", "bob"),
+            ],
+            res.log
+        );
+
+        let mut deps = initialize_with(InitMsg {
+            verbose_logging: true,
+            ..Default::default()
+        });
+        let res = handle(&mut deps, mock_env("alice", &[]), handle_msg).unwrap();
+        assert_eq!(
+            vec![
+                log("action", "approve"),
+                log("owner", "alice"),
+                log("spender", "bob"),
+                log("amount", "9"),
+            ],
+            res.log
+        );
+    }
+
+    #[test]
+    fn handle_reassign_allowance() {
+        let mut deps = initialize();
+        approve_to(&mut deps, "alice", "bob", 50);
+
+        let handle_msg = HandleMsg::ReassignAllowance {
+            from_spender: HumanAddr::from("bob"),
+            to_spender: HumanAddr::from("carol"),
+            value: Uint128(20),
+        };
+        match handle(&mut deps, mock_env("alice", &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+
+        assert_eq!(30, allowance_of(&mut deps, "alice", "bob"));
+        assert_eq!(20, allowance_of(&mut deps, "alice", "carol"));
+    }
+
+    #[test]
+    fn handle_reassign_allowance_insufficient() {
+        let mut deps = initialize();
+        approve_to(&mut deps, "alice", "bob", 50);
+        approve_to(&mut deps, "alice", "carol", 5);
+
+        let handle_msg = HandleMsg::ReassignAllowance {
+            from_spender: HumanAddr::from("bob"),
+            to_spender: HumanAddr::from("carol"),
+            value: Uint128(51),
+        };
+        match handle(&mut deps, mock_env("alice", &[]), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(_) => {}
+        }
+
+        assert_eq!(50, allowance_of(&mut deps, "alice", "bob"));
+        assert_eq!(5, allowance_of(&mut deps, "alice", "carol"));
+    }
+
+    #[test]
+    fn handle_transfer_with_reward() {
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 100);
+
+        let handle_msg = HandleMsg::SetRewardPool {
+            pool: HumanAddr::from("pool"),
+            max_reward_bps: 500,
+        };
+        match handle(&mut deps, mock_env("creator", &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+
+        let handle_msg = HandleMsg::TransferWithReward {
+            to: HumanAddr::from("bob"),
+            value: Uint128(50),
+            reward_bps: 500,
+        };
+        let res = handle(&mut deps, mock_env("alice", &[]), handle_msg).unwrap();
+        match from_binary(&res.data.unwrap()).unwrap() {
+            HandleResult::TransferWithReward { reward, .. } => assert_eq!(2, reward.u128()),
+            _ => panic!("unexpected"),
+        }
+        assert_eq!(50, balance_of(&deps, "alice"));
+        assert_eq!(48, balance_of(&deps, "bob"));
+        assert_eq!(2, balance_of(&deps, "pool"));
+
+        // reward above the cap
+        let handle_msg = HandleMsg::TransferWithReward {
+            to: HumanAddr::from("bob"),
+            value: Uint128(10),
+            reward_bps: 501,
+        };
+        match handle(&mut deps, mock_env("alice", &[]), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(_) => {}
+        }
+
+        // zero reward is a plain transfer
+        let handle_msg = HandleMsg::TransferWithReward {
+            to: HumanAddr::from("bob"),
+            value: Uint128(10),
+            reward_bps: 0,
+        };
+        match handle(&mut deps, mock_env("alice", &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+        assert_eq!(40, balance_of(&deps, "alice"));
+        assert_eq!(58, balance_of(&deps, "bob"));
+        assert_eq!(2, balance_of(&deps, "pool"));
+
+        // refused reward leaves no partial transfer behind
+        let handle_msg = HandleMsg::SetReceivePolicy {
+            allowed_senders: Some(vec![HumanAddr::from("carol")]),
+        };
+        handle(&mut deps, mock_env("pool", &[]), handle_msg).unwrap();
+        let handle_msg = HandleMsg::TransferWithReward {
+            to: HumanAddr::from("bob"),
+            value: Uint128(20),
+            reward_bps: 500,
+        };
+        match handle(&mut deps, mock_env("alice", &[]), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(_) => {}
+        }
+        assert_eq!(40, balance_of(&deps, "alice"));
+        assert_eq!(58, balance_of(&deps, "bob"));
+    }
+
+    fn start_recovery(deps: &mut Extern<MockStorage, MockApi, MockQuerier>, height: u64) {
+        let handle_msg = HandleMsg::SetGuardian {
+            guardian: Some(HumanAddr::from("guardian")),
+        };
+        match handle(deps, mock_env("alice", &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+
+        let handle_msg = HandleMsg::InitiateRecovery {
+            account: HumanAddr::from("alice"),
+            to: HumanAddr::from("alice2"),
+        };
+        let mut env = mock_env("guardian", &[]);
+        env.block.height = height;
+        match handle(deps, env, handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+    }
+
+    fn finalize_recovery_at(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        height: u64,
+    ) -> StdResult<HandleResponse> {
+        let handle_msg = HandleMsg::FinalizeRecovery {
+            account: HumanAddr::from("alice"),
+        };
+        let mut env = mock_env("guardian", &[]);
+        env.block.height = height;
+        handle(deps, env, handle_msg)
+    }
+
+    #[test]
+    fn handle_recovery() {
+        let mut deps = initialize_with(InitMsg {
+            recovery_delay: Some(10),
+            ..Default::default()
+        });
+        deposit(&mut deps, "alice", 69);
+        start_recovery(&mut deps, 100);
+
+        // delay has not elapsed
+        match finalize_recovery_at(&mut deps, 109) {
+            Ok(_) => panic!("should have failed"),
+            Err(_) => {}
+        }
+        assert_eq!(69, balance_of(&deps, "alice"));
+
+        match finalize_recovery_at(&mut deps, 110) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+        assert_eq!(0, balance_of(&deps, "alice"));
+        assert_eq!(69, balance_of(&deps, "alice2"));
+        assert_eq!(69, total_supply(&deps));
+    }
+
+    #[test]
+    fn handle_recovery_max_wallet_balance() {
+        let mut deps = initialize_with(InitMsg {
+            recovery_delay: Some(10),
+            max_wallet_balance: Some(Uint128(100)),
+            ..Default::default()
+        });
+        deposit(&mut deps, "alice", 69);
+        deposit(&mut deps, "alice2", 50);
+        start_recovery(&mut deps, 100);
+
+        match finalize_recovery_at(&mut deps, 110) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("can not exceed")),
+        }
+        assert_eq!(69, balance_of(&deps, "alice"));
+        assert_eq!(50, balance_of(&deps, "alice2"));
+    }
+
+    #[test]
+    fn handle_recovery_cancel() {
+        let mut deps = initialize_with(InitMsg {
+            recovery_delay: Some(10),
+            ..Default::default()
+        });
+        deposit(&mut deps, "alice", 69);
+        start_recovery(&mut deps, 100);
+
+        let handle_msg = HandleMsg::CancelRecovery {};
+        match handle(&mut deps, mock_env("alice", &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+
+        match finalize_recovery_at(&mut deps, 110) {
+            Ok(_) => panic!("should have failed"),
+            Err(_) => {}
+        }
+        assert_eq!(69, balance_of(&deps, "alice"));
+        assert_eq!(0, balance_of(&deps, "alice2"));
+    }
+
+    #[test]
+    fn query_allowances_batch_mixed() {
+        let mut deps = initialize();
+        approve_to(&mut deps, "alice", "bob", 50);
+        approve_to(&mut deps, "carol", "bob", 7);
+
+        let pair = |owner: &str, spender: &str| AllowancePair {
+            owner: HumanAddr::from(owner),
+            spender: HumanAddr::from(spender),
+        };
+        let approve_msg = HandleMsg::Approve {
+            spender: HumanAddr::from("carol"),
+            value: Uint128(9),
+            expiration: Some(100),
+        };
+        handle(&mut deps, mock_env("alice", &[]), approve_msg).unwrap();
+        let pairs = vec![
+            pair("alice", "bob"),
+            pair("bob", "alice"),
+            pair("carol", "bob"),
+            pair("alice", "carol"),
+        ];
+        let allowances_at = |current_height: u64| {
+            let query_msg = QueryMsg::AllowancesBatch {
+                pairs: pairs.clone(),
+                current_height,
+            };
+            match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+                QueryResult::AllowancesBatch {
+                    allowances,
+                    expirations,
+                } => (allowances, expirations),
+                _ => panic!("unexpected"),
+            }
+        };
+        let expirations = vec![None, None, None, Some(100)];
+        assert_eq!(
+            (
+                vec![Uint128(50), Uint128(0), Uint128(7), Uint128(9)],
+                expirations.clone()
+            ),
+            allowances_at(100)
+        );
+        // expired allowance is reported as zero
+        assert_eq!(
+            (
+                vec![Uint128(50), Uint128(0), Uint128(7), Uint128(0)],
+                expirations
+            ),
+            allowances_at(101)
+        );
+
+        let query_msg = QueryMsg::AllowancesBatch {
+            pairs: vec![pair("alice", "bob"); MAX_ALLOWANCES_BATCH + 1],
+            current_height: 12345,
+        };
+        assert!(query(&deps, query_msg).is_err());
+    }
+
+    #[test]
+    fn init_normalize_symbol() {
+        let query_symbol = |deps: &Extern<MockStorage, MockApi, MockQuerier>| match from_binary(
+            &query(deps, QueryMsg::Symbol {}).unwrap(),
+        )
+        .unwrap()
+        {
+            QueryResult::Symbol { symbol } => symbol,
+            _ => panic!("unexpected"),
+        };
+
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            symbol: "abc".to_string(),
+            ..Default::default()
+        };
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        assert_eq!("ABC", query_symbol(&deps));
+
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            symbol: "abc".to_string(),
+            normalize_symbol: Some(false),
+            ..Default::default()
+        };
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        assert_eq!("abc", query_symbol(&deps));
+    }
+
+    #[test]
+    fn query_token_id_round_trip() {
+        let deps = initialize_with(InitMsg {
+            token_id: "secret-token-v1".to_string(),
+            ..Default::default()
+        });
+
+        let res = query(&deps, QueryMsg::TokenId {}).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryResult::TokenId { token_id } => assert_eq!("secret-token-v1", token_id),
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn handle_deposit_to_minters() {
+        let mut deps = initialize();
+
+        let handle_msg = HandleMsg::SetMinters {
+            minters: vec![
+                MinterInfo {
+                    address: HumanAddr::from("minter"),
+                    expires_at: Some(100),
+                },
+                MinterInfo {
+                    address: HumanAddr::from("forever"),
+                    expires_at: None,
+                },
+            ],
+        };
+        match handle(&mut deps, mock_env("creator", &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+
+        let deposit_at =
+            |deps: &mut Extern<MockStorage, MockApi, MockQuerier>, sender: &str, height: u64| {
+                let handle_msg = HandleMsg::DepositTo {
+                    to: HumanAddr::from("alice"),
+                    value: Uint128(1),
+                };
+                let mut env = mock_env(sender, &[]);
+                env.block.height = height;
+                handle(deps, env, handle_msg)
+            };
+
+        assert!(deposit_at(&mut deps, "minter", 99).is_ok());
+        assert!(deposit_at(&mut deps, "minter", 100).is_err());
+        assert!(deposit_at(&mut deps, "forever", 100).is_ok());
+        assert!(deposit_at(&mut deps, "creator", 100).is_ok());
+        assert!(deposit_at(&mut deps, "bob", 100).is_err());
+        assert_eq!(3, balance_of(&deps, "alice"));
+    }
+
+    #[test]
+    fn handle_large_transfer() {
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 100);
+
+        let handle_msg = HandleMsg::SetLargeTransferThreshold {
+            threshold: Some(Uint128(50)),
+        };
+        match handle(&mut deps, mock_env("alice", &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+
+        // below threshold
+        let handle_msg = HandleMsg::Transfer {
+            to: HumanAddr::from("bob"),
+            value: Uint128(50),
+            log_note: None,
+            deadline: None,
+        };
+        match handle(&mut deps, mock_env("alice", &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+
+        // above threshold
+        let handle_msg = HandleMsg::Transfer {
+            to: HumanAddr::from("bob"),
+            value: Uint128(51),
+            log_note: None,
+            deadline: None,
+        };
+        match handle(&mut deps, mock_env("alice", &[]), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(_) => {}
+        }
+
+        let handle_msg = HandleMsg::ProposeLargeTransfer {
+            to: HumanAddr::from("bob"),
+            value: Uint128(50),
+        };
+        let res = handle(&mut deps, mock_env("alice", &[]), handle_msg).unwrap();
+        let id = match from_binary(&res.data.unwrap()).unwrap() {
+            HandleResult::ProposeLargeTransfer { id, .. } => id,
+            _ => panic!("unexpected"),
+        };
+        assert_eq!(50, balance_of(&deps, "alice"));
+
+        // nonexistent id
+        let handle_msg = HandleMsg::ConfirmLargeTransfer { id: id + 1 };
+        match handle(&mut deps, mock_env("alice", &[]), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(_) => {}
+        }
+
+        let handle_msg = HandleMsg::ConfirmLargeTransfer { id };
+        match handle(&mut deps, mock_env("alice", &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+        assert_eq!(0, balance_of(&deps, "alice"));
+        assert_eq!(100, balance_of(&deps, "bob"));
+
+        // confirmed transfer can not be executed again
+        let handle_msg = HandleMsg::ConfirmLargeTransfer { id };
+        match handle(&mut deps, mock_env("alice", &[]), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(_) => {}
+        }
+    }
+
+    #[test]
+    fn query_metadata_hash_known_value() {
+        let deps = initialize_with(InitMsg::default());
+
+        for _ in 0..2 {
+            let res = query(&deps, QueryMsg::MetadataHash {}).unwrap();
+            match from_binary(&res).unwrap() {
+                QueryResult::MetadataHash { hash } => assert_eq!(
+                    "42efd22d12224a0dd7e0471c1c7c2fe9377c32ca51ea5243a2906ed6e345fc1b",
+                    hash
+                ),
+                _ => panic!("unexpected"),
+            }
+        }
+    }
+
+    #[test]
+    fn handle_transfer_deadline() {
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 69);
+        approve_to(&mut deps, "alice", "bob", 20);
+
+        let mut env = mock_env("alice", &[]);
+        env.block.height = 100;
+
+        let handle_msg = HandleMsg::Transfer {
+            to: HumanAddr::from("bob"),
+            value: Uint128(9),
+            log_note: None,
+            deadline: Some(100),
+        };
+        match handle(&mut deps, env.clone(), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+
+        let handle_msg = HandleMsg::Transfer {
+            to: HumanAddr::from("bob"),
+            value: Uint128(9),
+            log_note: None,
+            deadline: Some(99),
+        };
+        match handle(&mut deps, env, handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("Expired")),
+        }
+
+        let mut env = mock_env("bob", &[]);
+        env.block.height = 100;
+        let handle_msg = HandleMsg::TransferFrom {
+            from: HumanAddr::from("alice"),
+            to: HumanAddr::from("carol"),
+            value: Uint128(10),
+            deadline: Some(99),
+        };
+        match handle(&mut deps, env, handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("Expired")),
+        }
+
+        assert_eq!(60, balance_of(&deps, "alice"));
+        assert_eq!(9, balance_of(&deps, "bob"));
+        assert_eq!(0, balance_of(&deps, "carol"));
+        assert_eq!(20, allowance_of(&mut deps, "alice", "bob"));
+    }
+
+    fn transfer_to(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        from: &str,
+        to: &str,
+        value: u128,
+    ) -> StdResult<HandleResponse> {
+        let handle_msg = HandleMsg::Transfer {
+            to: HumanAddr::from(to),
+            value: Uint128(value),
+            log_note: None,
+            deadline: None,
+        };
+        handle(deps, mock_env(from, &[]), handle_msg)
+    }
+
+    #[test]
+    fn handle_receive_policy() {
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 50);
+        deposit(&mut deps, "bob", 50);
+
+        // default accepts everyone
+        assert!(transfer_to(&mut deps, "alice", "carol", 1).is_ok());
+        assert!(transfer_to(&mut deps, "bob", "carol", 1).is_ok());
+
+        let handle_msg = HandleMsg::SetReceivePolicy {
+            allowed_senders: Some(vec![HumanAddr::from("alice")]),
+        };
+        match handle(&mut deps, mock_env("carol", &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+
+        assert!(transfer_to(&mut deps, "alice", "carol", 1).is_ok());
+        match transfer_to(&mut deps, "bob", "carol", 1) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("RecipientRefused")),
+        }
+
+        // spender can not bypass the policy
+        approve_to(&mut deps, "bob", "alice", 10);
+        let handle_msg = HandleMsg::TransferFrom {
+            from: HumanAddr::from("bob"),
+            to: HumanAddr::from("carol"),
+            value: Uint128(1),
+            deadline: None,
+        };
+        match handle(&mut deps, mock_env("alice", &[]), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("RecipientRefused")),
+        }
+
+        assert_eq!(3, balance_of(&deps, "carol"));
+        assert_eq!(49, balance_of(&deps, "bob"));
+    }
+
+    #[cfg(feature = "debug")]
+    #[test]
+    fn handle_debug_set_balance() {
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 69);
+
+        let handle_msg = HandleMsg::DebugSetBalance {
+            address: HumanAddr::from("alice"),
+            value: Uint128(9),
+        };
+        match handle(&mut deps, mock_env("anyone", &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+
+        assert_eq!(9, balance_of(&deps, "alice"));
+        assert_eq!(9, total_supply(&deps));
+    }
+
+    #[cfg(not(feature = "debug"))]
+    #[test]
+    fn debug_set_balance_not_compiled() {
+        let msg = br#"{"debugSetBalance":{"address":"alice","value":"9"}}"#;
+        assert!(cosmwasm_std::from_slice::<HandleMsg>(msg).is_err());
+    }
+
+    /// Storage that counts `get` calls made through it.
+    struct CountingStorage {
+        storage: MockStorage,
+        gets: Cell<usize>,
+    }
+
+    impl ReadonlyStorage for CountingStorage {
+        fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+            self.gets.set(self.gets.get() + 1);
+            self.storage.get(key)
+        }
+    }
+
+    impl Storage for CountingStorage {
+        fn set(&mut self, key: &[u8], value: &[u8]) {
+            self.storage.set(key, value)
+        }
+
+        fn remove(&mut self, key: &[u8]) {
+            self.storage.remove(key)
+        }
+    }
+
+    fn count_query_gets(
+        deps: &Extern<CountingStorage, MockApi, MockQuerier>,
+        msg: QueryMsg,
+    ) -> usize {
+        deps.storage.gets.set(0);
+        query(deps, msg).unwrap();
+        deps.storage.gets.get()
+    }
+
+    #[test]
+    fn query_storage_reads_constant() {
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 10);
+        approve_to(&mut deps, "alice", "bob", 5);
+        let deps = Extern {
+            storage: CountingStorage {
+                storage: deps.storage,
+                gets: Cell::new(0),
+            },
+            api: deps.api,
+            querier: deps.querier,
+        };
+
+        let balance_of = |address: &str| QueryMsg::BalanceOf {
+            address: HumanAddr::from(address),
+        };
+        assert_eq!(
+            count_query_gets(&deps, balance_of("alice")),
+            count_query_gets(&deps, balance_of("bob"))
+        );
+
+        let allowances = |owner: &str, spender: &str| QueryMsg::AllowancesBatch {
+            pairs: vec![AllowancePair {
+                owner: HumanAddr::from(owner),
+                spender: HumanAddr::from(spender),
+            }],
+            current_height: 12345,
+        };
+        assert_eq!(
+            count_query_gets(&deps, allowances("alice", "bob")),
+            count_query_gets(&deps, allowances("bob", "alice"))
+        );
+    }
+
+    fn set_owner_query_key(deps: &mut Extern<MockStorage, MockApi, MockQuerier>) {
+        let handle_msg = HandleMsg::SetOwnerQueryKey {
+            key: "owner key".to_string(),
+        };
+        handle(deps, mock_env("creator", &[]), handle_msg).unwrap();
+    }
+
+    fn raw_storage(deps: &Extern<MockStorage, MockApi, MockQuerier>, key: &str) -> Vec<u8> {
+        let query_msg = QueryMsg::RawStorage {
+            key: key.to_string(),
+            owner_key: "owner key".to_string(),
+        };
+        match from_binary(&query(deps, query_msg).unwrap()).unwrap() {
+            QueryResult::RawStorage { data } => data.0,
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn query_raw_storage_config() {
+        let mut deps = initialize();
+        set_owner_query_key(&mut deps);
+        deposit(&mut deps, "alice", 42);
+
+        let constants: Constants = bincode::deserialize(&raw_storage(&deps, "constants")).unwrap();
+        assert_eq!(69, constants.decimals);
+        assert_eq!(
+            deps.api
+                .canonical_address(&HumanAddr::from("creator"))
+                .unwrap(),
+            constants.owner
+        );
+
+        let total_supply = raw_storage(&deps, "total_supply");
+        assert_eq!(42u128.to_be_bytes().to_vec(), total_supply);
+    }
+
+    #[test]
+    fn query_raw_storage_disallowed_key() {
+        let mut deps = initialize();
+        set_owner_query_key(&mut deps);
+        for key in &["prng_seed", "minters", "balances"] {
+            let query_msg = QueryMsg::RawStorage {
+                key: key.to_string(),
+                owner_key: "owner key".to_string(),
+            };
+            assert!(query(&deps, query_msg).is_err());
+        }
+    }
+
+    #[test]
+    fn query_owner_query_key() {
+        let mut deps = initialize();
+        let raw_storage = |deps: &Extern<MockStorage, MockApi, MockQuerier>, owner_key: &str| {
+            let query_msg = QueryMsg::RawStorage {
+                key: "total_supply".to_string(),
+                owner_key: owner_key.to_string(),
+            };
+            query(deps, query_msg)
+        };
+
+        // no key set
+        match raw_storage(&deps, "") {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("Unauthorized")),
+        }
+
+        let set_key = |key: &str| HandleMsg::SetOwnerQueryKey {
+            key: key.to_string(),
+        };
+        match handle(&mut deps, mock_env("alice", &[]), set_key("alice key")) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("Only contract owner")),
+        }
+        assert!(handle(&mut deps, mock_env("creator", &[]), set_key("")).is_err());
+        handle(&mut deps, mock_env("creator", &[]), set_key("owner key")).unwrap();
+
+        match raw_storage(&deps, "alice key") {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("Unauthorized")),
+        }
+        assert!(raw_storage(&deps, "owner key").is_ok());
+
+        // a new key replaces the old one
+        handle(&mut deps, mock_env("creator", &[]), set_key("new key")).unwrap();
+        assert!(raw_storage(&deps, "owner key").is_err());
+        assert!(raw_storage(&deps, "new key").is_ok());
+    }
+
+    fn tips(deps: &Extern<MockStorage, MockApi, MockQuerier>) -> Vec<Coin> {
+        match from_binary(&query(deps, QueryMsg::Tips {}).unwrap()).unwrap() {
+            QueryResult::Tips { tips } => tips,
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn handle_transfer_tips() {
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 10);
+        assert!(tips(&deps).is_empty());
+
+        for _ in 0..2 {
+            let handle_msg = HandleMsg::Transfer {
+                to: HumanAddr::from("bob"),
+                value: Uint128(1),
+                log_note: None,
+                deadline: None,
+            };
+            match handle(
+                &mut deps,
+                mock_env("alice", &coins(100, "uscrt")),
+                handle_msg,
+            ) {
+                Ok(_) => {}
+                Err(e) => panic!("error: {:?}", e),
+            }
+        }
+
+        assert_eq!(coins(200, "uscrt"), tips(&deps));
+        assert_eq!(8, balance_of(&deps, "alice"));
+    }
+
+    fn pullable_amount(
+        deps: &Extern<MockStorage, MockApi, MockQuerier>,
+        owner: &str,
+        spender: &str,
+    ) -> (u128, u128, u128) {
+        let query_msg = QueryMsg::PullableAmount {
+            owner: HumanAddr::from(owner),
+            spender: HumanAddr::from(spender),
+            current_height: 12345,
+        };
+        match from_binary(&query(deps, query_msg).unwrap()).unwrap() {
+            QueryResult::PullableAmount {
+                pullable,
+                balance,
+                allowance,
+                ..
+            } => (pullable.u128(), balance.u128(), allowance.u128()),
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn query_pullable_amount() {
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 30);
+        approve_to(&mut deps, "alice", "bob", 50);
+        approve_to(&mut deps, "alice", "carol", 10);
+        approve_to(&mut deps, "bob", "alice", 5);
+
+        assert_eq!((30, 30, 50), pullable_amount(&deps, "alice", "bob"));
+        assert_eq!((10, 30, 10), pullable_amount(&deps, "alice", "carol"));
+        assert_eq!((0, 30, 0), pullable_amount(&deps, "alice", "dave"));
+        assert_eq!((0, 0, 5), pullable_amount(&deps, "bob", "alice"));
+
+        // expired allowance can not be pulled
+        let approve_msg = HandleMsg::Approve {
+            spender: HumanAddr::from("dave"),
+            value: Uint128(10),
+            expiration: Some(12344),
+        };
+        handle(&mut deps, mock_env("alice", &[]), approve_msg).unwrap();
+        assert_eq!((0, 30, 0), pullable_amount(&deps, "alice", "dave"));
+        let query_msg = QueryMsg::PullableAmount {
+            owner: HumanAddr::from("alice"),
+            spender: HumanAddr::from("dave"),
+            current_height: 12344,
+        };
+        match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            QueryResult::PullableAmount {
+                pullable,
+                expiration,
+                ..
+            } => assert_eq!((Uint128(10), Some(12344)), (pullable, expiration)),
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn handle_approve_burn() {
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 50);
+
+        let handle_msg = HandleMsg::ApproveBurn {
+            spender: HumanAddr::from("bob"),
+            value: Uint128(20),
+            expiration: None,
+        };
+        match handle(&mut deps, mock_env("alice", &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+        approve_to(&mut deps, "alice", "carol", 20);
+
+        let burn_msg = |value: u128| HandleMsg::BurnFrom {
+            from: HumanAddr::from("alice"),
+            value: Uint128(value),
+        };
+        let transfer_from_msg = HandleMsg::TransferFrom {
+            from: HumanAddr::from("alice"),
+            to: HumanAddr::from("dave"),
+            value: Uint128(5),
+            deadline: None,
+        };
+
+        // burn allowance can not be used to transfer
+        assert!(handle(&mut deps, mock_env("bob", &[]), transfer_from_msg.clone()).is_err());
+        match handle(&mut deps, mock_env("bob", &[]), burn_msg(15)) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+        assert!(handle(&mut deps, mock_env("bob", &[]), burn_msg(6)).is_err());
+        assert_eq!(35, balance_of(&deps, "alice"));
+        assert_eq!(35, total_supply(&deps));
+
+        // transfer allowance can not be used to burn
+        assert!(handle(&mut deps, mock_env("carol", &[]), burn_msg(5)).is_err());
+        match handle(&mut deps, mock_env("carol", &[]), transfer_from_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+        assert_eq!(30, balance_of(&deps, "alice"));
+        assert_eq!(5, balance_of(&deps, "dave"));
+
+        // expired burn allowance
+        let handle_msg = HandleMsg::ApproveBurn {
+            spender: HumanAddr::from("erin"),
+            value: Uint128(10),
+            expiration: Some(20000),
+        };
+        handle(&mut deps, mock_env("alice", &[]), handle_msg).unwrap();
+        let mut env = mock_env("erin", &[]);
+        env.block.height = 20001;
+        assert!(handle(&mut deps, env, burn_msg(5)).is_err());
+        let mut env = mock_env("erin", &[]);
+        env.block.height = 20000;
+        handle(&mut deps, env, burn_msg(5)).unwrap();
+        assert_eq!(25, balance_of(&deps, "alice"));
+    }
+
+    #[test]
+    fn query_supported_features() {
+        let deps = initialize_with(InitMsg {
+            reserve: Some(ReserveConfig {
+                denom: "uscrt".to_string(),
+                backing_ratio_bps: 10000,
+            }),
+            verbose_logging: true,
+            max_wallet_balance: Some(Uint128(1000)),
+            emit_sequence: true,
+            ..Default::default()
+        });
+        match from_binary(&query(&deps, QueryMsg::SupportedFeatures {}).unwrap()).unwrap() {
+            QueryResult::SupportedFeatures {
+                reserve,
+                minters,
+                reward_pool,
+                verbose_logging,
+                disabled_handlers,
+                debug,
+                transfer_guard,
+                price_oracle,
+                max_wallet_balance,
+                require_registration,
+                min_holding_period,
+                sequence_logging,
+            } => {
+                assert!(reserve);
+                assert!(verbose_logging);
+                assert!(!minters);
+                assert!(!reward_pool);
+                assert!(!disabled_handlers);
+                assert_eq!(cfg!(feature = "debug"), debug);
+                assert!(!transfer_guard);
+                assert!(!price_oracle);
+                assert!(max_wallet_balance);
+                assert!(!require_registration);
+                assert!(!min_holding_period);
+                assert!(sequence_logging);
+            }
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn handle_renounce_ownership() {
+        let mut deps = initialize_with(InitMsg {
+            token_id: "token".to_string(),
+            ..Default::default()
+        });
+        let handle_msg = HandleMsg::SetMinters {
+            minters: vec![MinterInfo {
+                address: HumanAddr::from("minter"),
+                expires_at: None,
+            }],
+        };
+        handle(&mut deps, mock_env("creator", &[]), handle_msg).unwrap();
+        let handle_msg = HandleMsg::SetMintAdmin {
+            mint_admin: Some(HumanAddr::from("admin")),
+        };
+        handle(&mut deps, mock_env("creator", &[]), handle_msg).unwrap();
+
+        let renounce_msg = |confirm: &str| HandleMsg::RenounceOwnership {
+            confirm: confirm.to_string(),
+        };
+        match handle(&mut deps, mock_env("creator", &[]), renounce_msg("other")) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("token id")),
+        }
+        assert!(handle(&mut deps, mock_env("bob", &[]), renounce_msg("token")).is_err());
+        match handle(&mut deps, mock_env("creator", &[]), renounce_msg("token")) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+
+        let owner_msgs = vec![
+            HandleMsg::DepositTo {
+                to: HumanAddr::from("creator"),
+                value: Uint128(1),
+            },
+            HandleMsg::SetDisabledHandlers { handlers: vec![] },
+            HandleMsg::SetMinters { minters: vec![] },
+            renounce_msg("token"),
+        ];
+        for msg in owner_msgs {
+            assert!(handle(&mut deps, mock_env("creator", &[]), msg).is_err());
+        }
+
+        // minting ends with the ownership
+        let handle_msg = HandleMsg::DepositTo {
+            to: HumanAddr::from("minter"),
+            value: Uint128(1),
+        };
+        match handle(&mut deps, mock_env("minter", &[]), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(_) => {}
+        }
+        let handle_msg = HandleMsg::SetMinters {
+            minters: vec![MinterInfo {
+                address: HumanAddr::from("minter"),
+                expires_at: None,
+            }],
+        };
+        assert!(handle(&mut deps, mock_env("admin", &[]), handle_msg).is_err());
+        assert_eq!(0, total_supply(&deps));
+    }
+
+    #[test]
+    fn query_ownership() {
+        let mut deps = initialize_with(InitMsg {
+            token_id: "token".to_string(),
+            ..Default::default()
+        });
+
+        let ownership = |deps: &Extern<MockStorage, MockApi, MockQuerier>| match from_binary(
+            &query(deps, QueryMsg::Ownership {}).unwrap(),
+        )
+        .unwrap()
+        {
+            QueryResult::Ownership { owner, renounced } => (owner, renounced),
+            _ => panic!("unexpected"),
+        };
+        assert_eq!((Some(HumanAddr::from("creator")), false), ownership(&deps));
+
+        let handle_msg = HandleMsg::RenounceOwnership {
+            confirm: "token".to_string(),
+        };
+        handle(&mut deps, mock_env("creator", &[]), handle_msg).unwrap();
+        assert_eq!((None, true), ownership(&deps));
+    }
+
+    fn queue_transfer_to(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        from: &str,
+        to: &str,
+        value: u128,
+    ) {
+        let handle_msg = HandleMsg::QueueTransfer {
+            to: HumanAddr::from(to),
+            value: Uint128(value),
+        };
+        match handle(deps, mock_env(from, &[]), handle_msg) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn handle_settle_transfers() {
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 50);
+
+        queue_transfer_to(&mut deps, "alice", "bob", 10);
+        queue_transfer_to(&mut deps, "alice", "carol", 5);
+        queue_transfer_to(&mut deps, "alice", "bob", 15);
+        assert_eq!(50, balance_of(&deps, "alice"));
+        assert_eq!(0, balance_of(&deps, "bob"));
+
+        match handle(
+            &mut deps,
+            mock_env("alice", &[]),
+            HandleMsg::SettleTransfers {},
+        ) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+        assert_eq!(20, balance_of(&deps, "alice"));
+        assert_eq!(25, balance_of(&deps, "bob"));
+        assert_eq!(5, balance_of(&deps, "carol"));
+
+        // queue is emptied by settlement
+        assert!(handle(
+            &mut deps,
+            mock_env("alice", &[]),
+            HandleMsg::SettleTransfers {}
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn handle_settle_transfers_underflow() {
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 20);
+
+        queue_transfer_to(&mut deps, "alice", "bob", 10);
+        queue_transfer_to(&mut deps, "alice", "carol", 15);
+        assert!(handle(
+            &mut deps,
+            mock_env("alice", &[]),
+            HandleMsg::SettleTransfers {}
+        )
+        .is_err());
+        assert_eq!(20, balance_of(&deps, "alice"));
+        assert_eq!(0, balance_of(&deps, "bob"));
+    }
+
+    #[test]
+    fn handle_settle_transfers_same_recipient() {
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 50);
+        deposit(&mut deps, "bob", 1);
+
+        for value in &[3, 5, 7] {
+            queue_transfer_to(&mut deps, "alice", "bob", *value);
+        }
+        match handle(
+            &mut deps,
+            mock_env("alice", &[]),
+            HandleMsg::SettleTransfers {},
+        ) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+        assert_eq!(35, balance_of(&deps, "alice"));
+        assert_eq!(16, balance_of(&deps, "bob"));
+        assert_eq!(51, total_supply(&deps));
+    }
+
+    fn metadata_uri(deps: &Extern<MockStorage, MockApi, MockQuerier>) -> Option<String> {
+        match from_binary(&query(deps, QueryMsg::MetadataUri {}).unwrap()).unwrap() {
+            QueryResult::MetadataUri { metadata_uri } => metadata_uri,
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn handle_set_metadata() {
+        let mut deps = initialize();
+        assert_eq!(None, metadata_uri(&deps));
+
+        let set_metadata_msg = |uri: &str| HandleMsg::SetMetadata {
+            metadata_uri: Some(uri.to_string()),
+        };
+        match handle(
+            &mut deps,
+            mock_env("creator", &[]),
+            set_metadata_msg("ipfs://token.json"),
+        ) {
+            Ok(_) => {}
+            Err(e) => panic!("error: {:?}", e),
+        }
+        assert_eq!(Some("ipfs://token.json".to_string()), metadata_uri(&deps));
+
+        let invalid_msgs = vec![
+            set_metadata_msg("javascript:alert(1)"),
+            set_metadata_msg(&format!("https://{}", "a".repeat(MAX_METADATA_URI_LEN))),
+        ];
+        for msg in invalid_msgs {
+            assert!(handle(&mut deps, mock_env("creator", &[]), msg).is_err());
+        }
+        assert!(handle(
+            &mut deps,
+            mock_env("bob", &[]),
+            set_metadata_msg("https://token.json")
+        )
+        .is_err());
+        assert_eq!(Some("ipfs://token.json".to_string()), metadata_uri(&deps));
+    }
+
+    fn adjust_allowance_by(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        owner: &str,
+        spender: &str,
+        delta: &str,
+    ) -> StdResult<HandleResponse> {
+        let handle_msg = HandleMsg::AdjustAllowance {
+            spender: HumanAddr::from(spender),
+            delta: delta.to_string(),
+        };
+        handle(deps, mock_env(owner, &[]), handle_msg)
+    }
+
+    #[test]
+    fn handle_adjust_allowance() {
+        let mut deps = initialize();
+
+        adjust_allowance_by(&mut deps, "alice", "bob", "30").unwrap();
+        assert_eq!(30, allowance_of(&mut deps, "alice", "bob"));
+
+        adjust_allowance_by(&mut deps, "alice", "bob", "-10").unwrap();
+        assert_eq!(20, allowance_of(&mut deps, "alice", "bob"));
+
+        adjust_allowance_by(&mut deps, "alice", "bob", "-50").unwrap();
+        assert_eq!(0, allowance_of(&mut deps, "alice", "bob"));
+
+        approve_to(&mut deps, "alice", "bob", u128::MAX);
+        match adjust_allowance_by(&mut deps, "alice", "bob", "1") {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("AllowanceOverflow")),
+        }
+        assert_eq!(u128::MAX, allowance_of(&mut deps, "alice", "bob"));
+
+        assert!(adjust_allowance_by(&mut deps, "alice", "bob", "0").is_err());
+        assert!(adjust_allowance_by(&mut deps, "alice", "bob", "1.5").is_err());
+
+        // decreasing to zero revokes like a zero approve
+        let mut deps = initialize_with(InitMsg {
+            zero_approve_revokes: true,
+            ..Default::default()
+        });
+        adjust_allowance_by(&mut deps, "alice", "bob", "30").unwrap();
+        adjust_allowance_by(&mut deps, "alice", "bob", "-50").unwrap();
+        let owner = deps
+            .api
+            .canonical_address(&HumanAddr::from("alice"))
+            .unwrap();
+        let spender = deps.api.canonical_address(&HumanAddr::from("bob")).unwrap();
+        let allowances = ReadonlyPrefixedStorage::multilevel(
+            &[StorageKeys::ALLOWANCES, owner.as_slice()],
+            &deps.storage,
+        );
+        assert_eq!(None, allowances.get(spender.as_slice()));
+        assert!(get_allowance_spenders(&deps.storage, &owner)
+            .unwrap()
+            .is_empty());
+        assert_eq!(None, get_allowance_entry(&deps.storage, 0).unwrap());
+    }
+
+    #[test]
+    fn handle_reject_value_above_supply() {
+        let transfer_msg = HandleMsg::Transfer {
+            to: HumanAddr::from("bob"),
+            value: Uint128(100),
+            log_note: None,
+            deadline: None,
+        };
+
+        let mut deps = initialize_with(InitMsg {
+            reject_value_above_supply: true,
+            ..Default::default()
+        });
+        deposit(&mut deps, "alice", 50);
+        match handle(&mut deps, mock_env("alice", &[]), transfer_msg.clone()) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("ImplausibleAmount")),
+        }
+        let approve_msg = HandleMsg::Approve {
+            spender: HumanAddr::from("bob"),
+            value: Uint128(51),
+            expiration: None,
+        };
+        assert!(handle(&mut deps, mock_env("alice", &[]), approve_msg).is_err());
+        match adjust_allowance_by(&mut deps, "alice", "bob", "51") {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("ImplausibleAmount")),
+        }
+        adjust_allowance_by(&mut deps, "alice", "bob", "-51").unwrap();
+
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 50);
+        match handle(&mut deps, mock_env("alice", &[]), transfer_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("balance underflow")),
+        }
+    }
+
+    #[test]
+    fn query_creation_info() {
+        let deps = initialize();
+        let env = mock_env("creator", &[]);
+        match from_binary(&query(&deps, QueryMsg::CreationInfo {}).unwrap()).unwrap() {
+            QueryResult::CreationInfo { height, time } => {
+                assert_eq!(env.block.height, height);
+                assert_eq!(env.block.time, time);
+            }
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn handle_transfer_from_expired_allowance() {
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 50);
+
+        let approve_msg = HandleMsg::Approve {
+            spender: HumanAddr::from("bob"),
+            value: Uint128(20),
+            expiration: Some(12345),
+        };
+        handle(&mut deps, mock_env("alice", &[]), approve_msg).unwrap();
+
+        let transfer_from_msg = HandleMsg::TransferFrom {
+            from: HumanAddr::from("alice"),
+            to: HumanAddr::from("carol"),
+            value: Uint128(5),
+            deadline: None,
+        };
+        let mut env = mock_env("bob", &[]);
+        handle(&mut deps, env.clone(), transfer_from_msg.clone()).unwrap();
+
+        env.block.height += 1;
+        match handle(&mut deps, env, transfer_from_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("expired")),
+        }
+        assert_eq!(5, balance_of(&deps, "carol"));
+        assert_eq!(15, allowance_of(&mut deps, "alice", "bob"));
+    }
+
+    #[test]
+    fn handle_max_wallet_balance() {
+        let mut deps = initialize_with(InitMsg {
+            max_wallet_balance: Some(Uint128(100)),
+            ..Default::default()
+        });
+        deposit(&mut deps, "alice", 100);
+        deposit(&mut deps, "creator", 500);
+
+        transfer_to(&mut deps, "alice", "bob", 60).unwrap();
+        match transfer_to(&mut deps, "alice", "bob", 41) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("can not exceed 100")),
+        }
+        transfer_to(&mut deps, "alice", "bob", 40).unwrap();
+        assert_eq!(100, balance_of(&deps, "bob"));
+
+        let transfer_from_msg = HandleMsg::TransferFrom {
+            from: HumanAddr::from("creator"),
+            to: HumanAddr::from("bob"),
+            value: Uint128(1),
+            deadline: None,
+        };
+        approve_to(&mut deps, "creator", "carol", 1);
+        assert!(handle(&mut deps, mock_env("carol", &[]), transfer_from_msg).is_err());
+
+        let deposit_msg = HandleMsg::DepositTo {
+            to: HumanAddr::from("bob"),
+            value: Uint128(1),
+        };
+        assert!(handle(&mut deps, mock_env("creator", &[]), deposit_msg).is_err());
+
+        // owner and reward pool are exempt
+        transfer_to(&mut deps, "bob", "creator", 100).unwrap();
+        assert_eq!(600, balance_of(&deps, "creator"));
+        let pool_msg = HandleMsg::SetRewardPool {
+            pool: HumanAddr::from("pool"),
+            max_reward_bps: 100,
+        };
+        handle(&mut deps, mock_env("creator", &[]), pool_msg).unwrap();
+        transfer_to(&mut deps, "creator", "pool", 200).unwrap();
+        assert_eq!(200, balance_of(&deps, "pool"));
+
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 1000);
+        transfer_to(&mut deps, "alice", "bob", 1000).unwrap();
+        assert_eq!(1000, balance_of(&deps, "bob"));
+    }
+
+    fn expired_allowances(
+        deps: &Extern<MockStorage, MockApi, MockQuerier>,
+        current_height: u64,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> (Vec<(String, String)>, Option<u64>) {
+        let query_msg = QueryMsg::ExpiredAllowances {
+            current_height,
+            start_after,
+            limit,
+        };
+        match from_binary(&query(deps, query_msg).unwrap()).unwrap() {
+            QueryResult::ExpiredAllowances { allowances, next } => (
+                allowances
+                    .into_iter()
+                    .map(|pair| (pair.owner.to_string(), pair.spender.to_string()))
+                    .collect(),
+                next,
+            ),
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn query_expired_allowances() {
+        let mut deps = initialize();
+        for (owner, spender, expiration) in &[
+            ("alice", "bob", Some(100)),
+            ("alice", "carol", None),
+            ("bob", "carol", Some(200)),
+            ("carol", "alice", Some(300)),
+        ] {
+            let approve_msg = HandleMsg::Approve {
+                spender: HumanAddr::from(*spender),
+                value: Uint128(10),
+                expiration: *expiration,
+            };
+            handle(&mut deps, mock_env(*owner, &[]), approve_msg).unwrap();
+        }
+        // updating an allowance does not index it again
+        let approve_msg = HandleMsg::Approve {
+            spender: HumanAddr::from("bob"),
+            value: Uint128(5),
+            expiration: Some(100),
+        };
+        handle(&mut deps, mock_env("alice", &[]), approve_msg).unwrap();
+
+        let pair = |owner: &str, spender: &str| (owner.to_string(), spender.to_string());
+        assert_eq!((vec![], None), expired_allowances(&deps, 100, None, None));
+        assert_eq!(
+            (vec![pair("alice", "bob"), pair("bob", "carol")], None),
+            expired_allowances(&deps, 250, None, None)
+        );
+
+        assert_eq!(
+            (vec![pair("alice", "bob")], Some(1)),
+            expired_allowances(&deps, 1000, None, Some(2))
+        );
+        assert_eq!(
+            (vec![pair("bob", "carol"), pair("carol", "alice")], None),
+            expired_allowances(&deps, 1000, Some(1), Some(2))
+        );
+    }
+
+    /// Guard answering every query with `allowed`, or failing if it is `None`.
+    struct GuardQuerier {
+        allowed: Option<bool>,
+    }
+
+    impl Querier for GuardQuerier {
+        fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+            let request: QueryRequest<Empty> = from_slice(bin_request).unwrap();
+            match request {
+                QueryRequest::Wasm(WasmQuery::Smart {
+                    contract_addr, msg, ..
+                }) => {
+                    assert_eq!(HumanAddr::from("guard"), contract_addr);
+                    let GuardQueryMsg::CanTransfer { value, .. } = from_binary(&msg).unwrap();
+                    assert_eq!(Uint128(5), value);
+                }
+                _ => panic!("unexpected"),
+            }
+            match self.allowed {
+                Some(allowed) => Ok(to_binary(&GuardResponse { allowed })),
+                None => Ok(Err(StdError::generic_err("guard is broken"))),
+            }
+        }
+    }
+
+    fn initialize_guarded(fail_open: bool) -> Extern<MockStorage, MockApi, GuardQuerier> {
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 50);
+        approve_to(&mut deps, "alice", "bob", 50);
+        let guard_msg = HandleMsg::SetTransferGuard {
+            guard: Some(TransferGuardInfo {
+                address: HumanAddr::from("guard"),
+                code_hash: "hash".to_string(),
+                fail_open,
+            }),
+        };
+        assert!(handle(&mut deps, mock_env("alice", &[]), guard_msg.clone()).is_err());
+        handle(&mut deps, mock_env("creator", &[]), guard_msg).unwrap();
+        Extern {
+            storage: deps.storage,
+            api: deps.api,
+            querier: GuardQuerier {
+                allowed: Some(true),
+            },
+        }
+    }
+
+    #[test]
+    fn handle_transfer_guard() {
+        let transfer_msg = HandleMsg::Transfer {
+            to: HumanAddr::from("carol"),
+            value: Uint128(5),
+            log_note: None,
+            deadline: None,
+        };
+        let transfer_from_msg = HandleMsg::TransferFrom {
+            from: HumanAddr::from("alice"),
+            to: HumanAddr::from("carol"),
+            value: Uint128(5),
+            deadline: None,
+        };
+
+        let mut deps = initialize_guarded(false);
+        handle(&mut deps, mock_env("alice", &[]), transfer_msg.clone()).unwrap();
+        handle(&mut deps, mock_env("bob", &[]), transfer_from_msg.clone()).unwrap();
+
+        deps.querier.allowed = Some(false);
+        match handle(&mut deps, mock_env("alice", &[]), transfer_msg.clone()) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("TransferVetoed")),
+        }
+        assert!(handle(&mut deps, mock_env("bob", &[]), transfer_from_msg).is_err());
+
+        // fail closed
+        deps.querier.allowed = None;
+        match handle(&mut deps, mock_env("alice", &[]), transfer_msg.clone()) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("TransferGuardFailed")),
+        }
+
+        let mut deps = initialize_guarded(true);
+        deps.querier.allowed = None;
+        handle(&mut deps, mock_env("alice", &[]), transfer_msg).unwrap();
+    }
+
+    #[test]
+    fn query_supply_stats() {
+        let mut deps = initialize_with(InitMsg::default());
+        deposit(&mut deps, "alice", 1_500_000);
+        deposit(&mut deps, "bob", 2_000_000);
+        let burn_msg = HandleMsg::BurnFrom {
+            from: HumanAddr::from("alice"),
+            value: Uint128(250_000),
+        };
+        handle(&mut deps, mock_env("alice", &[]), burn_msg).unwrap();
+        let batch_burn_msg = HandleMsg::BatchBurn {
+            actions: vec![BurnAction {
+                owner: HumanAddr::from("bob"),
+                value: Uint128(5),
+            }],
+        };
+        handle(&mut deps, mock_env("creator", &[]), batch_burn_msg).unwrap();
+
+        let formatted = |amount: u128, formatted: &str| FormattedAmount {
+            amount: Uint128(amount),
+            formatted: formatted.to_string(),
+        };
+        match from_binary(&query(&deps, QueryMsg::SupplyStats {}).unwrap()).unwrap() {
+            QueryResult::SupplyStats {
+                total_supply,
+                minted,
+                burned,
+            } => {
+                assert_eq!(formatted(3_249_995, "3.249995"), total_supply);
+                assert_eq!(formatted(3_500_000, "3.500000"), minted);
+                assert_eq!(formatted(250_005, "0.250005"), burned);
+            }
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn format_amount_decimals() {
+        assert_eq!("0.000001", format_amount(1, 6));
+        assert_eq!("1234.567890", format_amount(1_234_567_890, 6));
+        assert_eq!("69", format_amount(69, 0));
+        assert_eq!("0", format_amount(0, 0));
+    }
+
+    #[test]
+    fn format_amount_truncated_decimals() {
+        assert_eq!("1.23", format_amount_truncated(1_234_567, 6, 2).unwrap());
+        assert_eq!("1", format_amount_truncated(1_999_999, 6, 0).unwrap());
+        assert_eq!(
+            "1.234567",
+            format_amount_truncated(1_234_567, 6, 6).unwrap()
+        );
+        assert_eq!("0", format_amount_truncated(1, 255, 0).unwrap());
+        assert!(format_amount_truncated(1, 6, 7).is_err());
+    }
+
+    #[test]
+    fn query_formatted_balance() {
+        let mut deps = initialize_with(InitMsg::default());
+        deposit(&mut deps, "alice", 1_234_567);
+
+        let formatted_balance = |display_decimals: Option<u8>| {
+            let query_msg = QueryMsg::FormattedBalance {
+                address: HumanAddr::from("alice"),
+                display_decimals,
+            };
+            query(&deps, query_msg).map(|res| match from_binary(&res).unwrap() {
+                QueryResult::FormattedBalance { balance } => balance.formatted,
+                _ => panic!("unexpected"),
+            })
+        };
+        assert_eq!("1.234567", formatted_balance(None).unwrap());
+        assert_eq!("1.23", formatted_balance(Some(2)).unwrap());
+        match formatted_balance(Some(7)) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("Display decimals")),
+        }
+    }
+
+    #[test]
+    fn handle_transfer_to_zero_address() {
+        // mock api maps it to the all-zeros canonical address
+        let zero_address = "\0\0\0";
+
+        let mut deps = initialize_with(InitMsg {
+            treat_zero_address_as_burn: true,
+            ..Default::default()
+        });
+        deposit(&mut deps, "alice", 50);
+        transfer_to(&mut deps, "alice", zero_address, 20).unwrap();
+        assert_eq!(30, balance_of(&deps, "alice"));
+        assert_eq!(0, balance_of(&deps, zero_address));
+        assert_eq!(30, total_supply(&deps));
+
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 50);
+        transfer_to(&mut deps, "alice", zero_address, 20).unwrap();
+        assert_eq!(30, balance_of(&deps, "alice"));
+        assert_eq!(20, balance_of(&deps, zero_address));
+        assert_eq!(50, total_supply(&deps));
+    }
+
+    fn pending_rewards_of(deps: &Extern<MockStorage, MockApi, MockQuerier>, address: &str) -> u128 {
+        let query_msg = QueryMsg::PendingRewards {
+            address: HumanAddr::from(address),
+        };
+        match from_binary(&query(deps, query_msg).unwrap()).unwrap() {
+            QueryResult::PendingRewards { rewards } => rewards.u128(),
+            _ => panic!("unexpected"),
+        }
+    }
+
+    fn distribute(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        sender: &str,
+        amount: u128,
+    ) -> StdResult<HandleResponse> {
+        let handle_msg = HandleMsg::DistributeRewards {
+            amount: Uint128(amount),
+        };
+        handle(deps, mock_env(sender, &[]), handle_msg)
+    }
+
+    #[test]
+    fn handle_distribute_rewards() {
+        let mut deps = initialize();
+        assert!(distribute(&mut deps, "creator", 10).is_err());
+
+        deposit(&mut deps, "alice", 1);
+        deposit(&mut deps, "bob", 2);
+        assert!(distribute(&mut deps, "alice", 10).is_err());
+
+        // 10 / 3 per token, rounded down for each holder
+        distribute(&mut deps, "creator", 10).unwrap();
+        assert_eq!(3, pending_rewards_of(&deps, "alice"));
+        assert_eq!(6, pending_rewards_of(&deps, "bob"));
+
+        // rewards accrued before the transfer are kept
+        transfer_to(&mut deps, "alice", "bob", 1).unwrap();
+        distribute(&mut deps, "creator", 30).unwrap();
+        assert_eq!(3, pending_rewards_of(&deps, "alice"));
+        assert_eq!(36, pending_rewards_of(&deps, "bob"));
+
+        // new holders do not get rewards distributed before they held tokens
+        deposit(&mut deps, "carol", 3);
+        assert_eq!(0, pending_rewards_of(&deps, "carol"));
+        distribute(&mut deps, "creator", 60).unwrap();
+        assert_eq!(30, pending_rewards_of(&deps, "carol"));
+        assert_eq!(66, pending_rewards_of(&deps, "bob"));
+    }
+
+    #[test]
+    fn handle_swap_balances() {
+        let swap_msg = |a: &str, b: &str| HandleMsg::SwapBalances {
+            a: HumanAddr::from(a),
+            b: HumanAddr::from(b),
+        };
+
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 50);
+        deposit(&mut deps, "bob", 20);
+
+        assert!(handle(&mut deps, mock_env("alice", &[]), swap_msg("alice", "bob")).is_err());
+        handle(
+            &mut deps,
+            mock_env("creator", &[]),
+            swap_msg("alice", "bob"),
+        )
+        .unwrap();
+        assert_eq!(20, balance_of(&deps, "alice"));
+        assert_eq!(50, balance_of(&deps, "bob"));
+        assert_eq!(70, total_supply(&deps));
+
+        match handle(
+            &mut deps,
+            mock_env("creator", &[]),
+            swap_msg("alice", "alice"),
+        ) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("accounts are same")),
+        }
+        assert_eq!(20, balance_of(&deps, "alice"));
+
+        let swap_msg = |a: &str, b: &str| HandleMsg::SwapBalances {
+            a: HumanAddr::from(a),
+            b: HumanAddr::from(b),
+        };
+
+        // swapped balances respect the maximum wallet balance
+        let mut deps = initialize_with(InitMsg {
+            max_wallet_balance: Some(Uint128(100)),
+            ..Default::default()
+        });
+        deposit(&mut deps, "creator", 200);
+        match handle(
+            &mut deps,
+            mock_env("creator", &[]),
+            swap_msg("creator", "bob"),
+        ) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("can not exceed")),
+        }
+        assert_eq!(200, balance_of(&deps, "creator"));
+
+        // and registration
+        let mut deps = initialize_with(InitMsg {
+            require_registration: true,
+            ..Default::default()
+        });
+        deposit(&mut deps, "alice", 20);
+        match handle(
+            &mut deps,
+            mock_env("creator", &[]),
+            swap_msg("alice", "bob"),
+        ) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("RecipientNotRegistered")),
+        }
+        handle(
+            &mut deps,
+            mock_env("bob", &[]),
+            HandleMsg::RegisterAccount {},
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            mock_env("creator", &[]),
+            swap_msg("alice", "bob"),
+        )
+        .unwrap();
+        assert_eq!(20, balance_of(&deps, "bob"));
+    }
+
+    #[test]
+    fn handle_set_metadata_frozen() {
+        let set_metadata_msg = HandleMsg::SetMetadata {
+            metadata_uri: Some("ipfs://token.json".to_string()),
+        };
+
+        let mut deps = initialize_with(InitMsg {
+            metadata_frozen: true,
+            ..Default::default()
+        });
+        match handle(
+            &mut deps,
+            mock_env("creator", &[]),
+            set_metadata_msg.clone(),
+        ) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("MetadataFrozen")),
+        }
+        assert_eq!(None, metadata_uri(&deps));
+
+        let mut deps = initialize_with(InitMsg::default());
+        handle(&mut deps, mock_env("creator", &[]), set_metadata_msg).unwrap();
+        assert_eq!(Some("ipfs://token.json".to_string()), metadata_uri(&deps));
+    }
+
+    #[test]
+    fn handle_require_registration() {
+        let mut deps = initialize_with(InitMsg {
+            require_registration: true,
+            ..Default::default()
+        });
+        deposit(&mut deps, "alice", 50);
+
+        match transfer_to(&mut deps, "alice", "bob", 10) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("RecipientNotRegistered")),
+        }
+        assert_eq!(0, balance_of(&deps, "bob"));
+
+        handle(
+            &mut deps,
+            mock_env("bob", &[]),
+            HandleMsg::RegisterAccount {},
+        )
+        .unwrap();
+        transfer_to(&mut deps, "alice", "bob", 10).unwrap();
+        assert_eq!(10, balance_of(&deps, "bob"));
+    }
+
+    #[test]
+    fn query_total_approved_by() {
+        let mut deps = initialize();
+        for (spender, value, expiration) in &[
+            ("bob", 10, None),
+            ("carol", 20, Some(100)),
+            ("dave", 40, Some(200)),
+        ] {
+            let approve_msg = HandleMsg::Approve {
+                spender: HumanAddr::from(*spender),
+                value: Uint128(*value),
+                expiration: *expiration,
+            };
+            handle(&mut deps, mock_env("alice", &[]), approve_msg).unwrap();
+        }
+        approve_to(&mut deps, "bob", "alice", 1000);
+        approve_to(&mut deps, "alice", "bob", 5);
+
+        let query_msg = |owner_key: &str| QueryMsg::TotalApprovedBy {
+            owner: HumanAddr::from("alice"),
+            current_height: 100,
+            owner_key: owner_key.to_string(),
+        };
+        match query(&deps, query_msg("owner key")) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("Unauthorized")),
+        }
+        set_owner_query_key(&mut deps);
+        assert!(query(&deps, query_msg("alice key")).is_err());
+
+        let total_approved_by = |current_height: u64| {
+            let query_msg = QueryMsg::TotalApprovedBy {
+                owner: HumanAddr::from("alice"),
+                current_height,
+                owner_key: "owner key".to_string(),
+            };
+            match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+                QueryResult::TotalApprovedBy { total } => total.u128(),
+                _ => panic!("unexpected"),
+            }
+        };
+        assert_eq!(75, total_approved_by(100));
+        assert_eq!(55, total_approved_by(150));
+        assert_eq!(15, total_approved_by(201));
+    }
+
+    #[test]
+    fn handle_approve_overflow() {
+        let mut deps = initialize();
+        approve_to(&mut deps, "alice", "bob", u128::MAX - 1);
+
+        let approve_msg = HandleMsg::Approve {
+            spender: HumanAddr::from("bob"),
+            value: Uint128(2),
+            expiration: None,
+        };
+        match handle(&mut deps, mock_env("alice", &[]), approve_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("AllowanceOverflow")),
+        }
+        assert_eq!(u128::MAX - 1, allowance_of(&mut deps, "alice", "bob"));
+    }
+
+    #[test]
+    fn handle_set_mint_admin() {
+        let mut deps = initialize();
+        let set_mint_admin_msg = HandleMsg::SetMintAdmin {
+            mint_admin: Some(HumanAddr::from("treasury")),
+        };
+        assert!(handle(
+            &mut deps,
+            mock_env("alice", &[]),
+            set_mint_admin_msg.clone()
+        )
+        .is_err());
+        handle(&mut deps, mock_env("creator", &[]), set_mint_admin_msg).unwrap();
+        match from_binary(&query(&deps, QueryMsg::MintAdmin {}).unwrap()).unwrap() {
+            QueryResult::MintAdmin { mint_admin } => {
+                assert_eq!(Some(HumanAddr::from("treasury")), mint_admin)
+            }
+            _ => panic!("unexpected"),
+        }
+
+        let set_minters_msg = |minters: &[&str]| HandleMsg::SetMinters {
+            minters: minters
+                .iter()
+                .map(|minter| MinterInfo {
+                    address: HumanAddr::from(*minter),
+                    expires_at: None,
+                })
+                .collect(),
+        };
+        let deposit_msg = HandleMsg::DepositTo {
+            to: HumanAddr::from("alice"),
+            value: Uint128(1),
+        };
+
+        // mint admin is not a minter itself
+        assert!(handle(&mut deps, mock_env("treasury", &[]), deposit_msg.clone()).is_err());
+        handle(
+            &mut deps,
+            mock_env("treasury", &[]),
+            set_minters_msg(&["treasury", "minter"]),
+        )
+        .unwrap();
+        handle(&mut deps, mock_env("treasury", &[]), deposit_msg.clone()).unwrap();
+        handle(&mut deps, mock_env("minter", &[]), deposit_msg.clone()).unwrap();
+
+        // owner can still manage minters
+        handle(&mut deps, mock_env("creator", &[]), set_minters_msg(&[])).unwrap();
+        assert!(handle(&mut deps, mock_env("treasury", &[]), deposit_msg).is_err());
+        assert_eq!(2, balance_of(&deps, "alice"));
+
+        // mint admin has no owner powers
+        let disable_msg = HandleMsg::SetDisabledHandlers {
+            handlers: vec![HandlerId::Transfer],
+        };
+        assert!(handle(&mut deps, mock_env("treasury", &[]), disable_msg).is_err());
+        assert!(handle(&mut deps, mock_env("bob", &[]), set_minters_msg(&["bob"])).is_err());
+    }
+
+    #[test]
+    fn handle_default_allowance_expiry() {
+        let mut deps = initialize_with(InitMsg {
+            default_allowance_expiry: Some(100),
+            ..Default::default()
+        });
+        deposit(&mut deps, "alice", 50);
+        deposit(&mut deps, "bob", 50);
+        let set_expiry_msg = HandleMsg::SetDefaultAllowanceExpiry { blocks: Some(10) };
+        handle(&mut deps, mock_env("alice", &[]), set_expiry_msg).unwrap();
+        approve_to(&mut deps, "alice", "carol", 20);
+        approve_to(&mut deps, "bob", "carol", 20);
+
+        let transfer_from_at =
+            |deps: &mut Extern<MockStorage, MockApi, MockQuerier>, from: &str, blocks: u64| {
+                let transfer_from_msg = HandleMsg::TransferFrom {
+                    from: HumanAddr::from(from),
+                    to: HumanAddr::from("dave"),
+                    value: Uint128(1),
+                    deadline: None,
+                };
+                let mut env = mock_env("carol", &[]);
+                env.block.height += blocks;
+                handle(deps, env, transfer_from_msg)
+            };
+
+        // owner default takes precedence over contract default
+        assert!(transfer_from_at(&mut deps, "alice", 10).is_ok());
+        assert!(transfer_from_at(&mut deps, "alice", 11).is_err());
+        assert!(transfer_from_at(&mut deps, "bob", 100).is_ok());
+        assert!(transfer_from_at(&mut deps, "bob", 101).is_err());
+
+        // explicit expiration takes precedence over defaults
+        let approve_msg = HandleMsg::Approve {
+            spender: HumanAddr::from("carol"),
+            value: Uint128(1),
+            expiration: Some(u64::MAX),
+        };
+        handle(&mut deps, mock_env("alice", &[]), approve_msg).unwrap();
+        assert!(transfer_from_at(&mut deps, "alice", 1000).is_ok());
+    }
+
+    #[test]
+    fn query_allowance_detail() {
+        let allowance_detail = |deps: &Extern<MockStorage, MockApi, MockQuerier>| {
+            let query_msg = QueryMsg::AllowanceDetail {
+                owner: HumanAddr::from("alice"),
+                spender: HumanAddr::from("bob"),
+                current_height: 12345,
+            };
+            match from_binary(&query(deps, query_msg).unwrap()).unwrap() {
+                QueryResult::AllowanceDetail {
+                    allowance,
+                    balance,
+                    ratio_bps,
+                } => (allowance.u128(), balance.u128(), ratio_bps),
+                _ => panic!("unexpected"),
+            }
+        };
+
+        let mut deps = initialize();
+        assert_eq!((0, 0, 0), allowance_detail(&deps));
+        approve_to(&mut deps, "alice", "bob", 40);
+        assert_eq!((40, 0, 10_000), allowance_detail(&deps));
+
+        deposit(&mut deps, "alice", 80);
+        assert_eq!((40, 80, 5_000), allowance_detail(&deps));
+
+        approve_to(&mut deps, "alice", "bob", 60);
+        assert_eq!((100, 80, 10_000), allowance_detail(&deps));
+
+        // expired allowance is not reported
+        let approve_msg = HandleMsg::Approve {
+            spender: HumanAddr::from("bob"),
+            value: Uint128(1),
+            expiration: Some(12344),
+        };
+        handle(&mut deps, mock_env("alice", &[]), approve_msg).unwrap();
+        assert_eq!((0, 80, 0), allowance_detail(&deps));
+
+        assert_eq!(3_333, allowance_ratio_bps(1, 3));
+        assert_eq!(9_999, allowance_ratio_bps(u128::MAX - 1, u128::MAX));
+    }
+
+    #[test]
+    fn init_initial_allowances() {
+        let initial = |owner: &str, spender: &str, amount: u128| InitialAllowance {
+            owner: HumanAddr::from(owner),
+            spender: HumanAddr::from(spender),
+            amount: Uint128(amount),
+        };
+
+        let mut deps = initialize_with(InitMsg {
+            initial_allowances: vec![
+                initial("vesting", "router", 100),
+                initial("creator", "router", 50),
+            ],
+            ..Default::default()
+        });
+        assert_eq!(100, allowance_of(&mut deps, "vesting", "router"));
+        assert_eq!(50, allowance_of(&mut deps, "creator", "router"));
+
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            name: "test".to_string(),
+            symbol: "TST".to_string(),
+            initial_allowances: vec![
+                initial("vesting", "router", 100),
+                initial("vesting", "router", 50),
+            ],
+            ..Default::default()
+        };
+        match init(&mut deps, mock_env("creator", &[]), msg.clone()) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("Duplicate initial allowance")),
+        }
+
+        let msg = InitMsg {
+            initial_allowances: vec![initial("vesting", "vesting", 100)],
+            ..msg
+        };
+        assert!(init(&mut deps, mock_env("creator", &[]), msg).is_err());
+    }
+
+    #[test]
+    fn handle_transfer_min_holding_period() {
+        let mut deps = initialize_with(InitMsg {
+            min_holding_period: Some(10),
+            ..Default::default()
+        });
+        deposit(&mut deps, "alice", 100);
+
+        let transfer_at = |deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+                           from: &str,
+                           to: &str,
+                           height: u64| {
+            let mut env = mock_env(from, &[]);
+            env.block.height = height;
+            let handle_msg = HandleMsg::Transfer {
+                to: HumanAddr::from(to),
+                value: Uint128(10),
+                log_note: None,
+                deadline: None,
+            };
+            handle(deps, env, handle_msg)
+        };
+
+        match transfer_at(&mut deps, "alice", "bob", 12_354) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("HoldingPeriod")),
+        }
+        transfer_at(&mut deps, "alice", "bob", 12_355).unwrap();
+
+        // second credit does not restart bob holding period
+        transfer_at(&mut deps, "alice", "bob", 12_360).unwrap();
+        match transfer_at(&mut deps, "bob", "carol", 12_364) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("HoldingPeriod")),
+        }
+        transfer_at(&mut deps, "bob", "carol", 12_365).unwrap();
+        assert_eq!(10, balance_of(&deps, "bob"));
+
+        // owner is exempt
+        deposit(&mut deps, "creator", 100);
+        transfer_to(&mut deps, "creator", "alice", 10).unwrap();
+    }
+
+    #[test]
+    fn query_can_transfer() {
+        let mut deps = initialize_with(InitMsg {
+            min_holding_period: Some(10),
+            require_registration: true,
+            ..Default::default()
+        });
+        deposit(&mut deps, "alice", 100);
+        handle(
+            &mut deps,
+            mock_env("bob", &[]),
+            HandleMsg::RegisterAccount {},
+        )
+        .unwrap();
+
+        let can_transfer = |deps: &Extern<MockStorage, MockApi, MockQuerier>,
+                            to: &str,
+                            value: u128,
+                            current_height: u64| {
+            let query_msg = QueryMsg::CanTransfer {
+                from: HumanAddr::from("alice"),
+                to: HumanAddr::from(to),
+                value: Uint128(value),
+                current_height,
+                deadline: None,
+            };
+            match from_binary(&query(deps, query_msg).unwrap()).unwrap() {
+                QueryResult::CanTransfer { allowed, reason } => {
+                    assert_eq!(allowed, reason.is_none());
+                    reason
+                }
+                _ => panic!("unexpected"),
+            }
+        };
+
+        assert_eq!(None, can_transfer(&deps, "bob", 100, 12_355));
+        assert!(can_transfer(&deps, "bob", 100, 12_354)
+            .unwrap()
+            .contains("HoldingPeriod"));
+        assert!(can_transfer(&deps, "bob", 101, 12_355)
+            .unwrap()
+            .contains("balance underflow"));
+        assert!(can_transfer(&deps, "carol", 100, 12_355)
+            .unwrap()
+            .contains("RecipientNotRegistered"));
+
+        let handle_msg = HandleMsg::SetLargeTransferThreshold {
+            threshold: Some(Uint128(50)),
+        };
+        handle(&mut deps, mock_env("alice", &[]), handle_msg).unwrap();
+        assert!(can_transfer(&deps, "bob", 51, 12_355)
+            .unwrap()
+            .contains("large transfer threshold"));
+        assert_eq!(None, can_transfer(&deps, "bob", 50, 12_355));
+
+        let handle_msg = HandleMsg::SetDisabledHandlers {
+            handlers: vec![HandlerId::Transfer],
+        };
+        handle(&mut deps, mock_env("creator", &[]), handle_msg).unwrap();
+        assert!(can_transfer(&deps, "bob", 50, 12_355)
+            .unwrap()
+            .contains("HandlerDisabled"));
+        assert_eq!(100, balance_of(&deps, "alice"));
+    }
+
+    #[test]
+    fn handle_claim_with_code() {
+        let mut deps = initialize();
+
+        let handle_msg = HandleMsg::RegisterClaimCode {
+            code: "airdrop-42".to_string(),
+            amount: Uint128(42),
+        };
+        match handle(&mut deps, mock_env("alice", &[]), handle_msg.clone()) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("Only contract owner")),
+        }
+        handle(&mut deps, mock_env("creator", &[]), handle_msg.clone()).unwrap();
+        match handle(&mut deps, mock_env("creator", &[]), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("already registered")),
+        }
+
+        // only the hash of the code is stored
+        let stored = ReadonlyPrefixedStorage::new(StorageKeys::CLAIM_CODES, &deps.storage);
+        assert!(stored.get(b"airdrop-42").is_none());
+        assert!(stored.get(&claim_code_hash("airdrop-42")).is_some());
+
+        let claim = |code: &str| HandleMsg::ClaimWithCode {
+            code: code.to_string(),
+        };
+        match handle(&mut deps, mock_env("alice", &[]), claim("airdrop-43")) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("Invalid claim code")),
+        }
+
+        let res = handle(&mut deps, mock_env("alice", &[]), claim("airdrop-42")).unwrap();
+        match from_binary(&res.data.unwrap()).unwrap() {
+            HandleResult::ClaimWithCode { amount, .. } => assert_eq!(42, amount.u128()),
+            _ => panic!("unexpected"),
+        }
+        assert_eq!(42, balance_of(&deps, "alice"));
+        assert_eq!(42, total_supply(&deps));
+
+        match handle(&mut deps, mock_env("bob", &[]), claim("airdrop-42")) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("already used")),
+        }
+        assert_eq!(0, balance_of(&deps, "bob"));
+        assert_eq!(42, total_supply(&deps));
+    }
+
+    #[test]
+    fn handle_zero_approve() {
+        let zero_approve = HandleMsg::Approve {
+            spender: HumanAddr::from("bob"),
+            value: Uint128(0),
+            expiration: None,
+        };
+
+        let mut deps = initialize();
+        approve_to(&mut deps, "alice", "bob", 10);
+        match handle(&mut deps, mock_env("alice", &[]), zero_approve.clone()) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("Can not approve zero tokens")),
+        }
+        assert_eq!(10, allowance_of(&mut deps, "alice", "bob"));
+
+        let mut deps = initialize_with(InitMsg {
+            zero_approve_revokes: true,
+            ..Default::default()
+        });
+        let handle_msg = HandleMsg::Approve {
+            spender: HumanAddr::from("bob"),
+            value: Uint128(10),
+            expiration: Some(20_000),
+        };
+        handle(&mut deps, mock_env("alice", &[]), handle_msg).unwrap();
+        approve_to(&mut deps, "alice", "carol", 20);
+        handle(&mut deps, mock_env("alice", &[]), zero_approve).unwrap();
+        assert_eq!(0, allowance_of(&mut deps, "alice", "bob"));
+        assert_eq!(20, allowance_of(&mut deps, "alice", "carol"));
+
+        // allowance, spender list and index entries are removed from storage
+        let owner = deps
+            .api
+            .canonical_address(&HumanAddr::from("alice"))
+            .unwrap();
+        let spender = deps.api.canonical_address(&HumanAddr::from("bob")).unwrap();
+        let allowances = ReadonlyPrefixedStorage::multilevel(
+            &[StorageKeys::ALLOWANCES, owner.as_slice()],
+            &deps.storage,
+        );
+        assert_eq!(None, allowances.get(spender.as_slice()));
+        let carol = deps
+            .api
+            .canonical_address(&HumanAddr::from("carol"))
+            .unwrap();
+        assert_eq!(
+            vec![carol.clone()],
+            get_allowance_spenders(&deps.storage, &owner).unwrap()
+        );
+        assert_eq!(None, get_allowance_entry(&deps.storage, 0).unwrap());
+        assert_eq!(
+            carol,
+            get_allowance_entry(&deps.storage, 1)
+                .unwrap()
+                .unwrap()
+                .spender
+        );
+        let positions = ReadonlyPrefixedStorage::multilevel(
+            &[StorageKeys::ALLOWANCE_POSITIONS, owner.as_slice()],
+            &deps.storage,
+        );
+        assert_eq!(None, positions.get(spender.as_slice()));
+
+        // approving again starts from zero
+        approve_to(&mut deps, "alice", "bob", 5);
+        assert_eq!(5, allowance_of(&mut deps, "alice", "bob"));
+    }
+
+    #[test]
+    fn handle_lock_my_account() {
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 100);
+        approve_to(&mut deps, "alice", "bob", 50);
+
+        handle(
+            &mut deps,
+            mock_env("alice", &[]),
+            HandleMsg::LockMyAccount {},
+        )
+        .unwrap();
+        match transfer_to(&mut deps, "alice", "bob", 10) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("AccountLocked")),
+        }
+        let handle_msg = HandleMsg::TransferFrom {
+            from: HumanAddr::from("alice"),
+            to: HumanAddr::from("bob"),
+            value: Uint128(10),
+            deadline: None,
+        };
+        match handle(&mut deps, mock_env("bob", &[]), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("AccountLocked")),
+        }
+        let handle_msg = HandleMsg::Approve {
+            spender: HumanAddr::from("carol"),
+            value: Uint128(10),
+            expiration: None,
+        };
+        match handle(&mut deps, mock_env("alice", &[]), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("AccountLocked")),
+        }
+
+        // incoming transfers are still accepted
+        deposit(&mut deps, "carol", 10);
+        transfer_to(&mut deps, "carol", "alice", 10).unwrap();
+
+        // only the account can unlock itself
+        handle(
+            &mut deps,
+            mock_env("creator", &[]),
+            HandleMsg::UnlockMyAccount {},
+        )
+        .unwrap();
+        assert!(transfer_to(&mut deps, "alice", "bob", 10).is_err());
+
+        handle(
+            &mut deps,
+            mock_env("alice", &[]),
+            HandleMsg::UnlockMyAccount {},
+        )
+        .unwrap();
+        transfer_to(&mut deps, "alice", "bob", 10).unwrap();
+        assert_eq!(100, balance_of(&deps, "alice"));
+        assert_eq!(10, balance_of(&deps, "bob"));
+    }
+
+    #[test]
+    fn query_minters_detail() {
+        let mut deps = initialize();
+
+        let minter = |address: &str, expires_at: Option<u64>| MinterInfo {
+            address: HumanAddr::from(address),
+            expires_at,
+        };
+        let handle_msg = HandleMsg::SetMinters {
+            minters: vec![
+                minter("carol", None),
+                minter("alice", Some(100)),
+                minter("bob", Some(20_000)),
+            ],
+        };
+        handle(&mut deps, mock_env("creator", &[]), handle_msg).unwrap();
+
+        let minters_detail = |start_after: Option<&str>, limit: Option<u32>| {
+            let query_msg = QueryMsg::MintersDetail {
+                start_after: start_after.map(HumanAddr::from),
+                limit,
+            };
+            match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+                QueryResult::MintersDetail { minters } => minters,
+                _ => panic!("unexpected"),
+            }
+        };
+        assert_eq!(
+            vec![
+                minter("alice", Some(100)),
+                minter("bob", Some(20_000)),
+                minter("carol", None),
+            ],
+            minters_detail(None, None)
+        );
+        assert_eq!(
+            vec![minter("alice", Some(100)), minter("bob", Some(20_000))],
+            minters_detail(None, Some(2))
+        );
+        assert_eq!(
+            vec![minter("carol", None)],
+            minters_detail(Some("bob"), Some(2))
+        );
+        assert!(minters_detail(Some("carol"), None).is_empty());
+    }
+
+    #[test]
+    fn handle_transfer_from_clamp() {
+        let transfer_from = HandleMsg::TransferFrom {
+            from: HumanAddr::from("alice"),
+            to: HumanAddr::from("carol"),
+            value: Uint128(50),
+            deadline: None,
+        };
+
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 100);
+        approve_to(&mut deps, "alice", "bob", 30);
+        match handle(&mut deps, mock_env("bob", &[]), transfer_from.clone()) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("Not enough allowance")),
+        }
+        assert_eq!(100, balance_of(&deps, "alice"));
+
+        let mut deps = initialize_with(InitMsg {
+            clamp_transfer_from: true,
+            ..Default::default()
+        });
+        deposit(&mut deps, "alice", 100);
+        approve_to(&mut deps, "alice", "bob", 30);
+        handle(&mut deps, mock_env("bob", &[]), transfer_from.clone()).unwrap();
+        assert_eq!(70, balance_of(&deps, "alice"));
+        assert_eq!(30, balance_of(&deps, "carol"));
+        assert_eq!(0, allowance_of(&mut deps, "alice", "bob"));
+
+        match handle(&mut deps, mock_env("bob", &[]), transfer_from) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("Not enough allowance")),
+        }
+        assert_eq!(70, balance_of(&deps, "alice"));
+    }
+
+    #[test]
+    fn handle_transfer_fallback_recipient() {
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 100);
+
+        let handle_msg = HandleMsg::SetReceivePolicy {
+            allowed_senders: Some(vec![HumanAddr::from("carol")]),
+        };
+        handle(&mut deps, mock_env("bob", &[]), handle_msg).unwrap();
+        match transfer_to(&mut deps, "alice", "bob", 10) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("RecipientRefused")),
+        }
+
+        let handle_msg = HandleMsg::SetFallbackRecipient {
+            fallback: Some(HumanAddr::from("bob")),
+        };
+        match handle(&mut deps, mock_env("bob", &[]), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("self as fallback")),
+        }
+        let handle_msg = HandleMsg::SetFallbackRecipient {
+            fallback: Some(HumanAddr::from("escrow")),
+        };
+        handle(&mut deps, mock_env("bob", &[]), handle_msg).unwrap();
+
+        let res = transfer_to(&mut deps, "alice", "bob", 10).unwrap();
+        assert_eq!(vec![log("redirected_to", "escrow")], res.log);
+        assert_eq!(0, balance_of(&deps, "bob"));
+        assert_eq!(10, balance_of(&deps, "escrow"));
+        assert_eq!(90, balance_of(&deps, "alice"));
+
+        // accepted transfers are not redirected
+        deposit(&mut deps, "carol", 10);
+        let res = transfer_to(&mut deps, "carol", "bob", 10).unwrap();
+        assert!(res.log.is_empty());
+        assert_eq!(10, balance_of(&deps, "bob"));
+    }
+
+    struct OracleQuerier {
+        price: Option<u128>,
+    }
+
+    impl Querier for OracleQuerier {
+        fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+            let request: QueryRequest<Empty> = from_slice(bin_request).unwrap();
+            match request {
+                QueryRequest::Wasm(WasmQuery::Smart {
+                    contract_addr,
+                    callback_code_hash,
+                    msg,
+                }) => {
+                    assert_eq!(HumanAddr::from("oracle"), contract_addr);
+                    assert_eq!("hash", callback_code_hash);
+                    let OracleQueryMsg::Price {} = from_binary(&msg).unwrap();
+                }
+                _ => panic!("unexpected"),
+            }
+            match self.price {
+                Some(price) => Ok(to_binary(&PriceResponse {
+                    price: Uint128(price),
+                })),
+                None => Ok(Err(StdError::generic_err("oracle is broken"))),
+            }
+        }
+    }
+
+    #[test]
+    fn query_priced_value() {
+        let mut deps = initialize_with(InitMsg {
+            price_oracle: Some(PriceOracleInfo {
+                address: HumanAddr::from("oracle"),
+                code_hash: "hash".to_string(),
+            }),
+            ..Default::default()
+        });
+        deposit(&mut deps, "alice", 2_500_000);
+
+        let priced_value = |deps: &Extern<MockStorage, MockApi, OracleQuerier>| {
+            let query_msg = QueryMsg::PricedValue {
+                address: HumanAddr::from("alice"),
+            };
+            query(deps, query_msg).map(|res| match from_binary(&res).unwrap() {
+                QueryResult::PricedValue {
+                    balance,
+                    price,
+                    value,
+                } => (balance.u128(), price.u128(), value.u128()),
+                _ => panic!("unexpected"),
+            })
+        };
+        let deps = Extern {
+            storage: deps.storage,
+            api: deps.api,
+            querier: OracleQuerier { price: Some(1_200) },
+        };
+        assert_eq!((2_500_000, 1_200, 3_000), priced_value(&deps).unwrap());
+
+        // failed oracle query is an error rather than a stale price
+        let deps = Extern {
+            storage: deps.storage,
+            api: deps.api,
+            querier: OracleQuerier { price: None },
+        };
+        match priced_value(&deps) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("OracleQueryFailed")),
+        }
+
+        // token unit does not fit into u128
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            name: "test".to_string(),
+            symbol: "!@#$".to_string(),
+            decimals: 39,
+            price_oracle: Some(PriceOracleInfo {
+                address: HumanAddr::from("oracle"),
+                code_hash: "hash".to_string(),
+            }),
+            ..Default::default()
+        };
+        init(&mut deps, mock_env("creator", &[]), msg).unwrap();
+        deposit(&mut deps, "alice", 2_500_000);
+        let deps = Extern {
+            storage: deps.storage,
+            api: deps.api,
+            querier: OracleQuerier { price: Some(1_200) },
+        };
+        match priced_value(&deps) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("Priced value overflow")),
+        }
+
+        let deps = initialize();
+        match query(
+            &deps,
+            QueryMsg::PricedValue {
+                address: HumanAddr::from("alice"),
+            },
+        ) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("not configured")),
+        }
+    }
+
+    #[test]
+    fn handle_mint_and_approve() {
+        let mut deps = initialize();
+        let handle_msg = HandleMsg::SetMinters {
+            minters: vec![MinterInfo {
+                address: HumanAddr::from("vesting"),
+                expires_at: None,
+            }],
+        };
+        handle(&mut deps, mock_env("creator", &[]), handle_msg).unwrap();
+
+        let mint_and_approve = |to: &str| HandleMsg::MintAndApprove {
+            to: HumanAddr::from(to),
+            mint_value: Uint128(100),
+            spender: HumanAddr::from("beneficiary"),
+            approve_value: Uint128(40),
+        };
+
+        match handle(&mut deps, mock_env("alice", &[]), mint_and_approve("alice")) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("owner or minters")),
+        }
+        // minters can not approve on behalf of other accounts
+        match handle(
+            &mut deps,
+            mock_env("vesting", &[]),
+            mint_and_approve("alice"),
+        ) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("on behalf of another account")),
         }
+        assert_eq!(0, total_supply(&deps));
+
+        handle(
+            &mut deps,
+            mock_env("vesting", &[]),
+            mint_and_approve("vesting"),
+        )
+        .unwrap();
+        assert_eq!(100, balance_of(&deps, "vesting"));
+        assert_eq!(40, allowance_of(&mut deps, "vesting", "beneficiary"));
+
+        handle(
+            &mut deps,
+            mock_env("creator", &[]),
+            mint_and_approve("alice"),
+        )
+        .unwrap();
+        assert_eq!(100, balance_of(&deps, "alice"));
+        assert_eq!(40, allowance_of(&mut deps, "alice", "beneficiary"));
+        assert_eq!(200, total_supply(&deps));
     }
 
     #[test]
-    fn handle_burn_from() {
+    fn handle_burn_sweeps_dust() {
+        let burn = |value: u128| HandleMsg::BurnFrom {
+            from: HumanAddr::from("alice"),
+            value: Uint128(value),
+        };
+
         let mut deps = initialize();
-        let address = HumanAddr::from("address");
-        let handle_msg = HandleMsg::DepositTo {
-            to: address.clone(),
-            value: Uint128(69),
+        deposit(&mut deps, "alice", 100);
+        handle(&mut deps, mock_env("alice", &[]), burn(95)).unwrap();
+        assert_eq!(5, balance_of(&deps, "alice"));
+        assert_eq!(5, total_supply(&deps));
+
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            burn_sweeps_dust: true,
+            ..Default::default()
         };
-        match handle(&mut deps, mock_env("creator", &[]), handle_msg) {
-            Ok(_) => {}
-            Err(e) => panic!("error: {:?}", e),
-        }
+        assert!(init(&mut deps, mock_env("creator", &[]), msg).is_err());
 
-        let handle_msg = HandleMsg::BurnFrom {
-            from: address.clone(),
-            value: Uint128(9),
+        let mut deps = initialize_with(InitMsg {
+            burn_sweeps_dust: true,
+            dust_threshold: Some(Uint128(10)),
+            ..Default::default()
+        });
+        deposit(&mut deps, "alice", 100);
+        deposit(&mut deps, "bob", 10);
+        handle(&mut deps, mock_env("alice", &[]), burn(50)).unwrap();
+        assert_eq!(50, balance_of(&deps, "alice"));
+        handle(&mut deps, mock_env("alice", &[]), burn(40)).unwrap();
+        assert_eq!(10, balance_of(&deps, "alice"));
+        handle(&mut deps, mock_env("alice", &[]), burn(5)).unwrap();
+        assert_eq!(0, balance_of(&deps, "alice"));
+        assert_eq!(10, total_supply(&deps));
+
+        // batch burns sweep dust as well
+        let handle_msg = HandleMsg::BatchBurn {
+            actions: vec![BurnAction {
+                owner: HumanAddr::from("bob"),
+                value: Uint128(3),
+            }],
         };
-        match handle(&mut deps, mock_env("creator", &[]), handle_msg) {
-            Ok(_) => {}
-            Err(e) => panic!("error: {:?}", e),
-        }
+        handle(&mut deps, mock_env("creator", &[]), handle_msg).unwrap();
+        assert_eq!(0, balance_of(&deps, "bob"));
+        assert_eq!(0, total_supply(&deps));
+    }
 
-        // checking new balance
-        let res = query(&deps, QueryMsg::BalanceOf { address }).unwrap();
-        match from_binary(&res).unwrap() {
-            QueryResult::BalanceOf { balance } => assert_eq!(60, balance.u128()),
+    #[test]
+    fn query_schema_version() {
+        let mut deps = initialize();
+        let schema_version = |deps: &Extern<MockStorage, MockApi, MockQuerier>| match from_binary(
+            &query(deps, QueryMsg::SchemaVersion {}).unwrap(),
+        )
+        .unwrap()
+        {
+            QueryResult::SchemaVersion { version } => version,
             _ => panic!("unexpected"),
-        }
+        };
+        assert_eq!(SCHEMA_VERSION, schema_version(&deps));
 
-        // checking new total supply
-        let res = query(&deps, QueryMsg::TotalSupply {}).unwrap();
-        match from_binary(&res).unwrap() {
-            QueryResult::TotalSupply { total_supply } => assert_eq!(60, total_supply.u128()),
-            _ => panic!("unexpected"),
-        }
+        // migration bumping the minor version
+        let bumped = SchemaVersion {
+            major: SCHEMA_VERSION.major,
+            minor: SCHEMA_VERSION.minor + 1,
+        };
+        ContractStorage::from_storage(&mut deps.storage)
+            .set_schema_version(&bumped)
+            .unwrap();
+        assert_eq!(bumped, schema_version(&deps));
     }
 
     #[test]
-    fn handle_burn_from_owner() {
+    fn handle_escrow() {
         let mut deps = initialize();
-        let address = HumanAddr::from("address");
-        let handle_msg = HandleMsg::DepositTo {
-            to: address.clone(),
-            value: Uint128(69),
+        deposit(&mut deps, "alice", 100);
+
+        let at = |sender: &str, height: u64| {
+            let mut env = mock_env(sender, &[]);
+            env.block.height = height;
+            env
         };
-        match handle(&mut deps, mock_env("creator", &[]), handle_msg) {
+        let create_escrow = |deps: &mut Extern<MockStorage, MockApi, MockQuerier>| {
+            let handle_msg = HandleMsg::CreateEscrow {
+                recipient: HumanAddr::from("bob"),
+                value: Uint128(30),
+                release_height: 200,
+            };
+            let res = handle(deps, at("alice", 100), handle_msg).unwrap();
+            match from_binary(&res.data.unwrap()).unwrap() {
+                HandleResult::CreateEscrow { id, .. } => id,
+                _ => panic!("unexpected"),
+            }
+        };
+
+        let released = create_escrow(&mut deps);
+        let refunded = create_escrow(&mut deps);
+        assert_ne!(released, refunded);
+        assert_eq!(40, balance_of(&deps, "alice"));
+        assert_eq!(0, balance_of(&deps, "bob"));
+        assert_eq!(100, total_supply(&deps));
+
+        // early release
+        let handle_msg = HandleMsg::ReleaseEscrow { id: released };
+        match handle(&mut deps, at("bob", 199), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(_) => {}
+        }
+
+        // only the sender can refund
+        let handle_msg = HandleMsg::RefundEscrow { id: refunded };
+        match handle(&mut deps, at("bob", 150), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(_) => {}
+        }
+        let handle_msg = HandleMsg::RefundEscrow { id: refunded };
+        match handle(&mut deps, at("alice", 150), handle_msg) {
             Ok(_) => {}
             Err(e) => panic!("error: {:?}", e),
         }
+        assert_eq!(70, balance_of(&deps, "alice"));
 
-        let handle_msg = HandleMsg::BurnFrom {
-            from: address.clone(),
-            value: Uint128(9),
-        };
-        match handle(&mut deps, mock_env(address.clone(), &[]), handle_msg) {
+        // refunded escrow is gone
+        let handle_msg = HandleMsg::ReleaseEscrow { id: refunded };
+        match handle(&mut deps, at("bob", 200), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(_) => {}
+        }
+
+        // no refund once releasable
+        let handle_msg = HandleMsg::RefundEscrow { id: released };
+        match handle(&mut deps, at("alice", 200), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(_) => {}
+        }
+
+        let handle_msg = HandleMsg::ReleaseEscrow { id: released };
+        match handle(&mut deps, at("bob", 200), handle_msg) {
             Ok(_) => {}
             Err(e) => panic!("error: {:?}", e),
         }
+        assert_eq!(70, balance_of(&deps, "alice"));
+        assert_eq!(30, balance_of(&deps, "bob"));
 
-        // checking new balance
-        let res = query(&deps, QueryMsg::BalanceOf { address }).unwrap();
-        match from_binary(&res).unwrap() {
-            QueryResult::BalanceOf { balance } => assert_eq!(60, balance.u128()),
-            _ => panic!("unexpected"),
+        // released escrow can not be released again
+        let handle_msg = HandleMsg::ReleaseEscrow { id: released };
+        match handle(&mut deps, at("bob", 201), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(_) => {}
         }
+    }
 
-        // checking new total supply
-        let res = query(&deps, QueryMsg::TotalSupply {}).unwrap();
-        match from_binary(&res).unwrap() {
-            QueryResult::TotalSupply { total_supply } => assert_eq!(60, total_supply.u128()),
-            _ => panic!("unexpected"),
+    #[test]
+    fn handle_burn_min_total_supply() {
+        let burn = |value: u128| HandleMsg::BurnFrom {
+            from: HumanAddr::from("alice"),
+            value: Uint128(value),
+        };
+
+        let mut deps = initialize_with(InitMsg {
+            min_total_supply: Uint128(40),
+            ..Default::default()
+        });
+        deposit(&mut deps, "alice", 100);
+
+        // below the floor
+        match handle(&mut deps, mock_env("alice", &[]), burn(61)) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("BelowMinimumSupply")),
         }
+        assert_eq!(100, total_supply(&deps));
+
+        // exactly the floor
+        handle(&mut deps, mock_env("alice", &[]), burn(60)).unwrap();
+        assert_eq!(40, total_supply(&deps));
+        assert!(handle(&mut deps, mock_env("alice", &[]), burn(1)).is_err());
+
+        // zero disables the floor
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 100);
+        handle(&mut deps, mock_env("alice", &[]), burn(100)).unwrap();
+        assert_eq!(0, total_supply(&deps));
     }
 
     #[test]
-    fn handle_burn_from_invalid_sender() {
+    fn query_balance_by_tag() {
         let mut deps = initialize();
-        let address = HumanAddr::from("address");
-        let handle_msg = HandleMsg::DepositTo {
-            to: address.clone(),
-            value: Uint128(69),
+        deposit(&mut deps, "alice", 10);
+        deposit(&mut deps, "bob", 20);
+        deposit(&mut deps, "carol", 40);
+
+        let tag = |deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+                   sender: &str,
+                   account: &str,
+                   tag: Option<&str>| {
+            let handle_msg = HandleMsg::SetAccountTag {
+                account: HumanAddr::from(account),
+                tag: tag.map(String::from),
+            };
+            handle(deps, mock_env(sender, &[]), handle_msg)
+        };
+        let balance_by_tag = |deps: &Extern<MockStorage, MockApi, MockQuerier>, tag: &str| {
+            let query_msg = QueryMsg::BalanceByTag {
+                tag: tag.to_string(),
+                owner_key: "owner key".to_string(),
+            };
+            match from_binary(&query(deps, query_msg).unwrap()).unwrap() {
+                QueryResult::BalanceByTag { accounts, balance } => (accounts, balance.u128()),
+                _ => panic!("unexpected"),
+            }
         };
-        match handle(&mut deps, mock_env("creator", &[]), handle_msg) {
-            Ok(_) => {}
-            Err(e) => panic!("error: {:?}", e),
-        }
 
-        let handle_msg = HandleMsg::BurnFrom {
-            from: address.clone(),
-            value: Uint128(9),
+        // only the owner tags accounts
+        assert!(tag(&mut deps, "alice", "alice", Some("sales")).is_err());
+        assert!(tag(&mut deps, "creator", "alice", Some("")).is_err());
+
+        tag(&mut deps, "creator", "alice", Some("sales")).unwrap();
+        tag(&mut deps, "creator", "bob", Some("sales")).unwrap();
+        tag(&mut deps, "creator", "carol", Some("ops")).unwrap();
+
+        // only the owner queries the sums
+        let query_msg = QueryMsg::BalanceByTag {
+            tag: "sales".to_string(),
+            owner_key: "owner key".to_string(),
         };
-        match handle(&mut deps, mock_env("bob", &[]), handle_msg) {
+        match query(&deps, query_msg) {
             Ok(_) => panic!("should have failed"),
-            Err(_) => {}
+            Err(e) => assert!(e.to_string().contains("Unauthorized")),
         }
+        set_owner_query_key(&mut deps);
 
-        // checking balance
-        let res = query(&deps, QueryMsg::BalanceOf { address }).unwrap();
-        match from_binary(&res).unwrap() {
-            QueryResult::BalanceOf { balance } => assert_eq!(69, balance.u128()),
+        assert_eq!((2, 30), balance_by_tag(&deps, "sales"));
+        assert_eq!((1, 40), balance_by_tag(&deps, "ops"));
+        assert_eq!((0, 0), balance_by_tag(&deps, "legal"));
+
+        // sums follow balance changes
+        let handle_msg = HandleMsg::Transfer {
+            to: HumanAddr::from("alice"),
+            value: Uint128(15),
+            log_note: None,
+            deadline: None,
+        };
+        handle(&mut deps, mock_env("carol", &[]), handle_msg).unwrap();
+        assert_eq!((2, 45), balance_by_tag(&deps, "sales"));
+        assert_eq!((1, 25), balance_by_tag(&deps, "ops"));
+
+        // retagging moves the account between tags
+        tag(&mut deps, "creator", "bob", Some("ops")).unwrap();
+        assert_eq!((1, 25), balance_by_tag(&deps, "sales"));
+        assert_eq!((2, 45), balance_by_tag(&deps, "ops"));
+
+        tag(&mut deps, "creator", "carol", None).unwrap();
+        assert_eq!((1, 20), balance_by_tag(&deps, "ops"));
+    }
+
+    #[test]
+    fn handle_emits_sequence() {
+        let mut deps = initialize_with(InitMsg {
+            emit_sequence: true,
+            ..Default::default()
+        });
+        let transfer = || HandleMsg::Transfer {
+            to: HumanAddr::from("bob"),
+            value: Uint128(10),
+            log_note: None,
+            deadline: None,
+        };
+
+        let handle_msg = HandleMsg::DepositTo {
+            to: HumanAddr::from("alice"),
+            value: Uint128(100),
+        };
+        let res = handle(&mut deps, mock_env("creator", &[]), handle_msg).unwrap();
+        assert_eq!(vec![log("sequence", 1)], res.log);
+
+        let res = handle(&mut deps, mock_env("alice", &[]), transfer()).unwrap();
+        assert_eq!(vec![log("sequence", 2)], res.log);
+
+        // failed handles do not take a sequence number
+        assert!(handle(&mut deps, mock_env("carol", &[]), transfer()).is_err());
+
+        // neither do handles that change no state
+        let handle_msg = HandleMsg::Allowance {
+            owner: HumanAddr::from("alice"),
+            spender: HumanAddr::from("bob"),
+        };
+        let res = handle(&mut deps, mock_env("alice", &[]), handle_msg).unwrap();
+        assert!(res.log.is_empty());
+
+        let res = handle(&mut deps, mock_env("alice", &[]), transfer()).unwrap();
+        assert_eq!(vec![log("sequence", 3)], res.log);
+
+        // disabled by default
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 100);
+        let res = handle(&mut deps, mock_env("alice", &[]), transfer()).unwrap();
+        assert!(res.log.is_empty());
+    }
+
+    #[test]
+    fn query_parameters() {
+        let mut deps = initialize_with(InitMsg {
+            max_batch_size: Some(50),
+            max_wallet_balance: Some(Uint128(1000)),
+            require_registration: true,
+            min_holding_period: Some(10),
+            burn_sweeps_dust: true,
+            dust_threshold: Some(Uint128(5)),
+            min_total_supply: Uint128(100),
+            emit_sequence: true,
+            ..Default::default()
+        });
+        let handle_msg = HandleMsg::SetTransferGuard {
+            guard: Some(TransferGuardInfo {
+                address: HumanAddr::from("guard"),
+                code_hash: "hash".to_string(),
+                fail_open: true,
+            }),
+        };
+        handle(&mut deps, mock_env("creator", &[]), handle_msg).unwrap();
+        let handle_msg = HandleMsg::SetDisabledHandlers {
+            handlers: vec![HandlerId::BatchBurn],
+        };
+        handle(&mut deps, mock_env("creator", &[]), handle_msg).unwrap();
+        let handle_msg = HandleMsg::SetRewardPool {
+            pool: HumanAddr::from("pool"),
+            max_reward_bps: 100,
+        };
+        handle(&mut deps, mock_env("creator", &[]), handle_msg).unwrap();
+        let handle_msg = HandleMsg::SetMetadata {
+            metadata_uri: Some("ipfs://metadata".to_string()),
+        };
+        handle(&mut deps, mock_env("creator", &[]), handle_msg).unwrap();
+        let handle_msg = HandleMsg::SetMintAdmin {
+            mint_admin: Some(HumanAddr::from("admin")),
+        };
+        handle(&mut deps, mock_env("creator", &[]), handle_msg).unwrap();
+
+        let expected = Parameters {
+            decimals: 6,
+            reserve: None,
+            verbose_logging: false,
+            recovery_delay: DEFAULT_RECOVERY_DELAY,
+            max_batch_size: 50,
+            reject_value_above_supply: false,
+            max_wallet_balance: Some(Uint128(1000)),
+            treat_zero_address_as_burn: false,
+            metadata_frozen: false,
+            require_registration: true,
+            default_allowance_expiry: None,
+            min_holding_period: Some(10),
+            zero_approve_revokes: false,
+            clamp_transfer_from: false,
+            dust_threshold: Some(Uint128(5)),
+            min_total_supply: Uint128(100),
+            emit_sequence: true,
+            price_oracle: None,
+            transfer_guard: Some(TransferGuardInfo {
+                address: HumanAddr::from("guard"),
+                code_hash: "hash".to_string(),
+                fail_open: true,
+            }),
+            reward_pool: Some(RewardPoolInfo {
+                pool: HumanAddr::from("pool"),
+                max_reward_bps: 100,
+            }),
+            metadata_uri: Some("ipfs://metadata".to_string()),
+            mint_admin: Some(HumanAddr::from("admin")),
+            disabled_handlers: vec![HandlerId::BatchBurn],
+        };
+        let parameters = match from_binary(&query(&deps, QueryMsg::Parameters {}).unwrap()).unwrap()
+        {
+            QueryResult::Parameters { parameters } => parameters,
             _ => panic!("unexpected"),
+        };
+        assert_eq!(expected, parameters);
+
+        let json = cosmwasm_std::to_vec(&parameters).unwrap();
+        assert_eq!(parameters, from_slice::<Parameters>(&json).unwrap());
+    }
+
+    #[test]
+    fn handle_set_minters_after_renounce() {
+        let mut deps = initialize_with(InitMsg {
+            token_id: "token".to_string(),
+            ..Default::default()
+        });
+        let handle_msg = HandleMsg::RenounceOwnership {
+            confirm: "token".to_string(),
+        };
+        handle(&mut deps, mock_env("creator", &[]), handle_msg).unwrap();
+
+        // mint admin left over by a renounce that did not clear it
+        let admin = deps
+            .api
+            .canonical_address(&HumanAddr::from("admin"))
+            .unwrap();
+        let mut storage = ContractStorage::from_storage(&mut deps.storage);
+        let mut constants = storage.constants().unwrap();
+        constants.mint_admin = Some(admin);
+        storage.set_constants(&constants).unwrap();
+
+        let handle_msg = HandleMsg::SetMinters {
+            minters: vec![MinterInfo {
+                address: HumanAddr::from("minter"),
+                expires_at: None,
+            }],
+        };
+        match handle(&mut deps, mock_env("admin", &[]), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("MintingDisabled")),
         }
+    }
 
-        // checking new total supply
-        let res = query(&deps, QueryMsg::TotalSupply {}).unwrap();
-        match from_binary(&res).unwrap() {
-            QueryResult::TotalSupply { total_supply } => assert_eq!(69, total_supply.u128()),
-            _ => panic!("unexpected"),
+    #[test]
+    fn handle_transfer_with_reward_max_wallet_balance() {
+        let mut deps = initialize_with(InitMsg {
+            max_wallet_balance: Some(Uint128(100)),
+            ..Default::default()
+        });
+        deposit(&mut deps, "alice", 100);
+        deposit(&mut deps, "bob", 95);
+        let handle_msg = HandleMsg::SetRewardPool {
+            pool: HumanAddr::from("pool"),
+            max_reward_bps: 500,
+        };
+        handle(&mut deps, mock_env("creator", &[]), handle_msg).unwrap();
+
+        let transfer_with_reward = |value: u128, reward_bps: u16| HandleMsg::TransferWithReward {
+            to: HumanAddr::from("bob"),
+            value: Uint128(value),
+            reward_bps,
+        };
+        for reward_bps in vec![0, 500] {
+            match handle(
+                &mut deps,
+                mock_env("alice", &[]),
+                transfer_with_reward(20, reward_bps),
+            ) {
+                Ok(_) => panic!("should have failed"),
+                Err(e) => assert!(e.to_string().contains("can not exceed")),
+            }
         }
+        assert_eq!(100, balance_of(&deps, "alice"));
+        assert_eq!(95, balance_of(&deps, "bob"));
+
+        // net value within the cap
+        handle(
+            &mut deps,
+            mock_env("alice", &[]),
+            transfer_with_reward(5, 0),
+        )
+        .unwrap();
+        assert_eq!(100, balance_of(&deps, "bob"));
     }
 
     #[test]
-    fn handle_burn_from_invalid_founds() {
-        let mut deps = initialize();
-        let address = HumanAddr::from("address");
+    fn handle_transfer_guard_all_paths() {
+        let vetoed = |result: StdResult<HandleResponse>| match result {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("TransferVetoed")),
+        };
+        let at = |sender: &str, height: u64| {
+            let mut env = mock_env(sender, &[]);
+            env.block.height = height;
+            env
+        };
+
+        let mut deps = initialize_guarded(false);
+        let handle_msg = HandleMsg::ProposeLargeTransfer {
+            to: HumanAddr::from("carol"),
+            value: Uint128(5),
+        };
+        let res = handle(&mut deps, mock_env("alice", &[]), handle_msg).unwrap();
+        let pending_id = match from_binary(&res.data.unwrap()).unwrap() {
+            HandleResult::ProposeLargeTransfer { id, .. } => id,
+            _ => panic!("unexpected"),
+        };
+        let handle_msg = HandleMsg::QueueTransfer {
+            to: HumanAddr::from("carol"),
+            value: Uint128(5),
+        };
+        handle(&mut deps, mock_env("alice", &[]), handle_msg).unwrap();
+        let handle_msg = HandleMsg::CreateEscrow {
+            recipient: HumanAddr::from("carol"),
+            value: Uint128(5),
+            release_height: 20000,
+        };
+        let res = handle(&mut deps, mock_env("alice", &[]), handle_msg).unwrap();
+        let escrow_id = match from_binary(&res.data.unwrap()).unwrap() {
+            HandleResult::CreateEscrow { id, .. } => id,
+            _ => panic!("unexpected"),
+        };
         let handle_msg = HandleMsg::DepositTo {
-            to: address.clone(),
-            value: Uint128(0),
+            to: HumanAddr::from("dave"),
+            value: Uint128(5),
         };
-        match handle(&mut deps, mock_env("creator", &[]), handle_msg) {
-            Ok(_) => panic!("should have failed"),
-            Err(_) => {}
+        handle(&mut deps, mock_env("creator", &[]), handle_msg).unwrap();
+        let handle_msg = HandleMsg::SetGuardian {
+            guardian: Some(HumanAddr::from("guardian")),
+        };
+        handle(&mut deps, mock_env("dave", &[]), handle_msg).unwrap();
+        let handle_msg = HandleMsg::InitiateRecovery {
+            account: HumanAddr::from("dave"),
+            to: HumanAddr::from("erin"),
+        };
+        handle(&mut deps, mock_env("guardian", &[]), handle_msg).unwrap();
+
+        deps.querier.allowed = Some(false);
+        let handle_msg = HandleMsg::TransferWithReward {
+            to: HumanAddr::from("carol"),
+            value: Uint128(5),
+            reward_bps: 0,
+        };
+        vetoed(handle(&mut deps, mock_env("alice", &[]), handle_msg));
+        vetoed(handle(
+            &mut deps,
+            mock_env("alice", &[]),
+            HandleMsg::SettleTransfers {},
+        ));
+        let handle_msg = HandleMsg::ConfirmLargeTransfer { id: pending_id };
+        vetoed(handle(&mut deps, mock_env("alice", &[]), handle_msg));
+        let handle_msg = HandleMsg::CreateEscrow {
+            recipient: HumanAddr::from("carol"),
+            value: Uint128(5),
+            release_height: 20000,
+        };
+        vetoed(handle(&mut deps, mock_env("alice", &[]), handle_msg));
+        let handle_msg = HandleMsg::ReleaseEscrow { id: escrow_id };
+        vetoed(handle(&mut deps, at("carol", 20000), handle_msg));
+        let handle_msg = HandleMsg::RefundEscrow { id: escrow_id };
+        vetoed(handle(&mut deps, mock_env("alice", &[]), handle_msg));
+        let handle_msg = HandleMsg::SwapBalances {
+            a: HumanAddr::from("alice"),
+            b: HumanAddr::from("dave"),
+        };
+        vetoed(handle(&mut deps, mock_env("creator", &[]), handle_msg));
+        let handle_msg = HandleMsg::FinalizeRecovery {
+            account: HumanAddr::from("dave"),
+        };
+        vetoed(handle(
+            &mut deps,
+            at("guardian", 12345 + DEFAULT_RECOVERY_DELAY),
+            handle_msg,
+        ));
+
+        let balance_of = |address: &str| {
+            let address = deps
+                .api
+                .canonical_address(&HumanAddr::from(address))
+                .unwrap();
+            ReadOnlyBalances::from_storage(&deps.storage)
+                .balance(&address)
+                .u128()
+        };
+        assert_eq!(45, balance_of("alice"));
+        assert_eq!(0, balance_of("carol"));
+        assert_eq!(5, balance_of("dave"));
+        assert_eq!(0, balance_of("erin"));
+    }
+
+    #[test]
+    fn query_can_transfer_matches_transfer() {
+        let zero_address = "\0\0\0";
+        let mut deps = initialize_with(InitMsg {
+            reject_value_above_supply: true,
+            treat_zero_address_as_burn: true,
+            min_total_supply: Uint128(160),
+            max_wallet_balance: Some(Uint128(120)),
+            ..Default::default()
+        });
+        deposit(&mut deps, "alice", 100);
+        deposit(&mut deps, "carol", 100);
+        let handle_msg = HandleMsg::SetLargeTransferThreshold {
+            threshold: Some(Uint128(80)),
+        };
+        handle(&mut deps, mock_env("alice", &[]), handle_msg).unwrap();
+
+        let can_transfer =
+            |deps: &Extern<MockStorage, MockApi, MockQuerier>, to: &str, value: u128| {
+                let query_msg = QueryMsg::CanTransfer {
+                    from: HumanAddr::from("alice"),
+                    to: HumanAddr::from(to),
+                    value: Uint128(value),
+                    current_height: 12345,
+                    deadline: None,
+                };
+                match from_binary(&query(deps, query_msg).unwrap()).unwrap() {
+                    QueryResult::CanTransfer { reason, .. } => reason,
+                    _ => panic!("unexpected"),
+                }
+            };
+
+        let failing = vec![
+            // implausible amount
+            ("bob", 201, "ImplausibleAmount"),
+            // large transfer threshold
+            ("bob", 81, "large transfer threshold"),
+            // burn below the supply floor
+            (zero_address, 50, "BelowMinimumSupply"),
+            // recipient cap
+            ("carol", 21, "can not exceed"),
+        ];
+        for (to, value, reason) in failing {
+            assert!(can_transfer(&deps, to, value).unwrap().contains(reason));
+            match transfer_to(&mut deps, "alice", to, value) {
+                Ok(_) => panic!("should have failed"),
+                Err(e) => assert!(e.to_string().contains(reason)),
+            }
         }
 
-        // checking balance
-        let res = query(&deps, QueryMsg::BalanceOf { address }).unwrap();
-        match from_binary(&res).unwrap() {
-            QueryResult::BalanceOf { balance } => assert_eq!(0, balance.u128()),
-            _ => panic!("unexpected"),
+        assert_eq!(None, can_transfer(&deps, zero_address, 40));
+        transfer_to(&mut deps, "alice", zero_address, 40).unwrap();
+        assert_eq!(None, can_transfer(&deps, "carol", 20));
+        transfer_to(&mut deps, "alice", "carol", 20).unwrap();
+        assert_eq!(40, balance_of(&deps, "alice"));
+    }
+
+    #[test]
+    fn query_can_transfer_fallback_and_deadline() {
+        let mut deps = initialize_with(InitMsg {
+            max_wallet_balance: Some(Uint128(50)),
+            ..Default::default()
+        });
+        deposit(&mut deps, "alice", 100);
+        deposit(&mut deps, "escrow", 45);
+        let handle_msg = HandleMsg::SetReceivePolicy {
+            allowed_senders: Some(vec![HumanAddr::from("carol")]),
+        };
+        handle(&mut deps, mock_env("bob", &[]), handle_msg).unwrap();
+        let handle_msg = HandleMsg::SetFallbackRecipient {
+            fallback: Some(HumanAddr::from("escrow")),
+        };
+        handle(&mut deps, mock_env("bob", &[]), handle_msg).unwrap();
+
+        let can_transfer = |deps: &Extern<MockStorage, MockApi, MockQuerier>,
+                            value: u128,
+                            deadline: Option<u64>| {
+            let query_msg = QueryMsg::CanTransfer {
+                from: HumanAddr::from("alice"),
+                to: HumanAddr::from("bob"),
+                value: Uint128(value),
+                current_height: 12345,
+                deadline,
+            };
+            match from_binary(&query(deps, query_msg).unwrap()).unwrap() {
+                QueryResult::CanTransfer { reason, .. } => reason,
+                _ => panic!("unexpected"),
+            }
+        };
+        let transfer = |deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+                        value: u128,
+                        deadline: Option<u64>| {
+            let handle_msg = HandleMsg::Transfer {
+                to: HumanAddr::from("bob"),
+                value: Uint128(value),
+                log_note: None,
+                deadline,
+            };
+            handle(deps, mock_env("alice", &[]), handle_msg)
+        };
+
+        let failing = vec![
+            // redirected to the fallback, which is capped
+            (10, None, "can not exceed"),
+            // expired deadline
+            (5, Some(12344), "Expired"),
+        ];
+        for (value, deadline, reason) in failing {
+            assert!(can_transfer(&deps, value, deadline)
+                .unwrap()
+                .contains(reason));
+            match transfer(&mut deps, value, deadline) {
+                Ok(_) => panic!("should have failed"),
+                Err(e) => assert!(e.to_string().contains(reason)),
+            }
         }
 
-        // checking new total supply
-        let res = query(&deps, QueryMsg::TotalSupply {}).unwrap();
-        match from_binary(&res).unwrap() {
-            QueryResult::TotalSupply { total_supply } => assert_eq!(0, total_supply.u128()),
+        assert_eq!(None, can_transfer(&deps, 5, Some(12345)));
+        let res = transfer(&mut deps, 5, Some(12345)).unwrap();
+        assert_eq!(vec![log("redirected_to", "escrow")], res.log);
+        assert_eq!(50, balance_of(&deps, "escrow"));
+    }
+
+    #[test]
+    fn handle_create_escrow_checks() {
+        let mut deps = initialize_with(InitMsg {
+            reject_value_above_supply: true,
+            ..Default::default()
+        });
+        deposit(&mut deps, "alice", 100);
+        let handle_msg = HandleMsg::SetLargeTransferThreshold {
+            threshold: Some(Uint128(50)),
+        };
+        handle(&mut deps, mock_env("alice", &[]), handle_msg).unwrap();
+
+        let create_escrow = |value: u128, release_height: u64| HandleMsg::CreateEscrow {
+            recipient: HumanAddr::from("bob"),
+            value: Uint128(value),
+            release_height,
+        };
+
+        let failing = vec![
+            // release height already reached
+            (10, 12345, "must be in the future"),
+            (10, 12000, "must be in the future"),
+            // implausible amount
+            (101, 20000, "ImplausibleAmount"),
+            // large transfer threshold
+            (51, 20000, "large transfer threshold"),
+        ];
+        for (value, release_height, reason) in failing {
+            let handle_msg = create_escrow(value, release_height);
+            match handle(&mut deps, mock_env("alice", &[]), handle_msg) {
+                Ok(_) => panic!("should have failed"),
+                Err(e) => assert!(e.to_string().contains(reason)),
+            }
+        }
+        assert_eq!(100, balance_of(&deps, "alice"));
+
+        let handle_msg = create_escrow(50, 12346);
+        handle(&mut deps, mock_env("alice", &[]), handle_msg).unwrap();
+        assert_eq!(50, balance_of(&deps, "alice"));
+    }
+
+    #[test]
+    fn handle_escrow_rewards_and_release_policy() {
+        let mut deps = initialize();
+        deposit(&mut deps, "alice", 100);
+        deposit(&mut deps, "carol", 50);
+
+        let at = |sender: &str, height: u64| {
+            let mut env = mock_env(sender, &[]);
+            env.block.height = height;
+            env
+        };
+        let handle_msg = HandleMsg::CreateEscrow {
+            recipient: HumanAddr::from("bob"),
+            value: Uint128(50),
+            release_height: 200,
+        };
+        let res = handle(&mut deps, at("alice", 100), handle_msg).unwrap();
+        let id = match from_binary(&res.data.unwrap()).unwrap() {
+            HandleResult::CreateEscrow { id, .. } => id,
             _ => panic!("unexpected"),
+        };
+        assert_eq!(150, total_supply(&deps));
+
+        // escrowed tokens do not dilute rewards of the holders
+        distribute(&mut deps, "creator", 100).unwrap();
+        assert_eq!(50, pending_rewards_of(&deps, "alice"));
+        assert_eq!(50, pending_rewards_of(&deps, "carol"));
+
+        // the receive policy is checked when the escrow is released
+        let handle_msg = HandleMsg::SetReceivePolicy {
+            allowed_senders: Some(vec![HumanAddr::from("carol")]),
+        };
+        handle(&mut deps, mock_env("bob", &[]), handle_msg).unwrap();
+        let handle_msg = HandleMsg::ReleaseEscrow { id };
+        match handle(&mut deps, at("bob", 200), handle_msg) {
+            Ok(_) => panic!("should have failed"),
+            Err(e) => assert!(e.to_string().contains("RecipientRefused")),
         }
+        assert_eq!(0, balance_of(&deps, "bob"));
+        assert_eq!(50, balance_of(&deps, "alice"));
+
+        let handle_msg = HandleMsg::SetReceivePolicy {
+            allowed_senders: None,
+        };
+        handle(&mut deps, mock_env("bob", &[]), handle_msg).unwrap();
+        let handle_msg = HandleMsg::ReleaseEscrow { id };
+        handle(&mut deps, at("bob", 200), handle_msg).unwrap();
+        assert_eq!(50, balance_of(&deps, "bob"));
+        assert_eq!(50, balance_of(&deps, "alice"));
+        assert_eq!(0, pending_rewards_of(&deps, "bob"));
+        assert_eq!(50, pending_rewards_of(&deps, "alice"));
+
+        // every token is eligible again once the escrow is released
+        distribute(&mut deps, "creator", 150).unwrap();
+        assert_eq!(50, pending_rewards_of(&deps, "bob"));
+        assert_eq!(100, pending_rewards_of(&deps, "alice"));
     }
 }
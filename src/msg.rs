@@ -1,12 +1,114 @@
-use cosmwasm_std::{HumanAddr, Uint128};
+use cosmwasm_std::{Binary, Coin, HumanAddr, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default, JsonSchema)]
 pub struct InitMsg {
     pub name: String,
     pub symbol: String,
     pub decimals: u8,
+    /// Identifier clients use to verify the token they talk to
+    pub token_id: String,
+    pub reserve: Option<ReserveConfig>,
+    /// Include amounts in the response logs
+    #[serde(default)]
+    pub verbose_logging: bool,
+    /// Number of blocks between initiating and finalizing account recovery
+    pub recovery_delay: Option<u64>,
+    /// Store symbol in upper case, true by default
+    pub normalize_symbol: Option<bool>,
+    /// Maximum number of actions in batch handlers
+    pub max_batch_size: Option<u32>,
+    /// Reject transfers and approvals of more tokens than total supply
+    #[serde(default)]
+    pub reject_value_above_supply: bool,
+    /// Maximum balance of accounts other than the owner and reward pool
+    pub max_wallet_balance: Option<Uint128>,
+    /// Burn tokens transferred to the all-zeros address instead of crediting it
+    #[serde(default)]
+    pub treat_zero_address_as_burn: bool,
+    /// Permanently forbid metadata changes
+    #[serde(default)]
+    pub metadata_frozen: bool,
+    /// Accept transfers only to accounts that registered with RegisterAccount
+    #[serde(default)]
+    pub require_registration: bool,
+    /// Number of blocks approvals without expiration stay valid for
+    pub default_allowance_expiry: Option<u64>,
+    /// Number of blocks accounts must hold tokens after the first credit
+    /// before they can transfer them
+    pub min_holding_period: Option<u64>,
+    /// Treat approvals of zero tokens as revoking the allowance
+    #[serde(default)]
+    pub zero_approve_revokes: bool,
+    /// Transfer the whole allowance when TransferFrom value exceeds it
+    #[serde(default)]
+    pub clamp_transfer_from: bool,
+    /// Burn the remaining balance too when a burn leaves less than `dust_threshold`
+    #[serde(default)]
+    pub burn_sweeps_dust: bool,
+    pub dust_threshold: Option<Uint128>,
+    /// Contract queried for the token price by PricedValue
+    pub price_oracle: Option<PriceOracleInfo>,
+    /// Burns can not take total supply below it, zero disables the floor
+    #[serde(default)]
+    pub min_total_supply: Uint128,
+    /// Log a global sequence number in every state changing handle response
+    #[serde(default)]
+    pub emit_sequence: bool,
+    /// Allowances set at init, e.g. for a router
+    #[serde(default)]
+    pub initial_allowances: Vec<InitialAllowance>,
+    /// Token contract whose reserved symbols are rejected too, e.g. of a token factory
+    pub symbol_registry: Option<SymbolRegistryInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SymbolRegistryInfo {
+    pub address: HumanAddr,
+    pub code_hash: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InitialAllowance {
+    pub owner: HumanAddr,
+    pub spender: HumanAddr,
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PriceOracleInfo {
+    pub address: HumanAddr,
+    pub code_hash: String,
+}
+
+/// Query sent to the price oracle contract.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum OracleQueryMsg {
+    Price {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PriceResponse {
+    /// Price of one whole token in the smallest units of the quote currency
+    pub price: Uint128,
+}
+
+/// Version of the message schema the contract speaks. Minor versions
+/// only add messages or optional fields, major versions break clients.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+pub struct SchemaVersion {
+    pub major: u16,
+    pub minor: u16,
+}
+
+/// Native reserves backing the token.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReserveConfig {
+    pub denom: String,
+    /// Amount of native reserves required per token in basis points
+    pub backing_ratio_bps: u32,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -23,20 +125,335 @@ pub enum HandleMsg {
     Transfer {
         to: HumanAddr,
         value: Uint128,
+        /// Note written only to the response log, never stored
+        log_note: Option<String>,
+        /// Height after which transfer can not be executed
+        deadline: Option<u64>,
     },
     TransferFrom {
         from: HumanAddr,
         to: HumanAddr,
         value: Uint128,
+        /// Height after which transfer can not be executed
+        deadline: Option<u64>,
     },
     Approve {
         spender: HumanAddr,
         value: Uint128,
+        /// Height after which allowance can not be used
+        expiration: Option<u64>,
     },
     Allowance {
         owner: HumanAddr,
         spender: HumanAddr,
     },
+    SetDisabledHandlers {
+        handlers: Vec<HandlerId>,
+    },
+    BatchBurn {
+        actions: Vec<BurnAction>,
+    },
+    ReassignAllowance {
+        from_spender: HumanAddr,
+        to_spender: HumanAddr,
+        value: Uint128,
+    },
+    SetRewardPool {
+        pool: HumanAddr,
+        max_reward_bps: u16,
+    },
+    TransferWithReward {
+        to: HumanAddr,
+        value: Uint128,
+        reward_bps: u16,
+    },
+    SetGuardian {
+        guardian: Option<HumanAddr>,
+    },
+    InitiateRecovery {
+        account: HumanAddr,
+        to: HumanAddr,
+    },
+    CancelRecovery {},
+    FinalizeRecovery {
+        account: HumanAddr,
+    },
+    SetMinters {
+        minters: Vec<MinterInfo>,
+    },
+    SetLargeTransferThreshold {
+        threshold: Option<Uint128>,
+    },
+    ProposeLargeTransfer {
+        to: HumanAddr,
+        value: Uint128,
+    },
+    ConfirmLargeTransfer {
+        id: u64,
+    },
+    SetReceivePolicy {
+        /// Senders allowed to transfer to the account, `None` accepts everyone
+        allowed_senders: Option<Vec<HumanAddr>>,
+    },
+    #[cfg(feature = "debug")]
+    DebugSetBalance {
+        address: HumanAddr,
+        value: Uint128,
+    },
+    ApproveBurn {
+        spender: HumanAddr,
+        value: Uint128,
+        /// Height after which burn allowance can not be used
+        expiration: Option<u64>,
+    },
+    /// Irreversibly removes the contract owner, `confirm` must equal token id
+    RenounceOwnership {
+        confirm: String,
+    },
+    QueueTransfer {
+        to: HumanAddr,
+        value: Uint128,
+    },
+    SettleTransfers {},
+    SetMetadata {
+        /// Link to off-chain metadata document, `None` removes it
+        metadata_uri: Option<String>,
+    },
+    AdjustAllowance {
+        spender: HumanAddr,
+        /// Signed decimal amount, negative values decrease allowance down to zero
+        delta: String,
+    },
+    SetTransferGuard {
+        /// Contract queried before each transfer, `None` removes it
+        guard: Option<TransferGuardInfo>,
+    },
+    /// Accounts `amount` of rewards to holders proportionally to their balances
+    DistributeRewards {
+        amount: Uint128,
+    },
+    /// Exchanges balances of two accounts without changing total supply.
+    /// Registration and the maximum wallet balance apply, receive policies do not
+    SwapBalances {
+        a: HumanAddr,
+        b: HumanAddr,
+    },
+    RegisterAccount {},
+    SetMintAdmin {
+        /// Account allowed to set minters besides the owner, `None` removes it
+        mint_admin: Option<HumanAddr>,
+    },
+    SetDefaultAllowanceExpiry {
+        /// Number of blocks sender approvals without expiration stay valid for,
+        /// `None` falls back to the contract default
+        blocks: Option<u64>,
+    },
+    /// Burns whole balances of `accounts` and sends them native reserves
+    /// at the backing ratio, used to wind down the token
+    ForceRedeemAll {
+        accounts: Vec<HumanAddr>,
+    },
+    /// Registers `code` minting `amount` to the first account claiming it,
+    /// only the code hash is stored
+    RegisterClaimCode {
+        code: String,
+        amount: Uint128,
+    },
+    ClaimWithCode {
+        code: String,
+    },
+    /// Blocks outgoing transfers and approvals of the sender until it unlocks
+    LockMyAccount {},
+    UnlockMyAccount {},
+    SetFallbackRecipient {
+        /// Account receiving transfers the sender refuses, `None` removes it
+        fallback: Option<HumanAddr>,
+    },
+    /// Deposits `mint_value` to `to` and sets allowance of `spender` from `to`
+    /// to `approve_value`, e.g. for vesting contracts
+    MintAndApprove {
+        to: HumanAddr,
+        mint_value: Uint128,
+        spender: HumanAddr,
+        approve_value: Uint128,
+    },
+    /// Moves `value` from the sender into escrow held by the contract
+    /// until `release_height`
+    CreateEscrow {
+        recipient: HumanAddr,
+        value: Uint128,
+        release_height: u64,
+    },
+    /// Credits the escrow to its recipient, from the release height on
+    ReleaseEscrow {
+        id: u64,
+    },
+    /// Returns the escrow to its sender, before the release height only
+    RefundEscrow {
+        id: u64,
+    },
+    /// Tags the account for BalanceByTag, `None` removes the tag. Owner only
+    SetAccountTag {
+        account: HumanAddr,
+        tag: Option<String>,
+    },
+    /// Adds symbols rejected at init of tokens using this contract
+    /// as their symbol registry. Owner only
+    ReserveSymbols {
+        symbols: Vec<String>,
+    },
+    /// Sets the key owner only queries must carry, only its hash is stored
+    SetOwnerQueryKey {
+        key: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TransferGuardInfo {
+    pub address: HumanAddr,
+    pub code_hash: String,
+    /// Allow transfers if the guard query fails
+    pub fail_open: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RewardPoolInfo {
+    pub pool: HumanAddr,
+    pub max_reward_bps: u16,
+}
+
+/// Query sent to the transfer guard contract.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum GuardQueryMsg {
+    CanTransfer {
+        from: HumanAddr,
+        to: HumanAddr,
+        value: Uint128,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GuardResponse {
+    pub allowed: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BurnAction {
+    pub owner: HumanAddr,
+    pub value: Uint128,
+}
+
+impl HandleMsg {
+    /// Returns id used to check if handler is disabled.
+    /// `None` for handlers that can not be disabled.
+    pub fn handler_id(&self) -> Option<HandlerId> {
+        match self {
+            HandleMsg::DepositTo { .. } => Some(HandlerId::DepositTo),
+            HandleMsg::BurnFrom { .. } => Some(HandlerId::BurnFrom),
+            HandleMsg::Transfer { .. } => Some(HandlerId::Transfer),
+            HandleMsg::TransferFrom { .. } => Some(HandlerId::TransferFrom),
+            HandleMsg::Approve { .. } => Some(HandlerId::Approve),
+            HandleMsg::Allowance { .. } => Some(HandlerId::Allowance),
+            HandleMsg::BatchBurn { .. } => Some(HandlerId::BatchBurn),
+            HandleMsg::ReassignAllowance { .. } => Some(HandlerId::ReassignAllowance),
+            HandleMsg::SetRewardPool { .. } => Some(HandlerId::SetRewardPool),
+            HandleMsg::TransferWithReward { .. } => Some(HandlerId::TransferWithReward),
+            HandleMsg::SetGuardian { .. } => Some(HandlerId::SetGuardian),
+            HandleMsg::InitiateRecovery { .. } => Some(HandlerId::InitiateRecovery),
+            HandleMsg::CancelRecovery { .. } => Some(HandlerId::CancelRecovery),
+            HandleMsg::FinalizeRecovery { .. } => Some(HandlerId::FinalizeRecovery),
+            HandleMsg::SetMinters { .. } => Some(HandlerId::SetMinters),
+            HandleMsg::SetLargeTransferThreshold { .. } => {
+                Some(HandlerId::SetLargeTransferThreshold)
+            }
+            HandleMsg::ProposeLargeTransfer { .. } => Some(HandlerId::ProposeLargeTransfer),
+            HandleMsg::ConfirmLargeTransfer { .. } => Some(HandlerId::ConfirmLargeTransfer),
+            HandleMsg::SetReceivePolicy { .. } => Some(HandlerId::SetReceivePolicy),
+            HandleMsg::ApproveBurn { .. } => Some(HandlerId::ApproveBurn),
+            HandleMsg::QueueTransfer { .. } => Some(HandlerId::QueueTransfer),
+            HandleMsg::SettleTransfers { .. } => Some(HandlerId::SettleTransfers),
+            HandleMsg::SetMetadata { .. } => Some(HandlerId::SetMetadata),
+            HandleMsg::AdjustAllowance { .. } => Some(HandlerId::AdjustAllowance),
+            HandleMsg::SetTransferGuard { .. } => Some(HandlerId::SetTransferGuard),
+            HandleMsg::DistributeRewards { .. } => Some(HandlerId::DistributeRewards),
+            HandleMsg::SwapBalances { .. } => Some(HandlerId::SwapBalances),
+            HandleMsg::RegisterAccount { .. } => Some(HandlerId::RegisterAccount),
+            HandleMsg::SetMintAdmin { .. } => Some(HandlerId::SetMintAdmin),
+            HandleMsg::SetDefaultAllowanceExpiry { .. } => {
+                Some(HandlerId::SetDefaultAllowanceExpiry)
+            }
+            HandleMsg::ForceRedeemAll { .. } => Some(HandlerId::ForceRedeemAll),
+            HandleMsg::RegisterClaimCode { .. } => Some(HandlerId::RegisterClaimCode),
+            HandleMsg::ClaimWithCode { .. } => Some(HandlerId::ClaimWithCode),
+            HandleMsg::LockMyAccount { .. } => Some(HandlerId::LockMyAccount),
+            HandleMsg::UnlockMyAccount { .. } => Some(HandlerId::UnlockMyAccount),
+            HandleMsg::SetFallbackRecipient { .. } => Some(HandlerId::SetFallbackRecipient),
+            HandleMsg::MintAndApprove { .. } => Some(HandlerId::MintAndApprove),
+            HandleMsg::CreateEscrow { .. } => Some(HandlerId::CreateEscrow),
+            HandleMsg::ReleaseEscrow { .. } => Some(HandlerId::ReleaseEscrow),
+            HandleMsg::RefundEscrow { .. } => Some(HandlerId::RefundEscrow),
+            HandleMsg::SetAccountTag { .. } => Some(HandlerId::SetAccountTag),
+            HandleMsg::ReserveSymbols { .. } => Some(HandlerId::ReserveSymbols),
+            HandleMsg::SetOwnerQueryKey { .. } => None,
+            HandleMsg::SetDisabledHandlers { .. } => None,
+            HandleMsg::RenounceOwnership { .. } => None,
+            #[cfg(feature = "debug")]
+            HandleMsg::DebugSetBalance { .. } => None,
+        }
+    }
+
+    /// Returns true for handlers that do not change state.
+    pub fn is_read_only(&self) -> bool {
+        matches!(self, HandleMsg::Allowance { .. })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum HandlerId {
+    DepositTo,
+    BurnFrom,
+    Transfer,
+    TransferFrom,
+    Approve,
+    Allowance,
+    BatchBurn,
+    ReassignAllowance,
+    SetRewardPool,
+    TransferWithReward,
+    SetGuardian,
+    InitiateRecovery,
+    CancelRecovery,
+    FinalizeRecovery,
+    SetMinters,
+    SetLargeTransferThreshold,
+    ProposeLargeTransfer,
+    ConfirmLargeTransfer,
+    SetReceivePolicy,
+    ApproveBurn,
+    QueueTransfer,
+    SettleTransfers,
+    SetMetadata,
+    AdjustAllowance,
+    SetTransferGuard,
+    DistributeRewards,
+    SwapBalances,
+    RegisterAccount,
+    SetMintAdmin,
+    SetDefaultAllowanceExpiry,
+    ForceRedeemAll,
+    RegisterClaimCode,
+    ClaimWithCode,
+    LockMyAccount,
+    UnlockMyAccount,
+    SetFallbackRecipient,
+    MintAndApprove,
+    CreateEscrow,
+    ReleaseEscrow,
+    RefundEscrow,
+    SetAccountTag,
+    ReserveSymbols,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -57,9 +474,13 @@ pub enum HandleResult {
     },
     Transfer {
         status: Status,
+        block_height: u64,
+        tx_index: u64,
     },
     TransferFrom {
         status: Status,
+        block_height: u64,
+        tx_index: u64,
     },
     Approve {
         status: Status,
@@ -69,6 +490,132 @@ pub enum HandleResult {
         spender: HumanAddr,
         value: Uint128,
     },
+    SetDisabledHandlers {
+        status: Status,
+    },
+    BatchBurn {
+        status: Status,
+    },
+    ReassignAllowance {
+        status: Status,
+    },
+    SetRewardPool {
+        status: Status,
+    },
+    TransferWithReward {
+        status: Status,
+        reward: Uint128,
+    },
+    SetGuardian {
+        status: Status,
+    },
+    InitiateRecovery {
+        status: Status,
+    },
+    CancelRecovery {
+        status: Status,
+    },
+    FinalizeRecovery {
+        status: Status,
+    },
+    SetMinters {
+        status: Status,
+    },
+    SetLargeTransferThreshold {
+        status: Status,
+    },
+    ProposeLargeTransfer {
+        status: Status,
+        id: u64,
+    },
+    ConfirmLargeTransfer {
+        status: Status,
+    },
+    SetReceivePolicy {
+        status: Status,
+    },
+    #[cfg(feature = "debug")]
+    DebugSetBalance {
+        status: Status,
+    },
+    ApproveBurn {
+        status: Status,
+    },
+    RenounceOwnership {
+        status: Status,
+    },
+    QueueTransfer {
+        status: Status,
+    },
+    SettleTransfers {
+        status: Status,
+    },
+    SetMetadata {
+        status: Status,
+    },
+    AdjustAllowance {
+        status: Status,
+    },
+    SetTransferGuard {
+        status: Status,
+    },
+    DistributeRewards {
+        status: Status,
+    },
+    SwapBalances {
+        status: Status,
+    },
+    RegisterAccount {
+        status: Status,
+    },
+    SetMintAdmin {
+        status: Status,
+    },
+    SetDefaultAllowanceExpiry {
+        status: Status,
+    },
+    ForceRedeemAll {
+        status: Status,
+        redeemed: Uint128,
+    },
+    RegisterClaimCode {
+        status: Status,
+    },
+    ClaimWithCode {
+        status: Status,
+        amount: Uint128,
+    },
+    LockMyAccount {
+        status: Status,
+    },
+    UnlockMyAccount {
+        status: Status,
+    },
+    SetFallbackRecipient {
+        status: Status,
+    },
+    MintAndApprove {
+        status: Status,
+    },
+    CreateEscrow {
+        status: Status,
+        id: u64,
+    },
+    ReleaseEscrow {
+        status: Status,
+    },
+    RefundEscrow {
+        status: Status,
+    },
+    SetAccountTag {
+        status: Status,
+    },
+    ReserveSymbols {
+        status: Status,
+    },
+    SetOwnerQueryKey {
+        status: Status,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -77,17 +624,287 @@ pub enum QueryMsg {
     Name {},
     Symbol {},
     Decimals {},
-    BalanceOf { address: HumanAddr },
+    BalanceOf {
+        address: HumanAddr,
+    },
+    BalanceDetail {
+        address: HumanAddr,
+    },
     TotalSupply {},
+    ConvertAmount {
+        amount: Uint128,
+        target_decimals: u8,
+        rounding: Rounding,
+    },
+    /// Allowances expired at `current_height` are reported as zero
+    AllowancesBatch {
+        pairs: Vec<AllowancePair>,
+        current_height: u64,
+    },
+    TokenId {},
+    MetadataHash {},
+    /// Owner only
+    RawStorage {
+        key: String,
+        owner_key: String,
+    },
+    Tips {},
+    /// Allowance expired at `current_height` can not be pulled
+    PullableAmount {
+        owner: HumanAddr,
+        spender: HumanAddr,
+        current_height: u64,
+    },
+    SupportedFeatures {},
+    MetadataUri {},
+    CreationInfo {},
+    SupplyStats {},
+    PendingRewards {
+        address: HumanAddr,
+    },
+    /// Sum of allowances granted by `owner` not expired at `current_height`.
+    /// Owner only
+    TotalApprovedBy {
+        owner: HumanAddr,
+        current_height: u64,
+        owner_key: String,
+    },
+    MintAdmin {},
+    /// Allowance expired at `current_height` is reported as zero
+    AllowanceDetail {
+        owner: HumanAddr,
+        spender: HumanAddr,
+        current_height: u64,
+    },
+    /// Allowances expired at `current_height`, scanned in creation order
+    ExpiredAllowances {
+        current_height: u64,
+        /// Index of the last allowance scanned by the previous page
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Checks whether Transfer of `value` from `from` to `to` would succeed
+    /// at `current_height` without executing it
+    CanTransfer {
+        from: HumanAddr,
+        to: HumanAddr,
+        value: Uint128,
+        current_height: u64,
+        deadline: Option<u64>,
+    },
+    Ownership {},
+    FormattedBalance {
+        address: HumanAddr,
+        /// Number of fractional digits to show, truncating the rest,
+        /// all token decimals by default
+        display_decimals: Option<u8>,
+    },
+    /// Minters sorted by address
+    MintersDetail {
+        start_after: Option<HumanAddr>,
+        limit: Option<u32>,
+    },
+    /// Balance of the account valued at the price oracle price
+    PricedValue {
+        address: HumanAddr,
+    },
+    SchemaVersion {},
+    /// Sum of balances of accounts tagged with `tag`. Owner only
+    BalanceByTag {
+        tag: String,
+        owner_key: String,
+    },
+    Parameters {},
+    /// Whether init rejects `symbol` for tokens using this contract as their symbol registry
+    IsReservedSymbol {
+        symbol: String,
+    },
+}
+
+/// Every tunable parameter of the contract, for schema based clients.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Parameters {
+    pub decimals: u8,
+    pub reserve: Option<ReserveConfig>,
+    pub verbose_logging: bool,
+    pub recovery_delay: u64,
+    pub max_batch_size: u32,
+    pub reject_value_above_supply: bool,
+    pub max_wallet_balance: Option<Uint128>,
+    pub treat_zero_address_as_burn: bool,
+    pub metadata_frozen: bool,
+    pub require_registration: bool,
+    pub default_allowance_expiry: Option<u64>,
+    pub min_holding_period: Option<u64>,
+    pub zero_approve_revokes: bool,
+    pub clamp_transfer_from: bool,
+    pub dust_threshold: Option<Uint128>,
+    pub min_total_supply: Uint128,
+    pub emit_sequence: bool,
+    pub price_oracle: Option<PriceOracleInfo>,
+    pub transfer_guard: Option<TransferGuardInfo>,
+    pub reward_pool: Option<RewardPoolInfo>,
+    pub metadata_uri: Option<String>,
+    pub mint_admin: Option<HumanAddr>,
+    pub disabled_handlers: Vec<HandlerId>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MinterInfo {
+    pub address: HumanAddr,
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllowancePair {
+    pub owner: HumanAddr,
+    pub spender: HumanAddr,
+}
+
+/// Token amount along with its decimal representation.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FormattedAmount {
+    pub amount: Uint128,
+    pub formatted: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Rounding {
+    Down,
+    Up,
 }
 
 #[derive(Serialize, Deserialize, Debug, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum QueryResult {
-    Name { name: String },
-    Symbol { symbol: String },
-    Decimals { decimals: u8 },
-    BalanceOf { balance: Uint128 },
-    TotalSupply { total_supply: Uint128 },
-    Owner { owner: String },
+    Name {
+        name: String,
+    },
+    Symbol {
+        symbol: String,
+    },
+    Decimals {
+        decimals: u8,
+    },
+    BalanceOf {
+        balance: Uint128,
+    },
+    BalanceDetail {
+        balance: Uint128,
+        ever_held: bool,
+    },
+    TotalSupply {
+        total_supply: Uint128,
+    },
+    ConvertAmount {
+        amount: Uint128,
+        precision_lost: bool,
+    },
+    Owner {
+        owner: String,
+    },
+    AllowancesBatch {
+        allowances: Vec<Uint128>,
+        /// Expiration heights in the order of `allowances`
+        expirations: Vec<Option<u64>>,
+    },
+    TokenId {
+        token_id: String,
+    },
+    MetadataHash {
+        hash: String,
+    },
+    RawStorage {
+        data: Binary,
+    },
+    Tips {
+        tips: Vec<Coin>,
+    },
+    PullableAmount {
+        pullable: Uint128,
+        balance: Uint128,
+        allowance: Uint128,
+        expiration: Option<u64>,
+    },
+    SupportedFeatures {
+        reserve: bool,
+        minters: bool,
+        reward_pool: bool,
+        verbose_logging: bool,
+        disabled_handlers: bool,
+        debug: bool,
+        transfer_guard: bool,
+        price_oracle: bool,
+        max_wallet_balance: bool,
+        require_registration: bool,
+        min_holding_period: bool,
+        sequence_logging: bool,
+    },
+    MetadataUri {
+        metadata_uri: Option<String>,
+    },
+    CreationInfo {
+        height: u64,
+        time: u64,
+    },
+    SupplyStats {
+        total_supply: FormattedAmount,
+        minted: FormattedAmount,
+        burned: FormattedAmount,
+    },
+    PendingRewards {
+        rewards: Uint128,
+    },
+    MintAdmin {
+        mint_admin: Option<HumanAddr>,
+    },
+    AllowanceDetail {
+        allowance: Uint128,
+        balance: Uint128,
+        /// Allowance to balance ratio in basis points, 10000 if allowance exceeds balance
+        ratio_bps: u16,
+    },
+    TotalApprovedBy {
+        total: Uint128,
+    },
+    ExpiredAllowances {
+        allowances: Vec<AllowancePair>,
+        /// `start_after` of the next page, `None` if all allowances were scanned
+        next: Option<u64>,
+    },
+    CanTransfer {
+        allowed: bool,
+        /// Error the transfer would fail with, `None` if allowed
+        reason: Option<String>,
+    },
+    Ownership {
+        /// `None` if ownership was renounced
+        owner: Option<HumanAddr>,
+        renounced: bool,
+    },
+    FormattedBalance {
+        balance: FormattedAmount,
+    },
+    MintersDetail {
+        minters: Vec<MinterInfo>,
+    },
+    PricedValue {
+        balance: Uint128,
+        price: Uint128,
+        value: Uint128,
+    },
+    SchemaVersion {
+        version: SchemaVersion,
+    },
+    BalanceByTag {
+        accounts: u32,
+        balance: Uint128,
+    },
+    Parameters {
+        parameters: Parameters,
+    },
+    IsReservedSymbol {
+        reserved: bool,
+    },
 }
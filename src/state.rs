@@ -1,29 +1,230 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{CanonicalAddr, ReadonlyStorage, StdError, StdResult, Storage};
+use cosmwasm_std::{
+    from_slice, CanonicalAddr, Coin, ReadonlyStorage, StdError, StdResult, Storage, Uint128,
+};
 use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
 
+use crate::msg::{HandlerId, ReserveConfig, SchemaVersion};
+
 use serde::de::DeserializeOwned;
 use std::any::type_name;
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
 
-pub const NAMESPACE_STORAGE: &[u8] = b"config";
+/// Version of the storage layout, prepended to every namespace.
+pub const STORAGE_VERSION: u8 = 1;
+
+/// Prepends `STORAGE_VERSION` to the namespace name at compile time.
+macro_rules! versioned {
+    ($name:literal) => {{
+        const NAME: &[u8] = $name.as_bytes();
+        const NAMESPACE: [u8; NAME.len() + 1] = {
+            let mut namespace = [STORAGE_VERSION; NAME.len() + 1];
+            let mut i = 0;
+            while i < NAME.len() {
+                namespace[i + 1] = NAME[i];
+                i += 1;
+            }
+            namespace
+        };
+        &NAMESPACE
+    }};
+}
+
+/// Namespaces of the contract storage. Every namespace starts with the
+/// storage layout version, so data in a future format can be written next
+/// to the current one while it is migrated.
+pub struct StorageKeys;
+
+impl StorageKeys {
+    pub const CONFIG: &'static [u8] = versioned!("config");
+    pub const BALANCES: &'static [u8] = versioned!("balances");
+    pub const ALLOWANCES: &'static [u8] = versioned!("allowancws");
+    pub const TX_COUNTS: &'static [u8] = versioned!("tx_counts");
+    pub const SEEN: &'static [u8] = versioned!("seen");
+    pub const GUARDIANS: &'static [u8] = versioned!("guardians");
+    pub const RECOVERIES: &'static [u8] = versioned!("recoveries");
+    pub const LARGE_TRANSFER_THRESHOLDS: &'static [u8] = versioned!("large_transfer_thresholds");
+    pub const PENDING_TRANSFERS: &'static [u8] = versioned!("pending_transfers");
+    pub const PENDING_TRANSFER_IDS: &'static [u8] = versioned!("pending_transfer_ids");
+    pub const RECEIVE_OPT: &'static [u8] = versioned!("receive_opt");
+    pub const BURN_ALLOWANCES: &'static [u8] = versioned!("burn_allowances");
+    pub const QUEUED_TRANSFERS: &'static [u8] = versioned!("queued_transfers");
+    pub const ALLOWANCE_INDEX: &'static [u8] = versioned!("allowance_index");
+    pub const REWARD_DEBTS: &'static [u8] = versioned!("reward_debts");
+    pub const REGISTERED: &'static [u8] = versioned!("registered");
+    pub const ALLOWANCE_SPENDERS: &'static [u8] = versioned!("allowance_spenders");
+    pub const ALLOWANCE_EXPIRY_DEFAULTS: &'static [u8] = versioned!("allowance_expiry_defaults");
+    pub const FIRST_RECEIVED: &'static [u8] = versioned!("first_received");
+    pub const CLAIM_CODES: &'static [u8] = versioned!("claim_codes");
+    pub const LOCKED: &'static [u8] = versioned!("locked");
+    pub const FALLBACK_RECIPIENTS: &'static [u8] = versioned!("fallback_recipients");
+    pub const ESCROWS: &'static [u8] = versioned!("escrows");
+    pub const ACCOUNT_TAGS: &'static [u8] = versioned!("account_tags");
+    pub const TAG_ACCOUNTS: &'static [u8] = versioned!("tag_accounts");
+    pub const ALLOWANCE_POSITIONS: &'static [u8] = versioned!("allowance_positions");
+
+    /// Namespaces written before the storage layout was versioned.
+    /// Keys missing from their versioned successors are read from them.
+    pub const LEGACY_CONFIG: &'static [u8] = b"config";
+    pub const LEGACY_BALANCES: &'static [u8] = b"balances";
+    pub const LEGACY_ALLOWANCES: &'static [u8] = b"allowancws";
+}
+
+/// Namespace reading keys it does not have from its legacy predecessor.
+/// Writes only go to the versioned namespace, so they take precedence
+/// over the legacy data from then on.
+pub struct LegacyFallback<T> {
+    storage: T,
+    namespace: &'static [u8],
+    legacy: &'static [u8],
+}
+
+impl<T> LegacyFallback<T> {
+    fn new(storage: T, namespace: &'static [u8], legacy: &'static [u8]) -> Self {
+        Self {
+            storage,
+            namespace,
+            legacy,
+        }
+    }
+}
+
+fn get_with_legacy<S: ReadonlyStorage>(
+    storage: &S,
+    namespace: &[u8],
+    legacy: &[u8],
+    key: &[u8],
+) -> Option<Vec<u8>> {
+    ReadonlyPrefixedStorage::new(namespace, storage)
+        .get(key)
+        .or_else(|| ReadonlyPrefixedStorage::new(legacy, storage).get(key))
+}
+
+impl<'a, S: ReadonlyStorage> ReadonlyStorage for LegacyFallback<&'a S> {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        get_with_legacy(self.storage, self.namespace, self.legacy, key)
+    }
+}
+
+impl<'a, S: Storage> ReadonlyStorage for LegacyFallback<&'a mut S> {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        get_with_legacy(&*self.storage, self.namespace, self.legacy, key)
+    }
+}
+
+impl<'a, S: Storage> Storage for LegacyFallback<&'a mut S> {
+    fn set(&mut self, key: &[u8], value: &[u8]) {
+        PrefixedStorage::new(self.namespace, &mut *self.storage).set(key, value);
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        PrefixedStorage::new(self.namespace, &mut *self.storage).remove(key);
+        PrefixedStorage::new(self.legacy, &mut *self.storage).remove(key);
+    }
+}
+
 pub static KEY_CONSTANTS: &[u8] = b"constants";
 pub static KEY_TOTAL_SUPPLY: &[u8] = b"total_supply";
-
-pub const NAMESPACE_BALANCES: &[u8] = b"balances";
-pub const NAMESPACE_ALLOWANCES: &[u8] = b"allowancws";
+pub static KEY_TOTAL_MINTED: &[u8] = b"total_minted";
+pub static KEY_TOTAL_BURNED: &[u8] = b"total_burned";
+pub static KEY_DISABLED_HANDLERS: &[u8] = b"disabled_handlers";
+pub static KEY_RESERVED_SYMBOLS: &[u8] = b"reserved_symbols";
+pub static KEY_OWNER_QUERY_KEY: &[u8] = b"owner_query_key";
+pub static KEY_REWARD_POOL: &[u8] = b"reward_pool";
+pub static KEY_MINTERS: &[u8] = b"minters";
+pub static KEY_TIPS: &[u8] = b"tips";
+pub static KEY_ALLOWANCE_COUNT: &[u8] = b"allowance_count";
+pub static KEY_TRANSFER_GUARD: &[u8] = b"transfer_guard";
+pub static KEY_SCHEMA_VERSION: &[u8] = b"schema_version";
+pub static KEY_REWARD_PER_TOKEN: &[u8] = b"reward_per_token";
+pub static KEY_ESCROW_COUNT: &[u8] = b"escrow_count";
+pub static KEY_TOTAL_ESCROWED: &[u8] = b"total_escrowed";
+pub static KEY_SEQUENCE: &[u8] = b"sequence";
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Constants {
     pub name: String,
     pub symbol: String,
     pub decimals: u8,
+    pub token_id: String,
     pub owner: CanonicalAddr,
+    pub contract_address: CanonicalAddr,
+    pub reserve: Option<ReserveConfig>,
+    pub verbose_logging: bool,
+    pub recovery_delay: u64,
+    pub max_batch_size: u32,
+    pub metadata_uri: Option<String>,
+    pub reject_value_above_supply: bool,
+    pub created_height: u64,
+    pub created_time: u64,
+    pub max_wallet_balance: Option<TokenAmount>,
+    pub treat_zero_address_as_burn: bool,
+    pub metadata_frozen: bool,
+    pub require_registration: bool,
+    /// Account managing minters besides the owner
+    pub mint_admin: Option<CanonicalAddr>,
+    /// Number of blocks approvals without expiration stay valid for
+    pub default_allowance_expiry: Option<u64>,
+    pub min_holding_period: Option<u64>,
+    pub zero_approve_revokes: bool,
+    pub clamp_transfer_from: bool,
+    pub price_oracle: Option<PriceOracle>,
+    /// Balances left below it by burns are burned entirely
+    pub burn_dust_threshold: Option<TokenAmount>,
+    /// Total supply burns can not go below, zero disables it
+    pub min_total_supply: TokenAmount,
+    pub emit_sequence: bool,
+}
+
+/// Internal representation of token amounts used for
+/// balances, allowances and total supply.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Default, JsonSchema,
+)]
+pub struct TokenAmount(pub u128);
+
+impl TokenAmount {
+    pub fn u128(self) -> u128 {
+        self.0
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn checked_add(self, other: TokenAmount) -> Option<TokenAmount> {
+        self.0.checked_add(other.0).map(TokenAmount)
+    }
+
+    pub fn checked_sub(self, other: TokenAmount) -> Option<TokenAmount> {
+        self.0.checked_sub(other.0).map(TokenAmount)
+    }
+
+    pub fn saturating_add(self, other: TokenAmount) -> TokenAmount {
+        TokenAmount(self.0.saturating_add(other.0))
+    }
+}
+
+impl From<u128> for TokenAmount {
+    fn from(value: u128) -> Self {
+        TokenAmount(value)
+    }
+}
+
+impl From<Uint128> for TokenAmount {
+    fn from(value: Uint128) -> Self {
+        TokenAmount(value.u128())
+    }
 }
 
-type TotalSupply = u128;
+impl From<TokenAmount> for Uint128 {
+    fn from(value: TokenAmount) -> Self {
+        Uint128(value.0)
+    }
+}
 
 fn serialize<T: Serialize>(value: &T) -> StdResult<Vec<u8>> {
     bincode::serialize::<T>(value).map_err(|e| StdError::serialize_err(type_name::<T>(), e))
@@ -33,6 +234,12 @@ fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> StdResult<T> {
     bincode::deserialize::<T>(bytes).map_err(|e| StdError::serialize_err(type_name::<T>(), e))
 }
 
+/// Deserializes bincode encoded constants, falling back to JSON
+/// so constants written in either format can be read.
+fn deserialize_constants(bytes: &[u8]) -> StdResult<Constants> {
+    deserialize(bytes).or_else(|e| from_slice(bytes).map_err(|_| e))
+}
+
 fn slice_to_u128(bytes: &[u8]) -> StdResult<u128> {
     match <[u8; 16]>::try_from(bytes) {
         Ok(bytes) => Ok(u128::from_be_bytes(bytes)),
@@ -42,18 +249,67 @@ fn slice_to_u128(bytes: &[u8]) -> StdResult<u128> {
     }
 }
 
+fn slice_to_u64(bytes: &[u8]) -> StdResult<u64> {
+    match <[u8; 8]>::try_from(bytes) {
+        Ok(bytes) => Ok(u64::from_be_bytes(bytes)),
+        Err(_) => Err(StdError::generic_err(
+            "corrupted data, can not convert to u64",
+        )),
+    }
+}
+
+/// Account receiving part of the transfers made with reward.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RewardPool {
+    pub pool: CanonicalAddr,
+    pub max_reward_bps: u16,
+}
+
+/// Contract that can veto transfers.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TransferGuard {
+    pub contract: CanonicalAddr,
+    pub code_hash: String,
+    /// Allow transfers if the guard query fails
+    pub fail_open: bool,
+}
+
+/// Contract providing the token price.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PriceOracle {
+    pub contract: CanonicalAddr,
+    pub code_hash: String,
+}
+
+/// Account allowed to deposit tokens besides contract owner.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Minter {
+    pub address: CanonicalAddr,
+    /// Height starting from which minter can not deposit tokens
+    pub expires_at: Option<u64>,
+}
+
+impl Minter {
+    pub fn is_expired(&self, height: u64) -> bool {
+        match self.expires_at {
+            Some(expires_at) => expires_at <= height,
+            None => false,
+        }
+    }
+}
+
 pub struct ContractStorage<'a, S: Storage> {
-    storage: PrefixedStorage<'a, S>,
+    storage: LegacyFallback<&'a mut S>,
 }
 
 impl<'a, S: Storage> ContractStorage<'a, S> {
     pub fn from_storage(storage: &'a mut S) -> Self {
         Self {
-            storage: PrefixedStorage::new(NAMESPACE_STORAGE, storage),
+            storage: LegacyFallback::new(storage, StorageKeys::CONFIG, StorageKeys::LEGACY_CONFIG),
         }
     }
 
-    fn as_readonly(&self) -> ReadOnlyContractStorageImpl<PrefixedStorage<S>> {
+    fn as_readonly(&self) -> ReadOnlyContractStorageImpl<LegacyFallback<&'a mut S>> {
         ReadOnlyContractStorageImpl(&self.storage)
     }
 
@@ -67,28 +323,140 @@ impl<'a, S: Storage> ContractStorage<'a, S> {
         Ok(())
     }
 
-    pub fn total_supply(&self) -> StdResult<TotalSupply> {
+    pub fn total_supply(&self) -> StdResult<TokenAmount> {
         self.as_readonly().total_supply()
     }
 
-    pub fn set_total_supply(&mut self, value: u128) -> StdResult<()> {
-        self.storage.set(KEY_TOTAL_SUPPLY, &value.to_be_bytes()); //serialize(&value)?.as_ref());
+    pub fn set_total_supply(&mut self, value: TokenAmount) -> StdResult<()> {
+        self.storage.set(KEY_TOTAL_SUPPLY, &value.0.to_be_bytes()); //serialize(&value)?.as_ref());
+        Ok(())
+    }
+
+    pub fn total_minted(&self) -> StdResult<TokenAmount> {
+        self.as_readonly().total_minted()
+    }
+
+    pub fn set_total_minted(&mut self, value: TokenAmount) -> StdResult<()> {
+        self.storage.set(KEY_TOTAL_MINTED, &value.0.to_be_bytes());
+        Ok(())
+    }
+
+    pub fn total_burned(&self) -> StdResult<TokenAmount> {
+        self.as_readonly().total_burned()
+    }
+
+    pub fn set_total_burned(&mut self, value: TokenAmount) -> StdResult<()> {
+        self.storage.set(KEY_TOTAL_BURNED, &value.0.to_be_bytes());
+        Ok(())
+    }
+
+    pub fn disabled_handlers(&self) -> StdResult<Vec<HandlerId>> {
+        self.as_readonly().disabled_handlers()
+    }
+
+    pub fn set_disabled_handlers(&mut self, handlers: &[HandlerId]) -> StdResult<()> {
+        self.storage
+            .set(KEY_DISABLED_HANDLERS, serialize(&handlers)?.as_ref());
+        Ok(())
+    }
+
+    pub fn reserved_symbols(&self) -> StdResult<Vec<String>> {
+        self.as_readonly().reserved_symbols()
+    }
+
+    pub fn set_reserved_symbols(&mut self, symbols: &[String]) -> StdResult<()> {
+        self.storage
+            .set(KEY_RESERVED_SYMBOLS, serialize(&symbols)?.as_ref());
+        Ok(())
+    }
+
+    pub fn owner_query_key_hash(&self) -> Option<Vec<u8>> {
+        self.as_readonly().owner_query_key_hash()
+    }
+
+    pub fn set_owner_query_key_hash(&mut self, hash: &[u8]) {
+        self.storage.set(KEY_OWNER_QUERY_KEY, hash);
+    }
+
+    pub fn reward_pool(&self) -> StdResult<Option<RewardPool>> {
+        self.as_readonly().reward_pool()
+    }
+
+    pub fn set_reward_pool(&mut self, value: &Option<RewardPool>) -> StdResult<()> {
+        self.storage
+            .set(KEY_REWARD_POOL, serialize(&value)?.as_ref());
+        Ok(())
+    }
+
+    pub fn minters(&self) -> StdResult<Vec<Minter>> {
+        self.as_readonly().minters()
+    }
+
+    pub fn set_minters(&mut self, value: &[Minter]) -> StdResult<()> {
+        self.storage.set(KEY_MINTERS, serialize(&value)?.as_ref());
+        Ok(())
+    }
+
+    pub fn tips(&self) -> StdResult<Vec<Coin>> {
+        self.as_readonly().tips()
+    }
+
+    pub fn set_tips(&mut self, value: &[Coin]) -> StdResult<()> {
+        self.storage.set(KEY_TIPS, serialize(&value)?.as_ref());
+        Ok(())
+    }
+
+    pub fn transfer_guard(&self) -> StdResult<Option<TransferGuard>> {
+        self.as_readonly().transfer_guard()
+    }
+
+    pub fn reward_per_token(&self) -> StdResult<u128> {
+        self.as_readonly().reward_per_token()
+    }
+
+    pub fn set_reward_per_token(&mut self, value: u128) -> StdResult<()> {
+        self.storage.set(KEY_REWARD_PER_TOKEN, &value.to_be_bytes());
+        Ok(())
+    }
+
+    pub fn sequence(&self) -> StdResult<u64> {
+        self.as_readonly().sequence()
+    }
+
+    pub fn set_sequence(&mut self, value: u64) -> StdResult<()> {
+        self.storage.set(KEY_SEQUENCE, &value.to_be_bytes());
+        Ok(())
+    }
+
+    pub fn set_transfer_guard(&mut self, value: &Option<TransferGuard>) -> StdResult<()> {
+        self.storage
+            .set(KEY_TRANSFER_GUARD, serialize(&value)?.as_ref());
+        Ok(())
+    }
+
+    pub fn schema_version(&self) -> StdResult<SchemaVersion> {
+        self.as_readonly().schema_version()
+    }
+
+    pub fn set_schema_version(&mut self, value: &SchemaVersion) -> StdResult<()> {
+        self.storage
+            .set(KEY_SCHEMA_VERSION, serialize(value)?.as_ref());
         Ok(())
     }
 }
 
 pub struct ReadOnlyContractStorage<'a, S: Storage> {
-    storage: ReadonlyPrefixedStorage<'a, S>,
+    storage: LegacyFallback<&'a S>,
 }
 
 impl<'a, S: Storage> ReadOnlyContractStorage<'a, S> {
     pub fn from_storage(storage: &'a S) -> Self {
         Self {
-            storage: ReadonlyPrefixedStorage::new(NAMESPACE_STORAGE, storage),
+            storage: LegacyFallback::new(storage, StorageKeys::CONFIG, StorageKeys::LEGACY_CONFIG),
         }
     }
 
-    fn as_readonly(&self) -> ReadOnlyContractStorageImpl<ReadonlyPrefixedStorage<S>> {
+    fn as_readonly(&self) -> ReadOnlyContractStorageImpl<LegacyFallback<&'a S>> {
         ReadOnlyContractStorageImpl(&self.storage)
     }
 
@@ -96,9 +464,58 @@ impl<'a, S: Storage> ReadOnlyContractStorage<'a, S> {
         self.as_readonly().constants()
     }
 
-    pub fn total_supply(&self) -> StdResult<TotalSupply> {
+    pub fn total_supply(&self) -> StdResult<TokenAmount> {
         self.as_readonly().total_supply()
     }
+
+    pub fn total_minted(&self) -> StdResult<TokenAmount> {
+        self.as_readonly().total_minted()
+    }
+
+    pub fn total_burned(&self) -> StdResult<TokenAmount> {
+        self.as_readonly().total_burned()
+    }
+
+    pub fn disabled_handlers(&self) -> StdResult<Vec<HandlerId>> {
+        self.as_readonly().disabled_handlers()
+    }
+
+    pub fn reserved_symbols(&self) -> StdResult<Vec<String>> {
+        self.as_readonly().reserved_symbols()
+    }
+
+    pub fn owner_query_key_hash(&self) -> Option<Vec<u8>> {
+        self.as_readonly().owner_query_key_hash()
+    }
+
+    pub fn reward_pool(&self) -> StdResult<Option<RewardPool>> {
+        self.as_readonly().reward_pool()
+    }
+
+    pub fn minters(&self) -> StdResult<Vec<Minter>> {
+        self.as_readonly().minters()
+    }
+
+    /// Returns serialized value stored under config `key`.
+    pub fn raw(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.storage.get(key)
+    }
+
+    pub fn tips(&self) -> StdResult<Vec<Coin>> {
+        self.as_readonly().tips()
+    }
+
+    pub fn transfer_guard(&self) -> StdResult<Option<TransferGuard>> {
+        self.as_readonly().transfer_guard()
+    }
+
+    pub fn reward_per_token(&self) -> StdResult<u128> {
+        self.as_readonly().reward_per_token()
+    }
+
+    pub fn schema_version(&self) -> StdResult<SchemaVersion> {
+        self.as_readonly().schema_version()
+    }
 }
 
 struct ReadOnlyContractStorageImpl<'a, S: ReadonlyStorage>(&'a S);
@@ -109,58 +526,148 @@ impl<'a, S: ReadonlyStorage> ReadOnlyContractStorageImpl<'a, S> {
             .0
             .get(KEY_CONSTANTS)
             .ok_or(StdError::generic_err("no constants in storage"))?;
-        deserialize(&bytes)
+        deserialize_constants(&bytes)
     }
 
-    pub fn total_supply(&self) -> StdResult<TotalSupply> {
+    pub fn total_supply(&self) -> StdResult<TokenAmount> {
         let bytes = self
             .0
             .get(KEY_TOTAL_SUPPLY)
             .ok_or(StdError::generic_err("no constants in storage"))?;
-        slice_to_u128(&bytes)
+        slice_to_u128(&bytes).map(TokenAmount)
+    }
+
+    pub fn total_minted(&self) -> StdResult<TokenAmount> {
+        match self.0.get(KEY_TOTAL_MINTED) {
+            Some(bytes) => slice_to_u128(&bytes).map(TokenAmount),
+            None => Ok(TokenAmount(0)),
+        }
+    }
+
+    pub fn total_burned(&self) -> StdResult<TokenAmount> {
+        match self.0.get(KEY_TOTAL_BURNED) {
+            Some(bytes) => slice_to_u128(&bytes).map(TokenAmount),
+            None => Ok(TokenAmount(0)),
+        }
+    }
+
+    pub fn disabled_handlers(&self) -> StdResult<Vec<HandlerId>> {
+        match self.0.get(KEY_DISABLED_HANDLERS) {
+            Some(bytes) => deserialize(&bytes),
+            None => Ok(vec![]),
+        }
+    }
+
+    pub fn reserved_symbols(&self) -> StdResult<Vec<String>> {
+        match self.0.get(KEY_RESERVED_SYMBOLS) {
+            Some(bytes) => deserialize(&bytes),
+            None => Ok(vec![]),
+        }
+    }
+
+    pub fn owner_query_key_hash(&self) -> Option<Vec<u8>> {
+        self.0.get(KEY_OWNER_QUERY_KEY)
+    }
+
+    pub fn reward_pool(&self) -> StdResult<Option<RewardPool>> {
+        match self.0.get(KEY_REWARD_POOL) {
+            Some(bytes) => deserialize(&bytes),
+            None => Ok(None),
+        }
+    }
+
+    pub fn minters(&self) -> StdResult<Vec<Minter>> {
+        match self.0.get(KEY_MINTERS) {
+            Some(bytes) => deserialize(&bytes),
+            None => Ok(vec![]),
+        }
+    }
+
+    pub fn tips(&self) -> StdResult<Vec<Coin>> {
+        match self.0.get(KEY_TIPS) {
+            Some(bytes) => deserialize(&bytes),
+            None => Ok(vec![]),
+        }
+    }
+
+    pub fn transfer_guard(&self) -> StdResult<Option<TransferGuard>> {
+        match self.0.get(KEY_TRANSFER_GUARD) {
+            Some(bytes) => deserialize(&bytes),
+            None => Ok(None),
+        }
+    }
+
+    pub fn reward_per_token(&self) -> StdResult<u128> {
+        match self.0.get(KEY_REWARD_PER_TOKEN) {
+            Some(bytes) => slice_to_u128(&bytes),
+            None => Ok(0),
+        }
+    }
+
+    pub fn sequence(&self) -> StdResult<u64> {
+        match self.0.get(KEY_SEQUENCE) {
+            Some(bytes) => slice_to_u64(&bytes),
+            None => Ok(0),
+        }
+    }
+
+    pub fn schema_version(&self) -> StdResult<SchemaVersion> {
+        let bytes = self
+            .0
+            .get(KEY_SCHEMA_VERSION)
+            .ok_or_else(|| StdError::generic_err("no schema version in storage"))?;
+        deserialize(&bytes)
     }
 }
 
 pub struct Balances<'a, S: Storage> {
-    storage: PrefixedStorage<'a, S>,
+    storage: LegacyFallback<&'a mut S>,
 }
 
 impl<'a, S: Storage> Balances<'a, S> {
     pub fn from_storage(storage: &'a mut S) -> Self {
         Self {
-            storage: PrefixedStorage::new(NAMESPACE_BALANCES, storage),
+            storage: LegacyFallback::new(
+                storage,
+                StorageKeys::BALANCES,
+                StorageKeys::LEGACY_BALANCES,
+            ),
         }
     }
 
-    fn as_readonly(&self) -> ReadonlyBalancesImpl<PrefixedStorage<S>> {
+    fn as_readonly(&self) -> ReadonlyBalancesImpl<LegacyFallback<&'a mut S>> {
         ReadonlyBalancesImpl(&self.storage)
     }
 
-    pub fn balance(&self, address: &CanonicalAddr) -> u128 {
+    pub fn balance(&self, address: &CanonicalAddr) -> TokenAmount {
         self.as_readonly().balance(address)
     }
 
-    pub fn set_balance(&mut self, address: &CanonicalAddr, value: u128) {
-        self.storage.set(address.as_slice(), &value.to_be_bytes());
+    pub fn set_balance(&mut self, address: &CanonicalAddr, value: TokenAmount) {
+        self.storage.set(address.as_slice(), &value.0.to_be_bytes());
     }
 }
 
 pub struct ReadOnlyBalances<'a, S: Storage> {
-    storage: ReadonlyPrefixedStorage<'a, S>,
+    storage: LegacyFallback<&'a S>,
 }
 
 impl<'a, S: Storage> ReadOnlyBalances<'a, S> {
     pub fn from_storage(storage: &'a S) -> Self {
         Self {
-            storage: ReadonlyPrefixedStorage::new(NAMESPACE_BALANCES, storage),
+            storage: LegacyFallback::new(
+                storage,
+                StorageKeys::BALANCES,
+                StorageKeys::LEGACY_BALANCES,
+            ),
         }
     }
 
-    fn as_readonly(&self) -> ReadonlyBalancesImpl<ReadonlyPrefixedStorage<S>> {
+    fn as_readonly(&self) -> ReadonlyBalancesImpl<LegacyFallback<&'a S>> {
         ReadonlyBalancesImpl(&self.storage)
     }
 
-    pub fn balance(&self, address: &CanonicalAddr) -> u128 {
+    pub fn balance(&self, address: &CanonicalAddr) -> TokenAmount {
         self.as_readonly().balance(address)
     }
 }
@@ -168,17 +675,40 @@ impl<'a, S: Storage> ReadOnlyBalances<'a, S> {
 struct ReadonlyBalancesImpl<'a, S: ReadonlyStorage>(&'a S);
 
 impl<'a, S: ReadonlyStorage> ReadonlyBalancesImpl<'a, S> {
-    pub fn balance(&self, account: &CanonicalAddr) -> u128 {
+    pub fn balance(&self, account: &CanonicalAddr) -> TokenAmount {
         match self.0.get(account.as_slice()) {
-            Some(balance_bytes) => slice_to_u128(&balance_bytes).unwrap(),
-            None => 0,
+            Some(balance_bytes) => TokenAmount(slice_to_u128(&balance_bytes).unwrap()),
+            None => TokenAmount(0),
         }
     }
 }
 
 #[derive(Serialize, Debug, Deserialize, Clone, PartialEq, Default, JsonSchema)]
 pub struct Allowance {
-    pub amount: u128,
+    pub amount: TokenAmount,
+    /// Height after which allowance can not be used
+    pub expiration: Option<u64>,
+}
+
+impl Allowance {
+    pub fn is_expired(&self, height: u64) -> bool {
+        matches!(self.expiration, Some(expiration) if height > expiration)
+    }
+}
+
+/// Allowance format stored before expiration was added.
+#[derive(Serialize, Deserialize)]
+struct LegacyAllowance {
+    amount: TokenAmount,
+}
+
+impl From<LegacyAllowance> for Allowance {
+    fn from(allowance: LegacyAllowance) -> Self {
+        Self {
+            amount: allowance.amount,
+            expiration: None,
+        }
+    }
 }
 
 pub fn get_allowance<S: Storage>(
@@ -186,12 +716,12 @@ pub fn get_allowance<S: Storage>(
     owner: &CanonicalAddr,
     spender: &CanonicalAddr,
 ) -> StdResult<Allowance> {
-    let owner_storage =
-        ReadonlyPrefixedStorage::multilevel(&[NAMESPACE_ALLOWANCES, owner.as_slice()], storage);
-    match owner_storage.get(spender.as_slice()) {
-        Some(bytes) => deserialize(&bytes)?,
-        None => Ok(Allowance { amount: 0 }),
-    }
+    get_allowance_in(
+        storage,
+        &[StorageKeys::ALLOWANCES, StorageKeys::LEGACY_ALLOWANCES],
+        owner,
+        spender,
+    )
 }
 
 pub fn set_allowance<S: Storage>(
@@ -199,9 +729,1081 @@ pub fn set_allowance<S: Storage>(
     owner: &CanonicalAddr,
     spender: &CanonicalAddr,
     allowance: Allowance,
+) -> StdResult<()> {
+    let owner_storage =
+        ReadonlyPrefixedStorage::multilevel(&[StorageKeys::ALLOWANCES, owner.as_slice()], storage);
+    if owner_storage.get(spender.as_slice()).is_none() {
+        add_allowance_entry(storage, owner, spender)?;
+        let mut spenders = get_allowance_spenders(&*storage, owner)?;
+        spenders.push(spender.clone());
+        set_account_value(storage, StorageKeys::ALLOWANCE_SPENDERS, owner, &spenders)?;
+    }
+    set_allowance_in(storage, StorageKeys::ALLOWANCES, owner, spender, allowance)
+}
+
+/// Removes the allowance together with its spender list and index entries,
+/// leaving storage as if the `spender` was never approved.
+pub fn remove_allowance<S: Storage>(
+    storage: &mut S,
+    owner: &CanonicalAddr,
+    spender: &CanonicalAddr,
 ) -> StdResult<()> {
     let mut owner_storage =
-        PrefixedStorage::multilevel(&[NAMESPACE_ALLOWANCES, owner.as_slice()], storage);
-    owner_storage.set(spender.as_slice(), serialize(&allowance)?.as_ref());
+        PrefixedStorage::multilevel(&[StorageKeys::ALLOWANCES, owner.as_slice()], storage);
+    let exists = owner_storage.get(spender.as_slice()).is_some();
+    owner_storage.remove(spender.as_slice());
+    let mut legacy_storage =
+        PrefixedStorage::multilevel(&[StorageKeys::LEGACY_ALLOWANCES, owner.as_slice()], storage);
+    if !exists && legacy_storage.get(spender.as_slice()).is_none() {
+        return Ok(());
+    }
+    legacy_storage.remove(spender.as_slice());
+
+    let mut spenders = get_allowance_spenders(&*storage, owner)?;
+    spenders.retain(|approved| approved != spender);
+    if spenders.is_empty() {
+        remove_account_value(storage, StorageKeys::ALLOWANCE_SPENDERS, owner);
+    } else {
+        set_account_value(storage, StorageKeys::ALLOWANCE_SPENDERS, owner, &spenders)?;
+    }
+
+    // removed entries leave a gap, so indices of other entries stay stable for paging
+    let mut positions = PrefixedStorage::multilevel(
+        &[StorageKeys::ALLOWANCE_POSITIONS, owner.as_slice()],
+        storage,
+    );
+    if let Some(index) = positions.get(spender.as_slice()) {
+        positions.remove(spender.as_slice());
+        let mut entries = PrefixedStorage::new(StorageKeys::ALLOWANCE_INDEX, storage);
+        entries.remove(&index);
+    }
+    Ok(())
+}
+
+/// Owner and spender of an allowance, indexed in creation order
+/// since storage can not be iterated.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllowanceEntry {
+    pub owner: CanonicalAddr,
+    pub spender: CanonicalAddr,
+}
+
+fn add_allowance_entry<S: Storage>(
+    storage: &mut S,
+    owner: &CanonicalAddr,
+    spender: &CanonicalAddr,
+) -> StdResult<()> {
+    let index = allowance_count(&*storage)?;
+    let mut entries = PrefixedStorage::new(StorageKeys::ALLOWANCE_INDEX, storage);
+    let entry = AllowanceEntry {
+        owner: owner.clone(),
+        spender: spender.clone(),
+    };
+    entries.set(&index.to_be_bytes(), serialize(&entry)?.as_ref());
+
+    // position of the entry lets it be removed without scanning the index
+    let mut positions = PrefixedStorage::multilevel(
+        &[StorageKeys::ALLOWANCE_POSITIONS, owner.as_slice()],
+        storage,
+    );
+    positions.set(spender.as_slice(), &index.to_be_bytes());
+
+    let mut config = PrefixedStorage::new(StorageKeys::CONFIG, storage);
+    config.set(KEY_ALLOWANCE_COUNT, &(index + 1).to_be_bytes());
     Ok(())
 }
+
+/// Returns spenders the `owner` ever approved, in approval order.
+pub fn get_allowance_spenders<S: Storage>(
+    storage: &S,
+    owner: &CanonicalAddr,
+) -> StdResult<Vec<CanonicalAddr>> {
+    get_account_value(storage, StorageKeys::ALLOWANCE_SPENDERS, owner)
+        .map(|spenders| spenders.unwrap_or_default())
+}
+
+/// Returns number of entries ever added to the allowance index.
+pub fn allowance_count<S: Storage>(storage: &S) -> StdResult<u64> {
+    let config = ReadonlyPrefixedStorage::new(StorageKeys::CONFIG, storage);
+    match config.get(KEY_ALLOWANCE_COUNT) {
+        Some(bytes) => slice_to_u64(&bytes),
+        None => Ok(0),
+    }
+}
+
+pub fn get_allowance_entry<S: Storage>(
+    storage: &S,
+    index: u64,
+) -> StdResult<Option<AllowanceEntry>> {
+    let entries = ReadonlyPrefixedStorage::new(StorageKeys::ALLOWANCE_INDEX, storage);
+    match entries.get(&index.to_be_bytes()) {
+        Some(bytes) => deserialize(&bytes).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Allowance the `spender` can only burn from `owner` balance.
+pub fn get_burn_allowance<S: Storage>(
+    storage: &S,
+    owner: &CanonicalAddr,
+    spender: &CanonicalAddr,
+) -> StdResult<Allowance> {
+    get_allowance_in(storage, &[StorageKeys::BURN_ALLOWANCES], owner, spender)
+}
+
+pub fn set_burn_allowance<S: Storage>(
+    storage: &mut S,
+    owner: &CanonicalAddr,
+    spender: &CanonicalAddr,
+    allowance: Allowance,
+) -> StdResult<()> {
+    set_allowance_in(
+        storage,
+        StorageKeys::BURN_ALLOWANCES,
+        owner,
+        spender,
+        allowance,
+    )
+}
+
+/// Reads the allowance from the first of `namespaces` having it.
+fn get_allowance_in<S: Storage>(
+    storage: &S,
+    namespaces: &[&[u8]],
+    owner: &CanonicalAddr,
+    spender: &CanonicalAddr,
+) -> StdResult<Allowance> {
+    let bytes = namespaces.iter().find_map(|namespace| {
+        ReadonlyPrefixedStorage::multilevel(&[*namespace, owner.as_slice()], storage)
+            .get(spender.as_slice())
+    });
+    match bytes {
+        Some(bytes) => deserialize(&bytes)
+            .or_else(|_| deserialize::<LegacyAllowance>(&bytes).map(Allowance::from)),
+        None => Ok(Allowance::default()),
+    }
+}
+
+fn set_allowance_in<S: Storage>(
+    storage: &mut S,
+    namespace: &[u8],
+    owner: &CanonicalAddr,
+    spender: &CanonicalAddr,
+    allowance: Allowance,
+) -> StdResult<()> {
+    let mut owner_storage = PrefixedStorage::multilevel(&[namespace, owner.as_slice()], storage);
+    owner_storage.set(spender.as_slice(), serialize(&allowance)?.as_ref());
+    Ok(())
+}
+
+/// Returns index of the next transaction of the account
+/// and increments account transaction counter.
+pub fn next_tx_index<S: Storage>(storage: &mut S, account: &CanonicalAddr) -> StdResult<u64> {
+    let mut counts = PrefixedStorage::new(StorageKeys::TX_COUNTS, storage);
+    let index = match counts.get(account.as_slice()) {
+        Some(bytes) => slice_to_u64(&bytes)?,
+        None => 0,
+    };
+    counts.set(account.as_slice(), &(index + 1).to_be_bytes());
+    Ok(index)
+}
+
+/// Marks account as one that held tokens at some point
+/// and records `height` of the first credit.
+pub fn mark_seen<S: Storage>(storage: &mut S, account: &CanonicalAddr, height: u64) {
+    let mut seen = PrefixedStorage::new(StorageKeys::SEEN, storage);
+    if seen.get(account.as_slice()).is_none() {
+        seen.set(account.as_slice(), &[1]);
+        let mut first_received = PrefixedStorage::new(StorageKeys::FIRST_RECEIVED, storage);
+        first_received.set(account.as_slice(), &height.to_be_bytes());
+    }
+}
+
+pub fn is_seen<S: Storage>(storage: &S, account: &CanonicalAddr) -> bool {
+    let seen = ReadonlyPrefixedStorage::new(StorageKeys::SEEN, storage);
+    seen.get(account.as_slice()).is_some()
+}
+
+/// Height of the first credit, `None` for accounts seen before it was recorded.
+pub fn get_first_received_height<S: Storage>(
+    storage: &S,
+    account: &CanonicalAddr,
+) -> StdResult<Option<u64>> {
+    let first_received = ReadonlyPrefixedStorage::new(StorageKeys::FIRST_RECEIVED, storage);
+    first_received
+        .get(account.as_slice())
+        .map(|bytes| slice_to_u64(&bytes))
+        .transpose()
+}
+
+/// Marks account as allowed to receive tokens when registration is required.
+pub fn register_account<S: Storage>(storage: &mut S, account: &CanonicalAddr) {
+    let mut registered = PrefixedStorage::new(StorageKeys::REGISTERED, storage);
+    registered.set(account.as_slice(), &[1]);
+}
+
+pub fn is_registered<S: Storage>(storage: &S, account: &CanonicalAddr) -> bool {
+    let registered = ReadonlyPrefixedStorage::new(StorageKeys::REGISTERED, storage);
+    registered.get(account.as_slice()).is_some()
+}
+
+/// Locks or unlocks outgoing transfers and approvals of the account.
+pub fn set_locked<S: Storage>(storage: &mut S, account: &CanonicalAddr, locked: bool) {
+    let mut locks = PrefixedStorage::new(StorageKeys::LOCKED, storage);
+    if locked {
+        locks.set(account.as_slice(), &[1]);
+    } else {
+        locks.remove(account.as_slice());
+    }
+}
+
+pub fn is_locked<S: Storage>(storage: &S, account: &CanonicalAddr) -> bool {
+    let locks = ReadonlyPrefixedStorage::new(StorageKeys::LOCKED, storage);
+    locks.get(account.as_slice()).is_some()
+}
+
+fn get_account_value<S: Storage, T: DeserializeOwned>(
+    storage: &S,
+    namespace: &[u8],
+    account: &CanonicalAddr,
+) -> StdResult<Option<T>> {
+    let account_storage = ReadonlyPrefixedStorage::new(namespace, storage);
+    match account_storage.get(account.as_slice()) {
+        Some(bytes) => deserialize(&bytes).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn set_account_value<S: Storage, T: Serialize>(
+    storage: &mut S,
+    namespace: &[u8],
+    account: &CanonicalAddr,
+    value: &T,
+) -> StdResult<()> {
+    let mut account_storage = PrefixedStorage::new(namespace, storage);
+    account_storage.set(account.as_slice(), serialize(value)?.as_ref());
+    Ok(())
+}
+
+fn remove_account_value<S: Storage>(storage: &mut S, namespace: &[u8], account: &CanonicalAddr) {
+    let mut account_storage = PrefixedStorage::new(namespace, storage);
+    account_storage.remove(account.as_slice());
+}
+
+pub fn get_guardian<S: Storage>(
+    storage: &S,
+    account: &CanonicalAddr,
+) -> StdResult<Option<CanonicalAddr>> {
+    get_account_value(storage, StorageKeys::GUARDIANS, account)
+}
+
+pub fn set_guardian<S: Storage>(
+    storage: &mut S,
+    account: &CanonicalAddr,
+    guardian: Option<&CanonicalAddr>,
+) -> StdResult<()> {
+    match guardian {
+        Some(guardian) => set_account_value(storage, StorageKeys::GUARDIANS, account, guardian),
+        None => {
+            remove_account_value(storage, StorageKeys::GUARDIANS, account);
+            Ok(())
+        }
+    }
+}
+
+/// Account receiving transfers the account refuses.
+pub fn get_fallback_recipient<S: Storage>(
+    storage: &S,
+    account: &CanonicalAddr,
+) -> StdResult<Option<CanonicalAddr>> {
+    get_account_value(storage, StorageKeys::FALLBACK_RECIPIENTS, account)
+}
+
+pub fn set_fallback_recipient<S: Storage>(
+    storage: &mut S,
+    account: &CanonicalAddr,
+    fallback: Option<&CanonicalAddr>,
+) -> StdResult<()> {
+    match fallback {
+        Some(fallback) => {
+            set_account_value(storage, StorageKeys::FALLBACK_RECIPIENTS, account, fallback)
+        }
+        None => {
+            remove_account_value(storage, StorageKeys::FALLBACK_RECIPIENTS, account);
+            Ok(())
+        }
+    }
+}
+
+/// Number of blocks approvals of the account without expiration stay valid for.
+pub fn get_default_allowance_expiry<S: Storage>(
+    storage: &S,
+    account: &CanonicalAddr,
+) -> StdResult<Option<u64>> {
+    get_account_value(storage, StorageKeys::ALLOWANCE_EXPIRY_DEFAULTS, account)
+}
+
+pub fn set_default_allowance_expiry<S: Storage>(
+    storage: &mut S,
+    account: &CanonicalAddr,
+    blocks: Option<u64>,
+) -> StdResult<()> {
+    match blocks {
+        Some(blocks) => set_account_value(
+            storage,
+            StorageKeys::ALLOWANCE_EXPIRY_DEFAULTS,
+            account,
+            &blocks,
+        ),
+        None => {
+            remove_account_value(storage, StorageKeys::ALLOWANCE_EXPIRY_DEFAULTS, account);
+            Ok(())
+        }
+    }
+}
+
+/// Recovery of the account initiated by its guardian.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Recovery {
+    pub to: CanonicalAddr,
+    /// Height after which recovery can be finalized
+    pub unlock_height: u64,
+}
+
+pub fn get_recovery<S: Storage>(
+    storage: &S,
+    account: &CanonicalAddr,
+) -> StdResult<Option<Recovery>> {
+    get_account_value(storage, StorageKeys::RECOVERIES, account)
+}
+
+pub fn set_recovery<S: Storage>(
+    storage: &mut S,
+    account: &CanonicalAddr,
+    recovery: Option<&Recovery>,
+) -> StdResult<()> {
+    match recovery {
+        Some(recovery) => set_account_value(storage, StorageKeys::RECOVERIES, account, recovery),
+        None => {
+            remove_account_value(storage, StorageKeys::RECOVERIES, account);
+            Ok(())
+        }
+    }
+}
+
+/// Storage buffering all writes in memory
+/// until they are committed to the underlying storage.
+pub struct OverlayStorage<'a, S: Storage> {
+    storage: &'a mut S,
+    changes: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+}
+
+impl<'a, S: Storage> OverlayStorage<'a, S> {
+    pub fn new(storage: &'a mut S) -> Self {
+        Self {
+            storage,
+            changes: BTreeMap::new(),
+        }
+    }
+
+    pub fn commit(self) {
+        for (key, value) in self.changes {
+            match value {
+                Some(value) => self.storage.set(&key, &value),
+                None => self.storage.remove(&key),
+            }
+        }
+    }
+}
+
+impl<'a, S: Storage> ReadonlyStorage for OverlayStorage<'a, S> {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        match self.changes.get(key) {
+            Some(value) => value.clone(),
+            None => self.storage.get(key),
+        }
+    }
+}
+
+impl<'a, S: Storage> Storage for OverlayStorage<'a, S> {
+    fn set(&mut self, key: &[u8], value: &[u8]) {
+        self.changes.insert(key.to_vec(), Some(value.to_vec()));
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        self.changes.insert(key.to_vec(), None);
+    }
+}
+
+/// Runs `f` against an overlay of `storage` and writes its changes
+/// only if `f` succeeds, so a failed batch never leaves partial writes.
+pub fn apply_atomic<S, T, F>(storage: &mut S, f: F) -> StdResult<T>
+where
+    S: Storage,
+    F: FnOnce(&mut OverlayStorage<S>) -> StdResult<T>,
+{
+    let mut overlay = OverlayStorage::new(storage);
+    let result = f(&mut overlay)?;
+    overlay.commit();
+    Ok(result)
+}
+
+/// Storage buffering all writes over a read only storage
+/// and dropping them, so queries can run handler logic.
+pub struct DryRunStorage<'a, S: ReadonlyStorage> {
+    storage: &'a S,
+    changes: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+}
+
+impl<'a, S: ReadonlyStorage> ReadonlyStorage for DryRunStorage<'a, S> {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        match self.changes.get(key) {
+            Some(value) => value.clone(),
+            None => self.storage.get(key),
+        }
+    }
+}
+
+impl<'a, S: ReadonlyStorage> Storage for DryRunStorage<'a, S> {
+    fn set(&mut self, key: &[u8], value: &[u8]) {
+        self.changes.insert(key.to_vec(), Some(value.to_vec()));
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        self.changes.insert(key.to_vec(), None);
+    }
+}
+
+/// Runs `f` against an overlay of `storage` and drops its changes.
+pub fn dry_run<S, T, F>(storage: &S, f: F) -> StdResult<T>
+where
+    S: ReadonlyStorage,
+    F: FnOnce(&mut DryRunStorage<S>) -> StdResult<T>,
+{
+    f(&mut DryRunStorage {
+        storage,
+        changes: BTreeMap::new(),
+    })
+}
+
+pub fn get_large_transfer_threshold<S: Storage>(
+    storage: &S,
+    account: &CanonicalAddr,
+) -> StdResult<Option<TokenAmount>> {
+    get_account_value(storage, StorageKeys::LARGE_TRANSFER_THRESHOLDS, account)
+}
+
+pub fn set_large_transfer_threshold<S: Storage>(
+    storage: &mut S,
+    account: &CanonicalAddr,
+    threshold: Option<TokenAmount>,
+) -> StdResult<()> {
+    match threshold {
+        Some(threshold) => set_account_value(
+            storage,
+            StorageKeys::LARGE_TRANSFER_THRESHOLDS,
+            account,
+            &threshold,
+        ),
+        None => {
+            remove_account_value(storage, StorageKeys::LARGE_TRANSFER_THRESHOLDS, account);
+            Ok(())
+        }
+    }
+}
+
+/// Transfer above account threshold waiting for confirmation.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingTransfer {
+    pub to: CanonicalAddr,
+    pub value: TokenAmount,
+}
+
+/// Stores pending transfer of the account and returns its id.
+pub fn add_pending_transfer<S: Storage>(
+    storage: &mut S,
+    account: &CanonicalAddr,
+    transfer: &PendingTransfer,
+) -> StdResult<u64> {
+    let id: u64 = get_account_value(&*storage, StorageKeys::PENDING_TRANSFER_IDS, account)?
+        .unwrap_or_default();
+    set_account_value(
+        storage,
+        StorageKeys::PENDING_TRANSFER_IDS,
+        account,
+        &(id + 1),
+    )?;
+
+    let mut transfers = PrefixedStorage::multilevel(
+        &[StorageKeys::PENDING_TRANSFERS, account.as_slice()],
+        storage,
+    );
+    transfers.set(&id.to_be_bytes(), serialize(transfer)?.as_ref());
+    Ok(id)
+}
+
+/// Removes pending transfer of the account and returns it.
+pub fn take_pending_transfer<S: Storage>(
+    storage: &mut S,
+    account: &CanonicalAddr,
+    id: u64,
+) -> StdResult<Option<PendingTransfer>> {
+    let mut transfers = PrefixedStorage::multilevel(
+        &[StorageKeys::PENDING_TRANSFERS, account.as_slice()],
+        storage,
+    );
+    match transfers.get(&id.to_be_bytes()) {
+        Some(bytes) => {
+            transfers.remove(&id.to_be_bytes());
+            deserialize(&bytes).map(Some)
+        }
+        None => Ok(None),
+    }
+}
+
+/// Senders account accepts transfers from.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub enum ReceivePolicy {
+    AcceptAll,
+    Allowlist(Vec<CanonicalAddr>),
+}
+
+impl ReceivePolicy {
+    pub fn accepts(&self, sender: &CanonicalAddr) -> bool {
+        match self {
+            ReceivePolicy::AcceptAll => true,
+            ReceivePolicy::Allowlist(senders) => senders.contains(sender),
+        }
+    }
+}
+
+pub fn get_receive_policy<S: Storage>(
+    storage: &S,
+    account: &CanonicalAddr,
+) -> StdResult<ReceivePolicy> {
+    get_account_value(storage, StorageKeys::RECEIVE_OPT, account)
+        .map(|policy| policy.unwrap_or(ReceivePolicy::AcceptAll))
+}
+
+pub fn set_receive_policy<S: Storage>(
+    storage: &mut S,
+    account: &CanonicalAddr,
+    policy: &ReceivePolicy,
+) -> StdResult<()> {
+    match policy {
+        ReceivePolicy::AcceptAll => {
+            remove_account_value(storage, StorageKeys::RECEIVE_OPT, account);
+            Ok(())
+        }
+        ReceivePolicy::Allowlist(_) => {
+            set_account_value(storage, StorageKeys::RECEIVE_OPT, account, policy)
+        }
+    }
+}
+
+/// Rewards accrued by the account up to the `reward_per_token` snapshot.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default, JsonSchema)]
+pub struct RewardDebt {
+    pub reward_per_token: u128,
+    pub accrued: TokenAmount,
+}
+
+pub fn get_reward_debt<S: Storage>(storage: &S, account: &CanonicalAddr) -> StdResult<RewardDebt> {
+    get_account_value(storage, StorageKeys::REWARD_DEBTS, account)
+        .map(|debt| debt.unwrap_or_default())
+}
+
+pub fn set_reward_debt<S: Storage>(
+    storage: &mut S,
+    account: &CanonicalAddr,
+    debt: &RewardDebt,
+) -> StdResult<()> {
+    set_account_value(storage, StorageKeys::REWARD_DEBTS, account, debt)
+}
+
+/// Grant of tokens minted to the first account claiming the code.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ClaimCode {
+    pub amount: TokenAmount,
+    pub claimed: bool,
+}
+
+/// Returns claim code stored under the SHA-256 `code_hash` of the code.
+pub fn get_claim_code<S: Storage>(storage: &S, code_hash: &[u8]) -> StdResult<Option<ClaimCode>> {
+    let codes = ReadonlyPrefixedStorage::new(StorageKeys::CLAIM_CODES, storage);
+    match codes.get(code_hash) {
+        Some(bytes) => deserialize(&bytes).map(Some),
+        None => Ok(None),
+    }
+}
+
+pub fn set_claim_code<S: Storage>(
+    storage: &mut S,
+    code_hash: &[u8],
+    claim_code: &ClaimCode,
+) -> StdResult<()> {
+    let mut codes = PrefixedStorage::new(StorageKeys::CLAIM_CODES, storage);
+    codes.set(code_hash, serialize(claim_code)?.as_ref());
+    Ok(())
+}
+
+/// Returns transfers queued by the account and not settled yet.
+pub fn get_queued_transfers<S: Storage>(
+    storage: &S,
+    account: &CanonicalAddr,
+) -> StdResult<Vec<PendingTransfer>> {
+    get_account_value(storage, StorageKeys::QUEUED_TRANSFERS, account)
+        .map(|transfers| transfers.unwrap_or_default())
+}
+
+pub fn set_queued_transfers<S: Storage>(
+    storage: &mut S,
+    account: &CanonicalAddr,
+    transfers: &[PendingTransfer],
+) -> StdResult<()> {
+    if transfers.is_empty() {
+        remove_account_value(storage, StorageKeys::QUEUED_TRANSFERS, account);
+        Ok(())
+    } else {
+        set_account_value(storage, StorageKeys::QUEUED_TRANSFERS, account, &transfers)
+    }
+}
+
+/// Tokens debited from `sender` and held by the contract until
+/// they are released to `recipient` or refunded.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Escrow {
+    pub sender: CanonicalAddr,
+    pub recipient: CanonicalAddr,
+    pub value: TokenAmount,
+    pub release_height: u64,
+}
+
+/// Stores the escrow and returns its id.
+pub fn add_escrow<S: Storage>(storage: &mut S, escrow: &Escrow) -> StdResult<u64> {
+    let id = escrow_count(&*storage)?;
+    let mut escrows = PrefixedStorage::new(StorageKeys::ESCROWS, storage);
+    escrows.set(&id.to_be_bytes(), serialize(escrow)?.as_ref());
+
+    let total_escrowed = total_escrowed(&*storage)?
+        .checked_add(escrow.value)
+        .ok_or_else(|| StdError::generic_err("Escrowed amount overflow"))?;
+    let mut config = PrefixedStorage::new(StorageKeys::CONFIG, storage);
+    config.set(KEY_ESCROW_COUNT, &(id + 1).to_be_bytes());
+    config.set(KEY_TOTAL_ESCROWED, &total_escrowed.0.to_be_bytes());
+    Ok(id)
+}
+
+/// Returns tokens held in escrows, which are part of total supply
+/// but of no account balance.
+pub fn total_escrowed<S: Storage>(storage: &S) -> StdResult<TokenAmount> {
+    let config = ReadonlyPrefixedStorage::new(StorageKeys::CONFIG, storage);
+    match config.get(KEY_TOTAL_ESCROWED) {
+        Some(bytes) => slice_to_u128(&bytes).map(TokenAmount),
+        None => Ok(TokenAmount(0)),
+    }
+}
+
+pub fn escrow_count<S: Storage>(storage: &S) -> StdResult<u64> {
+    let config = ReadonlyPrefixedStorage::new(StorageKeys::CONFIG, storage);
+    match config.get(KEY_ESCROW_COUNT) {
+        Some(bytes) => slice_to_u64(&bytes),
+        None => Ok(0),
+    }
+}
+
+pub fn get_escrow<S: Storage>(storage: &S, id: u64) -> StdResult<Option<Escrow>> {
+    let escrows = ReadonlyPrefixedStorage::new(StorageKeys::ESCROWS, storage);
+    match escrows.get(&id.to_be_bytes()) {
+        Some(bytes) => deserialize(&bytes).map(Some),
+        None => Ok(None),
+    }
+}
+
+pub fn remove_escrow<S: Storage>(storage: &mut S, id: u64) -> StdResult<()> {
+    let escrow = match get_escrow(&*storage, id)? {
+        Some(escrow) => escrow,
+        None => return Ok(()),
+    };
+    let mut escrows = PrefixedStorage::new(StorageKeys::ESCROWS, storage);
+    escrows.remove(&id.to_be_bytes());
+
+    let total_escrowed = total_escrowed(&*storage)?
+        .checked_sub(escrow.value)
+        .unwrap_or_default();
+    let mut config = PrefixedStorage::new(StorageKeys::CONFIG, storage);
+    config.set(KEY_TOTAL_ESCROWED, &total_escrowed.0.to_be_bytes());
+    Ok(())
+}
+
+pub fn get_account_tag<S: Storage>(
+    storage: &S,
+    account: &CanonicalAddr,
+) -> StdResult<Option<String>> {
+    get_account_value(storage, StorageKeys::ACCOUNT_TAGS, account)
+}
+
+/// Returns accounts tagged with `tag`, in tagging order.
+pub fn get_tag_accounts<S: Storage>(storage: &S, tag: &str) -> StdResult<Vec<CanonicalAddr>> {
+    let tags = ReadonlyPrefixedStorage::new(StorageKeys::TAG_ACCOUNTS, storage);
+    match tags.get(tag.as_bytes()) {
+        Some(bytes) => deserialize(&bytes),
+        None => Ok(vec![]),
+    }
+}
+
+fn set_tag_accounts<S: Storage>(
+    storage: &mut S,
+    tag: &str,
+    accounts: &[CanonicalAddr],
+) -> StdResult<()> {
+    let mut tags = PrefixedStorage::new(StorageKeys::TAG_ACCOUNTS, storage);
+    if accounts.is_empty() {
+        tags.remove(tag.as_bytes());
+    } else {
+        tags.set(tag.as_bytes(), serialize(&accounts)?.as_ref());
+    }
+    Ok(())
+}
+
+/// Tags the account, moving it between tag indexes. `None` removes the tag.
+pub fn set_account_tag<S: Storage>(
+    storage: &mut S,
+    account: &CanonicalAddr,
+    tag: Option<&str>,
+) -> StdResult<()> {
+    if let Some(old_tag) = get_account_tag(&*storage, account)? {
+        let mut accounts = get_tag_accounts(&*storage, &old_tag)?;
+        accounts.retain(|tagged| tagged != account);
+        set_tag_accounts(storage, &old_tag, &accounts)?;
+    }
+    match tag {
+        Some(tag) => {
+            let mut accounts = get_tag_accounts(&*storage, tag)?;
+            accounts.push(account.clone());
+            set_tag_accounts(storage, tag, &accounts)?;
+            set_account_value(storage, StorageKeys::ACCOUNT_TAGS, account, &tag)
+        }
+        None => {
+            remove_account_value(storage, StorageKeys::ACCOUNT_TAGS, account);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::MockStorage;
+    use cosmwasm_std::Binary;
+
+    #[test]
+    fn token_amount_arithmetic() {
+        let a = TokenAmount(69);
+        let b = TokenAmount(9);
+
+        assert_eq!(Some(TokenAmount(78)), a.checked_add(b));
+        assert_eq!(Some(TokenAmount(60)), a.checked_sub(b));
+        assert_eq!(None, b.checked_sub(a));
+        assert_eq!(None, TokenAmount(u128::MAX).checked_add(b));
+        assert_eq!(
+            TokenAmount(u128::MAX),
+            TokenAmount(u128::MAX).saturating_add(b)
+        );
+        assert!(TokenAmount(0).is_zero());
+        assert!(!a.is_zero());
+    }
+
+    #[test]
+    fn token_amount_conversions() {
+        let amount = TokenAmount::from(Uint128(69));
+        assert_eq!(TokenAmount(69), amount);
+        assert_eq!(69, amount.u128());
+
+        let value: Uint128 = amount.into();
+        assert_eq!(Uint128(69), value);
+
+        assert_eq!(TokenAmount(69), TokenAmount::from(69u128));
+    }
+
+    #[test]
+    fn apply_atomic_failure_writes_nothing() {
+        let mut storage = MockStorage::new();
+        storage.set(b"existing", b"old");
+
+        let res: StdResult<()> = apply_atomic(&mut storage, |storage| {
+            for i in 0..5u8 {
+                if i == 3 {
+                    return Err(StdError::generic_err("item failed"));
+                }
+                storage.set(&[i], &[i]);
+                storage.set(b"existing", &[i]);
+                // writes are visible inside the batch
+                assert_eq!(Some(vec![i]), storage.get(&[i]));
+            }
+            Ok(())
+        });
+        assert!(res.is_err());
+
+        for i in 0..5u8 {
+            assert_eq!(None, storage.get(&[i]));
+        }
+        assert_eq!(Some(b"old".to_vec()), storage.get(b"existing"));
+    }
+
+    #[test]
+    fn apply_atomic_success_commits() {
+        let mut storage = MockStorage::new();
+        storage.set(b"removed", b"old");
+
+        apply_atomic(&mut storage, |storage| {
+            storage.set(b"added", b"new");
+            storage.remove(b"removed");
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(Some(b"new".to_vec()), storage.get(b"added"));
+        assert_eq!(None, storage.get(b"removed"));
+    }
+
+    #[test]
+    fn dry_run_writes_nothing() {
+        let mut storage = MockStorage::new();
+        storage.set(b"existing", b"old");
+
+        dry_run(&storage, |storage| {
+            storage.set(b"added", b"new");
+            storage.remove(b"existing");
+            // writes are visible inside the dry run
+            assert_eq!(Some(b"new".to_vec()), storage.get(b"added"));
+            assert_eq!(None, storage.get(b"existing"));
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(None, storage.get(b"added"));
+        assert_eq!(Some(b"old".to_vec()), storage.get(b"existing"));
+    }
+
+    #[test]
+    fn legacy_allowance_deserialization() {
+        let mut storage = MockStorage::new();
+        let owner = CanonicalAddr(Binary(vec![1u8; 20]));
+        let spender = CanonicalAddr(Binary(vec![2u8; 20]));
+
+        let legacy = serialize(&LegacyAllowance {
+            amount: TokenAmount(69),
+        })
+        .unwrap();
+        // allowances written before versioning are stored under the legacy namespace
+        let mut owner_storage = PrefixedStorage::multilevel(
+            &[StorageKeys::LEGACY_ALLOWANCES, owner.as_slice()],
+            &mut storage,
+        );
+        owner_storage.set(spender.as_slice(), &legacy);
+
+        let allowance = get_allowance(&storage, &owner, &spender).unwrap();
+        assert_eq!(TokenAmount(69), allowance.amount);
+        assert_eq!(None, allowance.expiration);
+
+        let allowance = Allowance {
+            amount: TokenAmount(9),
+            expiration: Some(100),
+        };
+        set_allowance(&mut storage, &owner, &spender, allowance.clone()).unwrap();
+        assert_eq!(
+            allowance,
+            get_allowance(&storage, &owner, &spender).unwrap()
+        );
+        assert!(!allowance.is_expired(100));
+        assert!(allowance.is_expired(101));
+    }
+
+    #[test]
+    fn first_received_height_set_once() {
+        let mut storage = MockStorage::new();
+        let account = CanonicalAddr(Binary::from(b"account".to_vec()));
+        assert!(!is_seen(&storage, &account));
+        assert_eq!(None, get_first_received_height(&storage, &account).unwrap());
+
+        mark_seen(&mut storage, &account, 100);
+        mark_seen(&mut storage, &account, 200);
+        assert!(is_seen(&storage, &account));
+        assert_eq!(
+            Some(100),
+            get_first_received_height(&storage, &account).unwrap()
+        );
+    }
+
+    #[test]
+    fn storage_keys_versioned() {
+        let namespaces = [
+            StorageKeys::CONFIG,
+            StorageKeys::BALANCES,
+            StorageKeys::ALLOWANCES,
+            StorageKeys::TX_COUNTS,
+            StorageKeys::SEEN,
+            StorageKeys::GUARDIANS,
+            StorageKeys::RECOVERIES,
+            StorageKeys::LARGE_TRANSFER_THRESHOLDS,
+            StorageKeys::PENDING_TRANSFERS,
+            StorageKeys::PENDING_TRANSFER_IDS,
+            StorageKeys::RECEIVE_OPT,
+            StorageKeys::BURN_ALLOWANCES,
+            StorageKeys::QUEUED_TRANSFERS,
+            StorageKeys::ALLOWANCE_INDEX,
+            StorageKeys::REWARD_DEBTS,
+            StorageKeys::REGISTERED,
+            StorageKeys::ALLOWANCE_SPENDERS,
+            StorageKeys::ALLOWANCE_EXPIRY_DEFAULTS,
+            StorageKeys::FIRST_RECEIVED,
+            StorageKeys::CLAIM_CODES,
+            StorageKeys::LOCKED,
+            StorageKeys::FALLBACK_RECIPIENTS,
+            StorageKeys::ESCROWS,
+            StorageKeys::ACCOUNT_TAGS,
+            StorageKeys::TAG_ACCOUNTS,
+            StorageKeys::ALLOWANCE_POSITIONS,
+        ];
+        for (i, namespace) in namespaces.iter().enumerate() {
+            assert_eq!(STORAGE_VERSION, namespace[0]);
+            assert!(!namespaces[i + 1..].contains(namespace));
+        }
+        assert_eq!(b"\x01balances", StorageKeys::BALANCES);
+    }
+
+    #[test]
+    fn storage_keys_stored_with_version() {
+        let mut storage = MockStorage::new();
+        let account = CanonicalAddr(Binary::from(b"account".to_vec()));
+        Balances::from_storage(&mut storage).set_balance(&account, TokenAmount(42));
+        assert_eq!(
+            TokenAmount(42),
+            ReadOnlyBalances::from_storage(&storage).balance(&account)
+        );
+
+        // prefixed storage keys are the length prefixed namespace followed by the key
+        let mut key = vec![0, StorageKeys::BALANCES.len() as u8, STORAGE_VERSION];
+        key.extend_from_slice(b"balances");
+        key.extend_from_slice(account.as_slice());
+        assert_eq!(Some(42u128.to_be_bytes().to_vec()), storage.get(&key));
+    }
+
+    #[test]
+    fn legacy_namespaces_fallback() {
+        let mut storage = MockStorage::new();
+        let account = CanonicalAddr(Binary(vec![1u8; 20]));
+        let spender = CanonicalAddr(Binary(vec![2u8; 20]));
+
+        PrefixedStorage::new(StorageKeys::LEGACY_CONFIG, &mut storage)
+            .set(KEY_TOTAL_SUPPLY, &100u128.to_be_bytes());
+        PrefixedStorage::new(StorageKeys::LEGACY_BALANCES, &mut storage)
+            .set(account.as_slice(), &42u128.to_be_bytes());
+        assert_eq!(
+            TokenAmount(100),
+            ReadOnlyContractStorage::from_storage(&storage)
+                .total_supply()
+                .unwrap()
+        );
+        assert_eq!(
+            TokenAmount(42),
+            ReadOnlyBalances::from_storage(&storage).balance(&account)
+        );
+
+        // writes go to the versioned namespace and take precedence
+        ContractStorage::from_storage(&mut storage)
+            .set_total_supply(TokenAmount(90))
+            .unwrap();
+        Balances::from_storage(&mut storage).set_balance(&account, TokenAmount(32));
+        assert_eq!(
+            TokenAmount(90),
+            ReadOnlyContractStorage::from_storage(&storage)
+                .total_supply()
+                .unwrap()
+        );
+        assert_eq!(
+            TokenAmount(32),
+            ReadOnlyBalances::from_storage(&storage).balance(&account)
+        );
+        assert_eq!(
+            Some(42u128.to_be_bytes().to_vec()),
+            ReadonlyPrefixedStorage::new(StorageKeys::LEGACY_BALANCES, &storage)
+                .get(account.as_slice())
+        );
+
+        // removed allowance does not fall back to the legacy one
+        let legacy = serialize(&LegacyAllowance {
+            amount: TokenAmount(69),
+        })
+        .unwrap();
+        PrefixedStorage::multilevel(
+            &[StorageKeys::LEGACY_ALLOWANCES, account.as_slice()],
+            &mut storage,
+        )
+        .set(spender.as_slice(), &legacy);
+        remove_allowance(&mut storage, &account, &spender).unwrap();
+        assert_eq!(
+            Allowance::default(),
+            get_allowance(&storage, &account, &spender).unwrap()
+        );
+    }
+
+    #[test]
+    fn constants_bincode_and_json_deserialization() {
+        let constants = Constants {
+            name: "test".to_string(),
+            symbol: "TEST".to_string(),
+            decimals: 6,
+            token_id: "test-token".to_string(),
+            owner: CanonicalAddr(Binary(vec![1u8; 20])),
+            contract_address: CanonicalAddr(Binary(vec![2u8; 20])),
+            reserve: None,
+            verbose_logging: true,
+            recovery_delay: 100,
+            max_batch_size: 10,
+            metadata_uri: Some("ipfs://metadata".to_string()),
+            reject_value_above_supply: false,
+            created_height: 12345,
+            created_time: 1571797419,
+            max_wallet_balance: None,
+            treat_zero_address_as_burn: false,
+            metadata_frozen: false,
+            require_registration: false,
+            mint_admin: None,
+            default_allowance_expiry: Some(1000),
+            min_holding_period: None,
+            zero_approve_revokes: false,
+            clamp_transfer_from: false,
+            price_oracle: None,
+            burn_dust_threshold: None,
+            min_total_supply: TokenAmount(0),
+            emit_sequence: false,
+        };
+
+        let mut storage = MockStorage::new();
+        ContractStorage::from_storage(&mut storage)
+            .set_constants(&constants)
+            .unwrap();
+        assert_eq!(
+            constants,
+            ReadOnlyContractStorage::from_storage(&storage)
+                .constants()
+                .unwrap()
+        );
+
+        let json = cosmwasm_std::to_vec(&constants).unwrap();
+        PrefixedStorage::new(StorageKeys::CONFIG, &mut storage).set(KEY_CONSTANTS, &json);
+        assert_eq!(
+            constants,
+            ReadOnlyContractStorage::from_storage(&storage)
+                .constants()
+                .unwrap()
+        );
+
+        PrefixedStorage::new(StorageKeys::CONFIG, &mut storage).set(KEY_CONSTANTS, b"corrupted");
+        assert!(ReadOnlyContractStorage::from_storage(&storage)
+            .constants()
+            .is_err());
+    }
+}